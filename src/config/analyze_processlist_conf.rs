@@ -0,0 +1,87 @@
+use crate::config::show_processlist_conf::{OUTPUT_FORMAT_JSONL, OUTPUT_FORMAT_TEXT};
+use crate::error::CustomError;
+use crate::utils;
+use chrono::NaiveDateTime;
+use clap::Args;
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_START_TIME: &str = "";
+const DEFAULT_END_TIME: &str = "";
+const DEFAULT_THRESHOLD: u64 = 0;
+const DEFAULT_INCIDENT_GAP: i64 = 60;
+const DEFAULT_TOP: usize = 5;
+const DEFAULT_OUTPUT_FORMAT: &str = OUTPUT_FORMAT_TEXT;
+const DEFAULT_LOG_LEVEL: &str = "info";
+
+#[derive(Args, Debug, Serialize, Deserialize, Clone)]
+pub struct AnalyzeProcesslistConf {
+    #[arg(
+        long,
+        action = clap::ArgAction::Append,
+        required = true,
+        help = "需要分析的 show-processlist 输出文件或目录, 目录会递归查找 .txt .log .jsonl 以及压缩后的 .gz 文件, 可指定多个. --merged-view 输出的文件只支持 jsonl 格式"
+    )]
+    pub input: Vec<String>,
+    #[arg(long, default_value_t = String::from(DEFAULT_START_TIME), help = "只分析该时间之后的快照, 格式: 2023-01-31 16:00:00")]
+    pub start_time: String,
+    #[arg(long, default_value_t = String::from(DEFAULT_END_TIME), help = "只分析该时间之前的快照, 格式: 2023-01-31 17:00:00")]
+    pub end_time: String,
+    #[arg(long, default_value_t = DEFAULT_THRESHOLD, help = "快照中的线程数达到多少才算超过阈值, 0 表示所有快照都算")]
+    pub threshold: u64,
+    #[arg(long, default_value_t = DEFAULT_INCIDENT_GAP, help = "同一个实例两个超过阈值的快照间隔不超过多久算同一次事件(单位:s)")]
+    pub incident_gap: i64,
+    #[arg(long, default_value_t = DEFAULT_TOP, help = "每次事件输出多少个 sql 指纹, 用户, 客户端ip")]
+    pub top: usize,
+    #[arg(long, default_value_t = String::from(DEFAULT_OUTPUT_FORMAT), help = "分析结果输出格式: text, jsonl(每个事件一行json)")]
+    pub output_format: String,
+    #[arg(long, default_value_t = String::from(DEFAULT_LOG_LEVEL), help = "日志级别")]
+    pub log_level: String,
+}
+
+impl AnalyzeProcesslistConf {
+    pub fn check(&self) -> Result<(), CustomError> {
+        if self.output_format != OUTPUT_FORMAT_TEXT && self.output_format != OUTPUT_FORMAT_JSONL {
+            return Err(CustomError::new(format!(
+                "不支持的输出格式: {output_format}, 可选值: {text}, {jsonl}",
+                output_format = &self.output_format,
+                text = OUTPUT_FORMAT_TEXT,
+                jsonl = OUTPUT_FORMAT_JSONL,
+            )));
+        }
+
+        self.get_start_time()?;
+        self.get_end_time()?;
+
+        Ok(())
+    }
+
+    pub fn get_start_time(&self) -> Result<Option<NaiveDateTime>, CustomError> {
+        parse_time("--start-time", &self.start_time)
+    }
+
+    pub fn get_end_time(&self) -> Result<Option<NaiveDateTime>, CustomError> {
+        parse_time("--end-time", &self.end_time)
+    }
+
+    pub fn is_jsonl(&self) -> bool {
+        self.output_format == OUTPUT_FORMAT_JSONL
+    }
+}
+
+pub fn parse_time(name: &str, value: &str) -> Result<Option<NaiveDateTime>, CustomError> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+
+    NaiveDateTime::parse_from_str(value, utils::time::NORMAL_FMT)
+        .map(Some)
+        .map_err(|e| {
+            CustomError::new(format!(
+                "{name} 时间格式不正确: {value}, 格式: {fmt}. {e}",
+                name = name,
+                value = value,
+                fmt = utils::time::NORMAL_FMT,
+                e = e
+            ))
+        })
+}
@@ -1,5 +1,9 @@
+use crate::config::analyze_processlist_conf::AnalyzeProcesslistConf;
+use crate::config::lock_waits_conf::LockWaitsConf;
+use crate::config::processlist_history_conf::ProcesslistHistoryConf;
 use crate::config::show_index_conf::ShowIndexConf;
 use crate::config::show_processlist_conf::ShowProcesslistConf;
+use crate::config::top_conf::TopConf;
 use clap::{Parser, Subcommand};
 
 #[derive(Debug, Parser)]
@@ -27,19 +31,45 @@ pub enum Commands {
     --easydb-password="WmlPc3JSY295bTduTUFVZElpx3Z5jRDQHK4vz9T65kQ6Zkz4j/08nnapTpEqATmc" \
     --easydb-database="easydb" \
     --sleep=1000 \
+    --incident-sleep=200 \
     --print-cnt-threshold=50 \
+    --processlist-source="auto" \
+    --threshold-filter="command not in ('Sleep', 'Binlog Dump', 'Binlog Dump GTID', 'Daemon') and user not in ('system user', 'monitor')" \
+    --output-filter="command != 'Sleep'" \
+    --threshold-override-file="conf/threshold_override.json" \
+    --config-file="conf/show_processlist.json" \
     --all \
     --product-instance-duration=21600 \
     --output-dir="./processlist_files" \
     --clear-file-duration=172800 \
     --ignore-instances="localhost:3306" \
     --ignore-instances="localhost:3307" \
+    --connection-budget=2000 \
+    --connect-rate=50 \
+    --poll-jitter=200 \
+    --reconnect-backoff-initial=1000 \
+    --reconnect-backoff-max=60000 \
+    --down-threshold=3 \
+    --health-log-interval=60 \
+    --output-layout="cluster" \
+    --rotate-size=100 \
+    --rotate-compress \
+    --rotate-keep-days=7 \
+    --output-format="text" \
+    --fingerprint-summary \
+    --group-summary \
+    --metrics-addr="0.0.0.0:9104" \
+    --alert-webhook-url="https://oapi.dingtalk.com/robot/send?access_token=xxx" \
+    --alert-webhook-type="dingtalk" \
+    --alert-cooldown=600 \
+    --history-store="sqlite" \
+    --history-sqlite-file="processlist_history.db" \
     --is-sql-log \
     --log-file="logs/show_processlist.log" \
     --log-level="info"
     "#
     )]
-    ShowProcesslist(ShowProcesslistConf),
+    ShowProcesslist(Box<ShowProcesslistConf>),
 
     #[command(
         about = "执行 SHOW INDEX 语句",
@@ -60,5 +90,85 @@ pub enum Commands {
     --log-level="info"
 "#
     )]
-    ShowIndex(ShowIndexConf),
+    ShowIndex(Box<ShowIndexConf>),
+
+    #[command(
+        about = "查看锁等待, 输出 阻塞者 -> 等待者 的树形结构",
+        long_about = r#"
+示例:
+./target/release/mysql-tool-rs lock-waits \
+    --username="root" \
+    --password="NHJtbG91cVdmVjIxTWpLTLr7hJX88U1EC1maABXZJoI=" \
+    --easydb-host="127.0.0.1" \
+    --easydb-port=3306 \
+    --easydb-username="yh_easydb" \
+    --easydb-password="WmlPc3JSY295bTduTUFVZElpx3Z5jRDQHK4vz9T65kQ6Zkz4j/08nnapTpEqATmc" \
+    --easydb-database="easydb" \
+    --vip-port="127.0.0.1:3306" \
+    --is-sql-log \
+    --log-level="info"
+"#
+    )]
+    LockWaits(Box<LockWaitsConf>),
+
+    #[command(
+        about = "离线分析 show-processlist 保存的文件, 按实例输出每次事件的峰值, 持续时间, top sql 指纹, 用户, 客户端ip",
+        long_about = r#"
+示例:
+./target/release/mysql-tool-rs analyze-processlist \
+    --input="./processlist_files" \
+    --input="./processlist_files/127.0.0.1_3306.txt" \
+    --start-time="2023-01-31 16:00:00" \
+    --end-time="2023-01-31 17:00:00" \
+    --threshold=50 \
+    --incident-gap=60 \
+    --top=5 \
+    --output-format="text" \
+    --log-level="info"
+"#
+    )]
+    AnalyzeProcesslist(Box<AnalyzeProcesslistConf>),
+
+    #[command(
+        about = "全屏实时查看 processlist, 支持排序, 过滤, 暂停, 查看完整 sql, KILL QUERY",
+        long_about = r#"
+示例:
+./target/release/mysql-tool-rs top \
+    --username="root" \
+    --password="NHJtbG91cVdmVjIxTWpLTLr7hJX88U1EC1maABXZJoI=" \
+    --easydb-host="127.0.0.1" \
+    --easydb-port=3306 \
+    --easydb-username="yh_easydb" \
+    --easydb-password="WmlPc3JSY295bTduTUFVZElpx3Z5jRDQHK4vz9T65kQ6Zkz4j/08nnapTpEqATmc" \
+    --easydb-database="easydb" \
+    --vip-port="127.0.0.1:3306" \
+    --refresh=2000 \
+    --sort="time" \
+    --filter="command != 'Sleep'"
+
+按键:
+    q/Esc/Ctrl-C: 退出    ↑/↓/PgUp/PgDn: 选择线程    t/u/d: 按 time/user/db 排序, 再按一次反向
+    /: 修改过滤表达式    p/空格: 暂停/继续刷新    Enter: 查看完整 sql    k: KILL QUERY 选中的线程
+"#
+    )]
+    Top(Box<TopConf>),
+
+    #[command(
+        about = "查询 show-processlist --history-store 保存的历史快照, 按实例, 时间范围, 用户, sql 过滤",
+        long_about = r#"
+示例:
+./target/release/mysql-tool-rs processlist-history \
+    --history-store="sqlite" \
+    --history-sqlite-file="processlist_history.db" \
+    --instance="127.0.0.1:3306" \
+    --start-time="2023-01-31 16:00:00" \
+    --end-time="2023-01-31 17:00:00" \
+    --user="app" \
+    --sql="order_info" \
+    --limit=1000 \
+    --output-format="text" \
+    --log-level="info"
+"#
+    )]
+    ProcesslistHistory(Box<ProcesslistHistoryConf>),
 }
@@ -1,6 +1,10 @@
+pub mod analyze_processlist_conf;
 pub mod config;
+pub mod lock_waits_conf;
+pub mod processlist_history_conf;
 pub mod show_index_conf;
 pub mod show_processlist_conf;
+pub mod top_conf;
 
 pub use config::Commands;
 pub use config::Config;
@@ -0,0 +1,113 @@
+use crate::config::analyze_processlist_conf::parse_time;
+use crate::config::show_processlist_conf::{
+    self, DEFAULT_HISTORY_SQLITE_FILE, DEFAULT_HISTORY_TABLE, HISTORY_STORE_SQLITE,
+    OUTPUT_FORMAT_JSONL, OUTPUT_FORMAT_TEXT,
+};
+use crate::error::CustomError;
+use chrono::NaiveDateTime;
+use clap::Args;
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_EASYDB_USERNAME: &str = "yh_easydb";
+const DEFAULT_EASYDB_PASSWORD: &str =
+    "WmlPc3JSY295bTduTUFVZElpx3Z5jRDQHK4vz9T65kQ6Zkz4j/08nnapTpEqATmc";
+const DEFAULT_EASYDB_HOST: &str = "127.0.0.1";
+const DEFAULT_EASYDB_PORT: u16 = 3306;
+const DEFAULT_EASYDB_DATABASE: &str = "easydb";
+const DEFAULT_HISTORY_STORE: &str = HISTORY_STORE_SQLITE;
+const DEFAULT_HISTORY_DATABASE: &str = "";
+const DEFAULT_INSTANCE: &str = "";
+const DEFAULT_START_TIME: &str = "";
+const DEFAULT_END_TIME: &str = "";
+const DEFAULT_USER: &str = "";
+const DEFAULT_SQL: &str = "";
+const DEFAULT_LIMIT: u64 = 1000;
+const DEFAULT_OUTPUT_FORMAT: &str = OUTPUT_FORMAT_TEXT;
+const DEFAULT_LOG_LEVEL: &str = "info";
+const DEFAULT_IS_SQL_LOG: bool = false;
+
+#[derive(Args, Debug, Serialize, Deserialize, Clone)]
+pub struct ProcesslistHistoryConf {
+    #[arg(long, default_value_t = String::from(DEFAULT_HISTORY_STORE), help = "历史库类型, 和 show-processlist --history-store 相同: sqlite, mysql")]
+    pub history_store: String,
+    #[arg(long, default_value_t = String::from(DEFAULT_HISTORY_SQLITE_FILE), help = "--history-store=sqlite 时历史快照的 sqlite 文件")]
+    pub history_sqlite_file: String,
+    #[arg(long, default_value_t = String::from(DEFAULT_HISTORY_DATABASE), help = "--history-store=mysql 时历史快照的数据库, 使用 easydb 的链接信息, 不指定则使用 --easydb-database")]
+    pub history_database: String,
+    #[arg(long, default_value_t = String::from(DEFAULT_HISTORY_TABLE), help = "历史快照的表名")]
+    pub history_table: String,
+    #[arg(long, default_value_t = String::from(DEFAULT_EASYDB_USERNAME), help = "easydb 数据库用户名")]
+    pub easydb_username: String,
+    #[arg(long, default_value_t = String::from(DEFAULT_EASYDB_PASSWORD), help = "easydb 数据库密码")]
+    pub easydb_password: String,
+    #[arg(long, default_value_t = String::from(DEFAULT_EASYDB_HOST), help = "easydb 数据库地址")]
+    pub easydb_host: String,
+    #[arg(long, default_value_t = DEFAULT_EASYDB_PORT, help = "easydb 数据库端口")]
+    pub easydb_port: u16,
+    #[arg(long, default_value_t = String::from(DEFAULT_EASYDB_DATABASE), help = "easydb链接的数据库名")]
+    pub easydb_database: String,
+    #[arg(long, default_value_t = String::from(DEFAULT_INSTANCE), help = "只查询该实例, 格式: host:port")]
+    pub instance: String,
+    #[arg(long, default_value_t = String::from(DEFAULT_START_TIME), help = "只查询该时间之后的快照, 格式: 2023-01-31 16:00:00")]
+    pub start_time: String,
+    #[arg(long, default_value_t = String::from(DEFAULT_END_TIME), help = "只查询该时间之前的快照, 格式: 2023-01-31 17:00:00")]
+    pub end_time: String,
+    #[arg(long, default_value_t = String::from(DEFAULT_USER), help = "只查询该用户的线程")]
+    pub user: String,
+    #[arg(long, default_value_t = String::from(DEFAULT_SQL), help = "只查询 sql 中包含该字符串的线程")]
+    pub sql: String,
+    #[arg(long, default_value_t = DEFAULT_LIMIT, help = "最多输出多少条最新的记录")]
+    pub limit: u64,
+    #[arg(long, default_value_t = String::from(DEFAULT_OUTPUT_FORMAT), help = "查询结果输出格式: text, jsonl(每条记录一行json)")]
+    pub output_format: String,
+    #[arg(long, default_value_t = String::from(DEFAULT_LOG_LEVEL), help = "日志级别")]
+    pub log_level: String,
+    #[arg(long, default_value_t = DEFAULT_IS_SQL_LOG, help = "执行sql是否打印日志")]
+    pub is_sql_log: bool,
+}
+
+impl ProcesslistHistoryConf {
+    pub fn check(&self) -> Result<(), CustomError> {
+        show_processlist_conf::check_history(&self.history_store, &self.history_table)?;
+
+        if self.output_format != OUTPUT_FORMAT_TEXT && self.output_format != OUTPUT_FORMAT_JSONL {
+            return Err(CustomError::new(format!(
+                "不支持的输出格式: {output_format}, 可选值: {text}, {jsonl}",
+                output_format = &self.output_format,
+                text = OUTPUT_FORMAT_TEXT,
+                jsonl = OUTPUT_FORMAT_JSONL,
+            )));
+        }
+
+        self.get_start_time()?;
+        self.get_end_time()?;
+
+        Ok(())
+    }
+
+    pub fn get_start_time(&self) -> Result<Option<NaiveDateTime>, CustomError> {
+        parse_time("--start-time", &self.start_time)
+    }
+
+    pub fn get_end_time(&self) -> Result<Option<NaiveDateTime>, CustomError> {
+        parse_time("--end-time", &self.end_time)
+    }
+
+    pub fn get_history_mysql_dsn(&self) -> String {
+        show_processlist_conf::get_history_mysql_dsn(
+            &self.easydb_username,
+            &self.easydb_password,
+            &self.easydb_host,
+            self.easydb_port,
+            if self.history_database.is_empty() {
+                &self.easydb_database
+            } else {
+                &self.history_database
+            },
+        )
+    }
+
+    pub fn is_jsonl(&self) -> bool {
+        self.output_format == OUTPUT_FORMAT_JSONL
+    }
+}
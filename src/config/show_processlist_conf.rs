@@ -1,5 +1,6 @@
 use crate::error::CustomError;
 use crate::utils::peep;
+use crate::utils::rotate_file::RotatePolicy;
 use clap::Args;
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
@@ -19,11 +20,81 @@ const DEFAULT_LOG_FILE_SHOW_PROCESSLIT: &str = "logs/show_processlist.log";
 const DEFAULT_LOG_LEVEL_SHOW_PROCESSLIT: &str = "info";
 const DEFAULT_VIP_PORT_SHOW_PROCESSLIT: &str = "";
 const DEFAULT_ALL: bool = false;
+const DEFAULT_MERGED_VIEW: bool = false;
 const DEFAULT_OUTPUT_DIR: &str = "./processlist_files";
 const DEFAULT_PRODUCT_INSTANCE_DURATION: u64 = 6 * 60 * 60;
 const DEFAULT_SLEEP_SHOW_PROCESSLIT: u64 = 1000; // 单位毫秒
+const DEFAULT_INCIDENT_SLEEP: u64 = 0; // 单位毫秒
 const DEFAULT_PRINT_CNT_THRESHOLD: u64 = 50;
 const DEFAULT_CLEAR_FILE_DURATION: i64 = 2 * 24 * 60 * 60; // 清理文件的时间
+const DEFAULT_PROCESSLIST_SOURCE: &str = PROCESSLIST_SOURCE_AUTO;
+const DEFAULT_THRESHOLD_FILTER: &str = "command != 'Sleep' and user != 'system user'";
+const DEFAULT_OUTPUT_FILTER: &str = "command != 'Sleep'";
+const DEFAULT_THRESHOLD_OVERRIDE_FILE: &str = "";
+const DEFAULT_CONFIG_FILE: &str = "";
+const DEFAULT_OUTPUT_FORMAT: &str = OUTPUT_FORMAT_TEXT;
+const DEFAULT_OUTPUT_LAYOUT: &str = OUTPUT_LAYOUT_FLAT;
+const DEFAULT_CONNECTION_BUDGET: usize = 0;
+const DEFAULT_CONNECT_RATE: u32 = 0;
+const DEFAULT_POLL_JITTER: u64 = 0; // 单位毫秒
+const DEFAULT_RECONNECT_BACKOFF_INITIAL: u64 = 1000; // 单位毫秒
+const DEFAULT_RECONNECT_BACKOFF_MAX: u64 = 60 * 1000; // 单位毫秒
+const DEFAULT_DOWN_THRESHOLD: u64 = 3;
+const DEFAULT_HEALTH_LOG_INTERVAL: u64 = 60;
+const DEFAULT_ROTATE_SIZE: u64 = 0; // 单位MB
+const DEFAULT_ROTATE_DAILY: bool = false;
+const DEFAULT_ROTATE_COMPRESS: bool = false;
+const DEFAULT_ROTATE_KEEP_FILES: usize = 0;
+const DEFAULT_ROTATE_KEEP_DAYS: u64 = 0;
+const DEFAULT_FINGERPRINT_SUMMARY: bool = false;
+const DEFAULT_FINGERPRINT_SUMMARY_LIMIT: usize = 10;
+const DEFAULT_GROUP_SUMMARY: bool = false;
+const DEFAULT_GROUP_SUMMARY_LIMIT: usize = 10;
+const DEFAULT_TRACK_QUERIES: bool = false;
+const DEFAULT_TRACK_MIN_TIME: u64 = 1;
+const DEFAULT_DIAGNOSTIC: bool = false;
+const DEFAULT_DIAGNOSTIC_INTERVAL: u64 = 5 * 60;
+const DEFAULT_LOCK_WAITS: bool = false;
+const DEFAULT_PRE_TRIGGER_SNAPSHOTS: usize = 0;
+const DEFAULT_SATURATION_CONNECTED_PCT: u64 = 0;
+const DEFAULT_SATURATION_RUNNING_PCT: u64 = 0;
+const DEFAULT_SATURATION_TOP: usize = 10;
+const DEFAULT_EXPLAIN_MIN_TIME: u64 = 0;
+const DEFAULT_EXPLAIN_WINDOW: u64 = 10 * 60;
+const DEFAULT_EXPLAIN_LIMIT: usize = 5;
+const DEFAULT_METRICS_ADDR: &str = "";
+const DEFAULT_ALERT_WEBHOOK_URL: &str = "";
+const DEFAULT_ALERT_WEBHOOK_TYPE: &str = ALERT_WEBHOOK_TYPE_GENERIC;
+const DEFAULT_ALERT_THRESHOLD: u64 = 0; // 0 使用 --print-cnt-threshold
+const DEFAULT_ALERT_COOLDOWN: u64 = 10 * 60;
+const DEFAULT_ALERT_TOP_FINGERPRINTS: usize = 5;
+const DEFAULT_ALERT_TIMEOUT: u64 = 5;
+const DEFAULT_KILL: bool = false;
+const DEFAULT_KILL_EXECUTE: bool = false; // 默认 dry-run 只记录不执行
+const DEFAULT_KILL_TYPE: &str = KILL_TYPE_QUERY;
+const DEFAULT_KILL_MIN_TIME: u64 = 0;
+const DEFAULT_KILL_INFO_REGEX: &str = "";
+const DEFAULT_KILL_AUDIT_FILE: &str = "logs/kill_audit.log";
+const DEFAULT_HISTORY_STORE: &str = "";
+pub const DEFAULT_HISTORY_SQLITE_FILE: &str = "processlist_history.db";
+const DEFAULT_HISTORY_DATABASE: &str = "";
+pub const DEFAULT_HISTORY_TABLE: &str = "processlist_history";
+
+pub const PROCESSLIST_SOURCE_AUTO: &str = "auto";
+pub const PROCESSLIST_SOURCE_INFORMATION_SCHEMA: &str = "information_schema";
+pub const PROCESSLIST_SOURCE_PS_PROCESSLIST: &str = "performance_schema.processlist";
+pub const PROCESSLIST_SOURCE_PS_THREADS: &str = "performance_schema.threads";
+pub const OUTPUT_FORMAT_TEXT: &str = "text";
+pub const OUTPUT_FORMAT_JSONL: &str = "jsonl";
+pub const OUTPUT_LAYOUT_FLAT: &str = "flat";
+pub const OUTPUT_LAYOUT_CLUSTER: &str = "cluster";
+pub const ALERT_WEBHOOK_TYPE_GENERIC: &str = "generic";
+pub const ALERT_WEBHOOK_TYPE_DINGTALK: &str = "dingtalk";
+pub const ALERT_WEBHOOK_TYPE_WECOM: &str = "wecom";
+pub const KILL_TYPE_QUERY: &str = "query";
+pub const KILL_TYPE_CONNECTION: &str = "connection";
+pub const HISTORY_STORE_SQLITE: &str = "sqlite";
+pub const HISTORY_STORE_MYSQL: &str = "mysql";
 
 #[derive(Args, Debug, Serialize, Deserialize, Clone)]
 pub struct ShowProcesslistConf {
@@ -37,6 +108,8 @@ pub struct ShowProcesslistConf {
     pub port: u16,
     #[arg(long, default_value_t = String::from(DEFAULT_VIP_PORT_SHOW_PROCESSLIT), help = "需要执行 show processlist 数据库地址, 如果指定了 --host --port 参数则忽略该参数")]
     pub vip_port: String,
+    #[arg(long, default_value_t = DEFAULT_MERGED_VIEW, help = "在指定 --vip-port 时, 集群所有实例同步执行 SHOW PROCESSLIST, 任意实例超过阈值时合并输出一个表格(包含实例和角色列, 每个实例的小计以及一行集群汇总). 不支持 --pre-trigger-snapshots, --explain-min-time, --lock-waits, --fingerprint-summary, --group-summary, 同时指定时报错. 合并输出的 text 格式不能被 analyze-processlist 解析, 需要分析时使用 --output-format=jsonl")]
+    pub merged_view: bool,
     #[arg(long, default_value_t = String::from(DEFAULT_EASYDB_USERNAME), help = "easydb 数据库用户名")]
     pub easydb_username: String,
     #[arg(long, default_value_t = String::from(DEFAULT_EASYDB_PASSWORD), help = "easydb 数据库密码")]
@@ -49,15 +122,27 @@ pub struct ShowProcesslistConf {
     pub easydb_database: String,
     #[arg(long, default_value_t = DEFAULT_SLEEP_SHOW_PROCESSLIT, help = "循环执行 SHOW PROCESSLIST 中间需要睡眠多久(单位:ms)")]
     pub sleep: u64,
+    #[arg(long, default_value_t = DEFAULT_INCIDENT_SLEEP, help = "满足 --threshold-filter 的线程数超过 --print-cnt-threshold 时, 循环执行 SHOW PROCESSLIST 的间隔缩短为多少毫秒, 恢复后间隔每次翻倍直到 --sleep, 0 不开启(单位:ms)")]
+    pub incident_sleep: u64,
     #[arg(long, default_value_t = DEFAULT_PRINT_CNT_THRESHOLD, help = "SHOW PROCESSLIST返回多少数据需要打印到日志文件")]
     pub print_cnt_threshold: u64,
+    #[arg(long, default_value_t = String::from(DEFAULT_PROCESSLIST_SOURCE), help = "processlist 数据来源: auto(根据版本自动选择, 8.0.22+ 使用 performance_schema.processlist, 5.7/8.0 使用 performance_schema.threads, 不可用时使用 information_schema), information_schema, performance_schema.processlist, performance_schema.threads")]
+    pub processlist_source: String,
+    #[arg(long, default_value_t = String::from(DEFAULT_THRESHOLD_FILTER), help = "过滤表达式, 满足条件的线程才计入 --print-cnt-threshold 和告警阈值. 支持字段: id, user, host, db, command, time, state, info, thread_id, digest, execution_engine, 支持: = != <> > >= < <= [not] in, [not] like, [not] regexp, is [not] null, and, or, not, 括号")]
+    pub threshold_filter: String,
+    #[arg(long, default_value_t = String::from(DEFAULT_OUTPUT_FILTER), help = "过滤表达式, 满足条件的线程才输出到快照, 语法同 --threshold-filter")]
+    pub output_filter: String,
+    #[arg(long, default_value_t = String::from(DEFAULT_THRESHOLD_OVERRIDE_FILE), help = "阈值覆盖配置文件(json), 按集群名, 业务线, set名, 角色, host:port 覆盖 print_cnt_threshold, sleep, alert_threshold")]
+    pub threshold_override_file: String,
+    #[arg(long, default_value_t = String::from(DEFAULT_CONFIG_FILE), help = "配置文件(json), 配置项和命令行参数相同(使用下划线), 覆盖命令行参数. 收到 SIGHUP 或者文件修改后重新加载, 阈值, 过滤表达式, 告警, kill 规则, 忽略实例等配置不需要重新链接实例即可生效")]
+    pub config_file: String,
     #[arg(long, default_value_t = DEFAULT_ALL, help = "所有集群对实例进行 SHOW PROCESSLIST")]
     pub all: bool,
     #[arg(long, default_value_t = String::from(DEFAULT_OUTPUT_DIR), help = "在使用 --all 参数时每个实例的信息输出到哪个目录")]
     pub output_dir: String,
     #[arg(long, default_value_t = DEFAULT_PRODUCT_INSTANCE_DURATION, help = "在指定 --all 参数时, 多久进行重新生成一次实例(单位:s)")]
     pub product_instance_duration: u64,
-    #[arg(long, default_value_t = DEFAULT_CLEAR_FILE_DURATION, help = "在指定 --all 参数时, 保存processlist信息文件多久清理一次(单位:s)")]
+    #[arg(long, default_value_t = DEFAULT_CLEAR_FILE_DURATION, help = "在指定 --all 参数时, 保存processlist信息文件多久清理一次(单位:s), 开启文件滚动后不再清理")]
     pub clear_file_duration: i64,
     #[arg(
         long,
@@ -65,6 +150,124 @@ pub struct ShowProcesslistConf {
         help = "在指定 --all 参数时, 忽略哪些实例不进行手机 processlist 信息"
     )]
     pub ignore_instances: Vec<String>,
+    #[arg(long, default_value_t = DEFAULT_CONNECTION_BUDGET, help = "在指定 --all 参数时, 所有实例最多同时打开多少个数据库链接(包含采集诊断信息的链接), 每个实例一直占用一个链接, 小于实例数时启动失败, 0 不限制")]
+    pub connection_budget: usize,
+    #[arg(long, default_value_t = DEFAULT_CONNECT_RATE, help = "在指定 --all 参数时, 每秒最多新建多少个实例链接, 避免启动时同时链接所有实例, 0 不限制")]
+    pub connect_rate: u32,
+    #[arg(long, default_value_t = DEFAULT_POLL_JITTER, help = "每次 processlist 之间在 --sleep 的基础上随机增加 0 ~ poll_jitter 毫秒, 避免所有实例在同一时间执行(单位:ms)")]
+    pub poll_jitter: u64,
+    #[arg(long, default_value_t = DEFAULT_RECONNECT_BACKOFF_INITIAL, help = "链接或 processlist 失败后第一次重试等待时间, 之后每次失败翻倍并加上随机值(单位:ms)")]
+    pub reconnect_backoff_initial: u64,
+    #[arg(long, default_value_t = DEFAULT_RECONNECT_BACKOFF_MAX, help = "失败重试最长等待时间(单位:ms)")]
+    pub reconnect_backoff_max: u64,
+    #[arg(long, default_value_t = DEFAULT_DOWN_THRESHOLD, help = "processlist 连续失败多少次认为实例 down, 关闭链接后重新链接")]
+    pub down_threshold: u64,
+    #[arg(long, default_value_t = DEFAULT_HEALTH_LOG_INTERVAL, help = "多久输出一次各个健康状态(connecting, healthy, degraded, down)的实例数(单位:s), 0 不输出")]
+    pub health_log_interval: u64,
+    #[arg(long, default_value_t = String::from(DEFAULT_OUTPUT_LAYOUT), help = "在指定 --all 参数时, 输出文件的目录结构: flat(output_dir/host_port.txt), cluster(output_dir/集群名/host_port/日期.log)")]
+    pub output_layout: String,
+    #[arg(long, default_value_t = DEFAULT_ROTATE_SIZE, help = "在指定 --all 参数时, 输出文件超过多大进行滚动(单位:MB), 0 不按大小滚动")]
+    pub rotate_size: u64,
+    #[arg(long, default_value_t = DEFAULT_ROTATE_DAILY, help = "在指定 --all 参数时, 输出文件每天滚动一次")]
+    pub rotate_daily: bool,
+    #[arg(long, default_value_t = DEFAULT_ROTATE_COMPRESS, help = "滚动后的文件使用 gzip 压缩")]
+    pub rotate_compress: bool,
+    #[arg(long, default_value_t = DEFAULT_ROTATE_KEEP_FILES, help = "每个输出文件最多保留多少个滚动后的历史文件, 0 不限制")]
+    pub rotate_keep_files: usize,
+    #[arg(long, default_value_t = DEFAULT_ROTATE_KEEP_DAYS, help = "滚动后的历史文件最多保留多少天, 0 不限制")]
+    pub rotate_keep_days: u64,
+    #[arg(long, default_value_t = String::from(DEFAULT_OUTPUT_FORMAT), help = "processlist 信息输出格式: text, jsonl(每行一个json对象)")]
+    pub output_format: String,
+    #[arg(long, default_value_t = DEFAULT_FINGERPRINT_SUMMARY, help = "输出快照时, 在前面添加按 sql 指纹聚合的汇总表")]
+    pub fingerprint_summary: bool,
+    #[arg(long, default_value_t = DEFAULT_FINGERPRINT_SUMMARY_LIMIT, help = "sql 指纹汇总表最多输出多少个指纹")]
+    pub fingerprint_summary_limit: usize,
+    #[arg(long, default_value_t = DEFAULT_GROUP_SUMMARY, help = "输出快照时, 添加按 user, client_ip, db, command, state 分组的线程数(包含 Sleep 线程)")]
+    pub group_summary: bool,
+    #[arg(long, default_value_t = DEFAULT_GROUP_SUMMARY_LIMIT, help = "分组线程数中每个分组最多输出多少个值")]
+    pub group_summary_limit: usize,
+    #[arg(long, default_value_t = DEFAULT_TRACK_QUERIES, help = "跟踪每次 processlist 之间 sql 的开始和结束, 输出 query_started/query_finished 事件")]
+    pub track_queries: bool,
+    #[arg(long, default_value_t = DEFAULT_TRACK_MIN_TIME, help = "跟踪 sql 时, 执行时间达到多少秒才输出事件(单位:s)")]
+    pub track_min_time: u64,
+    #[arg(long, default_value_t = DEFAULT_DIAGNOSTIC, help = "超过 --print-cnt-threshold 时, 额外采集 INNODB STATUS, INNODB_TRX, GLOBAL STATUS, 锁等待等诊断信息")]
+    pub diagnostic: bool,
+    #[arg(long, default_value_t = DEFAULT_DIAGNOSTIC_INTERVAL, help = "同一个实例两次采集诊断信息最少间隔多久(单位:s)")]
+    pub diagnostic_interval: u64,
+    #[arg(long, default_value_t = DEFAULT_LOCK_WAITS, help = "输出快照时, 添加 阻塞者 -> 等待者 的锁等待信息")]
+    pub lock_waits: bool,
+    #[arg(long, default_value_t = DEFAULT_PRE_TRIGGER_SNAPSHOTS, help = "在内存中保留每个实例最近多少次没有超过阈值的快照, 超过 --print-cnt-threshold 时先输出这些快照(标记为 pre-trigger), 0 不开启")]
+    pub pre_trigger_snapshots: usize,
+    #[arg(long, default_value_t = DEFAULT_SATURATION_CONNECTED_PCT, help = "Threads_connected 达到 max_connections 的百分之多少, 或者单个用户的链接数达到 max_user_connections 的百分之多少时输出快照, 并记录链接数最多的用户和客户端. 0 不检测")]
+    pub saturation_connected_pct: u64,
+    #[arg(long, default_value_t = DEFAULT_SATURATION_RUNNING_PCT, help = "Threads_running 达到 max_connections 的百分之多少时输出快照, 并记录链接数最多的用户和客户端. 0 不检测")]
+    pub saturation_running_pct: u64,
+    #[arg(long, default_value_t = DEFAULT_SATURATION_TOP, help = "链接接近饱和时, 最多记录多少个链接数最多的用户和客户端")]
+    pub saturation_top: usize,
+    #[arg(long, default_value_t = DEFAULT_EXPLAIN_MIN_TIME, help = "输出快照时, 对执行时间达到多少秒的 SELECT, UPDATE, DELETE 执行 EXPLAIN FOR CONNECTION(5.7+) 获取执行计划, 失败时 SELECT 在对应的 db 上执行 EXPLAIN. 0 不开启(单位:s)")]
+    pub explain_min_time: u64,
+    #[arg(long, default_value_t = DEFAULT_EXPLAIN_WINDOW, help = "同一个 sql 指纹在多长时间内只获取一次执行计划(单位:s)")]
+    pub explain_window: u64,
+    #[arg(long, default_value_t = DEFAULT_EXPLAIN_LIMIT, help = "每次输出快照最多获取多少个执行计划")]
+    pub explain_limit: usize,
+    #[arg(long, default_value_t = String::from(DEFAULT_METRICS_ADDR), help = "prometheus 指标 http 服务监听地址, 例如: 0.0.0.0:9104, 通过 /metrics 获取指标. 不指定则不开启")]
+    pub metrics_addr: String,
+    #[arg(long, default_value_t = String::from(DEFAULT_ALERT_WEBHOOK_URL), help = "告警 webhook 地址, 实例活跃线程数超过阈值时发送告警, 恢复时发送恢复通知. 不指定则不告警")]
+    pub alert_webhook_url: String,
+    #[arg(long, default_value_t = String::from(DEFAULT_ALERT_WEBHOOK_TYPE), help = "告警 webhook 类型: generic(直接发送告警json), dingtalk(钉钉机器人), wecom(企业微信机器人)")]
+    pub alert_webhook_type: String,
+    #[arg(long, default_value_t = DEFAULT_ALERT_THRESHOLD, help = "满足 --threshold-filter 的线程数达到多少发送告警, 0 使用 --print-cnt-threshold")]
+    pub alert_threshold: u64,
+    #[arg(long, default_value_t = DEFAULT_ALERT_COOLDOWN, help = "同一个实例两次告警最少间隔多久(单位:s)")]
+    pub alert_cooldown: u64,
+    #[arg(long, default_value_t = DEFAULT_ALERT_TOP_FINGERPRINTS, help = "告警消息中最多包含多少个 sql 指纹")]
+    pub alert_top_fingerprints: usize,
+    #[arg(long, default_value_t = DEFAULT_ALERT_TIMEOUT, help = "发送告警超时时间(单位:s)")]
+    pub alert_timeout: u64,
+    #[arg(long, default_value_t = DEFAULT_KILL, help = "开启 kill 模式, 对匹配 --kill-* 规则的线程执行 kill")]
+    pub kill: bool,
+    #[arg(long, default_value_t = DEFAULT_KILL_EXECUTE, help = "真正执行 kill, 不指定时为 dry-run 只记录审计日志")]
+    pub kill_execute: bool,
+    #[arg(long, default_value_t = String::from(DEFAULT_KILL_TYPE), help = "kill 类型: query(KILL QUERY), connection(KILL)")]
+    pub kill_type: String,
+    #[arg(long, default_value_t = DEFAULT_KILL_MIN_TIME, help = "kill 规则: TIME 大于等于多少秒(单位:s)")]
+    pub kill_min_time: u64,
+    #[arg(
+        long,
+        action = clap::ArgAction::Append,
+        help = "kill 规则: 匹配的 COMMAND, 可指定多个. 不指定时不会 kill Sleep 线程"
+    )]
+    pub kill_commands: Vec<String>,
+    #[arg(
+        long,
+        action = clap::ArgAction::Append,
+        help = "kill 规则: 匹配的 USER, 可指定多个"
+    )]
+    pub kill_users: Vec<String>,
+    #[arg(
+        long,
+        action = clap::ArgAction::Append,
+        help = "kill 规则: 匹配的 DB, 可指定多个"
+    )]
+    pub kill_dbs: Vec<String>,
+    #[arg(long, default_value_t = String::from(DEFAULT_KILL_INFO_REGEX), help = "kill 规则: INFO 需要匹配的正则表达式")]
+    pub kill_info_regex: String,
+    #[arg(
+        long,
+        action = clap::ArgAction::Append,
+        help = "受保护的用户, 可指定多个, 这些用户的线程永远不会被 kill. system user, event_scheduler, repl 始终受保护"
+    )]
+    pub kill_protected_users: Vec<String>,
+    #[arg(long, default_value_t = String::from(DEFAULT_KILL_AUDIT_FILE), help = "kill 审计文件, 记录每一次 kill 以及对应的 processlist 信息")]
+    pub kill_audit_file: String,
+    #[arg(long, default_value_t = String::from(DEFAULT_HISTORY_STORE), help = "输出快照时同时保存到历史库, 可以通过 processlist-history 子命令查询: sqlite(本地文件 --history-sqlite-file), mysql(easydb 链接的 --history-database 库). 不指定则不保存")]
+    pub history_store: String,
+    #[arg(long, default_value_t = String::from(DEFAULT_HISTORY_SQLITE_FILE), help = "--history-store=sqlite 时保存历史快照的 sqlite 文件")]
+    pub history_sqlite_file: String,
+    #[arg(long, default_value_t = String::from(DEFAULT_HISTORY_DATABASE), help = "--history-store=mysql 时保存历史快照的数据库, 使用 easydb 的链接信息, 不指定则使用 --easydb-database")]
+    pub history_database: String,
+    #[arg(long, default_value_t = String::from(DEFAULT_HISTORY_TABLE), help = "保存历史快照的表名, 不存在时自动创建")]
+    pub history_table: String,
     #[arg(long, default_value_t = DEFAULT_IS_SQL_LOG, help = "执行sql是否打印日志")]
     pub is_sql_log: bool,
     #[arg(long, default_value_t = String::from(DEFAULT_LOG_FILE_SHOW_PROCESSLIT), help = "日志文件")]
@@ -81,6 +284,110 @@ impl ShowProcesslistConf {
             )));
         }
 
+        if self.output_format != OUTPUT_FORMAT_TEXT && self.output_format != OUTPUT_FORMAT_JSONL {
+            return Err(CustomError::new(format!(
+                "不支持的输出格式: {output_format}, 可选值: {text}, {jsonl}",
+                output_format = &self.output_format,
+                text = OUTPUT_FORMAT_TEXT,
+                jsonl = OUTPUT_FORMAT_JSONL,
+            )));
+        }
+
+        if self.processlist_source != PROCESSLIST_SOURCE_AUTO
+            && self.processlist_source != PROCESSLIST_SOURCE_INFORMATION_SCHEMA
+            && self.processlist_source != PROCESSLIST_SOURCE_PS_PROCESSLIST
+            && self.processlist_source != PROCESSLIST_SOURCE_PS_THREADS
+        {
+            return Err(CustomError::new(format!(
+                "不支持的 processlist 数据来源: {source}, 可选值: {auto}, {information_schema}, {ps_processlist}, {ps_threads}",
+                source = &self.processlist_source,
+                auto = PROCESSLIST_SOURCE_AUTO,
+                information_schema = PROCESSLIST_SOURCE_INFORMATION_SCHEMA,
+                ps_processlist = PROCESSLIST_SOURCE_PS_PROCESSLIST,
+                ps_threads = PROCESSLIST_SOURCE_PS_THREADS,
+            )));
+        }
+
+        if self.alert_webhook_type != ALERT_WEBHOOK_TYPE_GENERIC
+            && self.alert_webhook_type != ALERT_WEBHOOK_TYPE_DINGTALK
+            && self.alert_webhook_type != ALERT_WEBHOOK_TYPE_WECOM
+        {
+            return Err(CustomError::new(format!(
+                "不支持的告警 webhook 类型: {webhook_type}, 可选值: {generic}, {dingtalk}, {wecom}",
+                webhook_type = &self.alert_webhook_type,
+                generic = ALERT_WEBHOOK_TYPE_GENERIC,
+                dingtalk = ALERT_WEBHOOK_TYPE_DINGTALK,
+                wecom = ALERT_WEBHOOK_TYPE_WECOM,
+            )));
+        }
+
+        if self.kill {
+            self.check_kill()?;
+        }
+
+        if !self.history_store.is_empty() {
+            check_history(&self.history_store, &self.history_table)?;
+        }
+
+        if self.saturation_connected_pct > 100 || self.saturation_running_pct > 100 {
+            return Err(CustomError::new(String::from(
+                "--saturation-connected-pct, --saturation-running-pct 需要在 0 ~ 100 之间",
+            )));
+        }
+
+        // 合并输出只包含 processlist 和链接饱和度
+        if self.merged_view
+            && (self.pre_trigger_snapshots > 0
+                || self.explain_min_time > 0
+                || self.lock_waits
+                || self.fingerprint_summary
+                || self.group_summary)
+        {
+            return Err(CustomError::new(String::from(
+                "--merged-view 不支持 --pre-trigger-snapshots, --explain-min-time, --lock-waits, --fingerprint-summary, --group-summary",
+            )));
+        }
+
+        Ok(())
+    }
+
+    // 开启 kill 模式时检测 kill 规则
+    pub fn check_kill(&self) -> Result<(), CustomError> {
+        if self.kill_type != KILL_TYPE_QUERY && self.kill_type != KILL_TYPE_CONNECTION {
+            return Err(CustomError::new(format!(
+                "不支持的 kill 类型: {kill_type}, 可选值: {query}, {connection}",
+                kill_type = &self.kill_type,
+                query = KILL_TYPE_QUERY,
+                connection = KILL_TYPE_CONNECTION,
+            )));
+        }
+
+        // 防止没有任何规则时 kill 掉所有线程
+        if self.kill_min_time == 0
+            && self.kill_commands.is_empty()
+            && self.kill_users.is_empty()
+            && self.kill_dbs.is_empty()
+            && self.kill_info_regex.is_empty()
+        {
+            return Err(CustomError::new(String::from(
+                "开启了 --kill 但是没有指定任何 kill 规则, 请至少指定 --kill-min-time --kill-commands --kill-users --kill-dbs --kill-info-regex 其中一个",
+            )));
+        }
+
+        if let Err(e) = regex::Regex::new(&self.kill_info_regex) {
+            return Err(CustomError::new(format!(
+                "--kill-info-regex 正则表达式不合法: {regex}. {e}",
+                regex = &self.kill_info_regex,
+                e = e
+            )));
+        }
+
+        if self.kill_audit_file.is_empty() {
+            return Err(CustomError::new(String::from(
+                "开启了 --kill 需要通过 --kill-audit-file 指定审计文件",
+            )));
+        }
+
         Ok(())
     }
 
@@ -92,6 +399,15 @@ impl ShowProcesslistConf {
             )));
         }
 
+        if self.output_layout != OUTPUT_LAYOUT_FLAT && self.output_layout != OUTPUT_LAYOUT_CLUSTER {
+            return Err(CustomError::new(format!(
+                "不支持的输出文件目录结构: {output_layout}, 可选值: {flat}, {cluster}",
+                output_layout = &self.output_layout,
+                flat = OUTPUT_LAYOUT_FLAT,
+                cluster = OUTPUT_LAYOUT_CLUSTER,
+            )));
+        }
+
         Ok(())
     }
 
@@ -108,6 +424,21 @@ impl ShowProcesslistConf {
         );
     }
 
+    // --history-store=mysql 时历史库的链接
+    pub fn get_history_mysql_dsn(&self) -> String {
+        get_history_mysql_dsn(
+            &self.easydb_username,
+            &self.easydb_password,
+            &self.easydb_host,
+            self.easydb_port,
+            if self.history_database.is_empty() {
+                &self.easydb_database
+            } else {
+                &self.history_database
+            },
+        )
+    }
+
     pub fn get_password(&self) -> String {
         peep::decrypt_default(&self.password)
     }
@@ -116,10 +447,57 @@ impl ShowProcesslistConf {
         return !self.host.is_empty() && self.port > 0;
     }
 
+    // 是否需要检测链接饱和度
+    pub fn is_saturation(&self) -> bool {
+        self.saturation_connected_pct > 0 || self.saturation_running_pct > 0
+    }
+
     pub fn have_vip_port(&self) -> bool {
         return !self.vip_port.is_empty();
     }
 
+    pub fn get_alert_threshold(&self) -> u64 {
+        if self.alert_threshold > 0 {
+            self.alert_threshold
+        } else {
+            self.print_cnt_threshold
+        }
+    }
+
+    pub fn is_jsonl(&self) -> bool {
+        self.output_format == OUTPUT_FORMAT_JSONL
+    }
+
+    // 使用 --all 参数时, 每个实例输出文件的后缀
+    pub fn output_file_ext(&self) -> &str {
+        if self.is_jsonl() {
+            "jsonl"
+        } else if self.is_cluster_layout() {
+            "log"
+        } else {
+            "txt"
+        }
+    }
+
+    pub fn is_cluster_layout(&self) -> bool {
+        self.output_layout == OUTPUT_LAYOUT_CLUSTER
+    }
+
+    // 是否开启了输出文件滚动, 按集群目录结构输出时文件名带日期, 也算滚动
+    pub fn is_rotate(&self) -> bool {
+        self.rotate_size > 0 || self.rotate_daily || self.is_cluster_layout()
+    }
+
+    pub fn get_rotate_policy(&self) -> RotatePolicy {
+        RotatePolicy {
+            max_size: self.rotate_size * 1024 * 1024,
+            daily: self.rotate_daily,
+            compress: self.rotate_compress,
+            keep_files: self.rotate_keep_files,
+            keep_days: self.rotate_keep_days,
+        }
+    }
+
     pub fn ignore_instances_to_set(&self) -> HashSet<String> {
         self.ignore_instances
             .iter()
@@ -127,3 +505,60 @@ impl ShowProcesslistConf {
             .collect::<HashSet<String>>()
     }
 }
+
+// 检测历史库类型和表名, show-processlist 和 processlist-history 共用
+pub fn check_history(history_store: &str, history_table: &str) -> Result<(), CustomError> {
+    if history_store != HISTORY_STORE_SQLITE && history_store != HISTORY_STORE_MYSQL {
+        return Err(CustomError::new(format!(
+            "不支持的历史库类型: {history_store}, 可选值: {sqlite}, {mysql}",
+            history_store = history_store,
+            sqlite = HISTORY_STORE_SQLITE,
+            mysql = HISTORY_STORE_MYSQL,
+        )));
+    }
+
+    // 表名会拼接到 sql 中
+    if history_table.is_empty()
+        || !history_table
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return Err(CustomError::new(format!(
+            "历史快照表名不合法: {history_table}, 只能包含字母, 数字, 下划线",
+            history_table = history_table
+        )));
+    }
+
+    Ok(())
+}
+
+pub fn get_history_mysql_dsn(
+    username: &str,
+    password: &str,
+    host: &str,
+    port: u16,
+    database: &str,
+) -> String {
+    format!(
+        "mysql://{username}:{password}@{host}:{port}/{database}",
+        username = username,
+        password = peep::decrypt_default(password),
+        host = host,
+        port = port,
+        database = database,
+    )
+}
+
+// 测试中根据 show-processlist 子命令的参数创建配置
+#[cfg(test)]
+pub fn parse_test_args(args: &[&str]) -> ShowProcesslistConf {
+    use crate::config::{Commands, Config};
+    use clap::Parser;
+
+    let mut all_args = vec!["mysql-tool-rs", "show-processlist"];
+    all_args.extend_from_slice(args);
+    match Config::parse_from(all_args).command {
+        Commands::ShowProcesslist(cfg) => *cfg,
+        _ => panic!("不是 show-processlist 命令"),
+    }
+}
@@ -0,0 +1,100 @@
+use crate::error::CustomError;
+use crate::utils::peep;
+use clap::Args;
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_USERNAME: &str = "root";
+const DEFAULT_PASSWORD: &str = "NHJtbG91cVdmVjIxTWpLTLr7hJX88U1EC1maABXZJoI=";
+const DEFAULT_HOST: &str = "";
+const DEFAULT_PORT: u16 = 0;
+const DEFAULT_EASYDB_USERNAME: &str = "yh_easydb";
+const DEFAULT_EASYDB_PASSWORD: &str =
+    "WmlPc3JSY295bTduTUFVZElpx3Z5jRDQHK4vz9T65kQ6Zkz4j/08nnapTpEqATmc";
+const DEFAULT_EASYDB_HOST: &str = "127.0.0.1";
+const DEFAULT_EASYDB_PORT: u16 = 3306;
+const DEFAULT_EASYDB_DATABASE: &str = "easydb";
+const DEFAULT_VIP_PORT: &str = "";
+const DEFAULT_REFRESH: u64 = 2000; // 单位毫秒
+const DEFAULT_SORT: &str = SORT_TIME;
+const DEFAULT_FILTER: &str = "command != 'Sleep'";
+
+pub const SORT_TIME: &str = "time";
+pub const SORT_USER: &str = "user";
+pub const SORT_DB: &str = "db";
+
+#[derive(Args, Debug, Serialize, Deserialize, Clone)]
+pub struct TopConf {
+    #[arg(long, default_value_t = String::from(DEFAULT_USERNAME), help = "数据库用户名")]
+    pub username: String,
+    #[arg(long, default_value_t = String::from(DEFAULT_PASSWORD), help = "数据库密码")]
+    pub password: String,
+    #[arg(long, default_value_t = String::from(DEFAULT_HOST), help = "需要查看的数据库host")]
+    pub host: String,
+    #[arg(long, default_value_t = DEFAULT_PORT, help = "需要查看的数据库端口")]
+    pub port: u16,
+    #[arg(long, default_value_t = String::from(DEFAULT_VIP_PORT), help = "需要查看集群的vip_port, 会查看集群所有实例, 如果指定了 --host --port 参数则忽略该参数")]
+    pub vip_port: String,
+    #[arg(long, default_value_t = String::from(DEFAULT_EASYDB_USERNAME), help = "easydb 数据库用户名")]
+    pub easydb_username: String,
+    #[arg(long, default_value_t = String::from(DEFAULT_EASYDB_PASSWORD), help = "easydb 数据库密码")]
+    pub easydb_password: String,
+    #[arg(long, default_value_t = String::from(DEFAULT_EASYDB_HOST), help = "easydb 数据库地址")]
+    pub easydb_host: String,
+    #[arg(long, default_value_t = DEFAULT_EASYDB_PORT, help = "easydb 数据库端口")]
+    pub easydb_port: u16,
+    #[arg(long, default_value_t = String::from(DEFAULT_EASYDB_DATABASE), help = "easydb链接的数据库名")]
+    pub easydb_database: String,
+    #[arg(long, default_value_t = DEFAULT_REFRESH, help = "多久刷新一次 processlist(单位:ms)")]
+    pub refresh: u64,
+    #[arg(long, default_value_t = String::from(DEFAULT_SORT), help = "默认排序字段: time, user, db. 界面中可以通过 t u d 键切换")]
+    pub sort: String,
+    #[arg(long, default_value_t = String::from(DEFAULT_FILTER), help = "默认过滤表达式, 语法同 show-processlist --threshold-filter. 界面中可以通过 / 键修改")]
+    pub filter: String,
+}
+
+impl TopConf {
+    pub fn check(&self) -> Result<(), CustomError> {
+        if self.vip_port.is_empty() && !self.have_host_port() {
+            return Err(CustomError::new(String::from(
+                "没有获取到需要查询的实例信息, 请指定 --vip-port 或 --host --port 参数",
+            )));
+        }
+
+        if self.sort != SORT_TIME && self.sort != SORT_USER && self.sort != SORT_DB {
+            return Err(CustomError::new(format!(
+                "不支持的排序字段: {sort}, 可选值: {time}, {user}, {db}",
+                sort = &self.sort,
+                time = SORT_TIME,
+                user = SORT_USER,
+                db = SORT_DB,
+            )));
+        }
+
+        if self.refresh == 0 {
+            return Err(CustomError::new(String::from("--refresh 需要大于 0")));
+        }
+
+        Ok(())
+    }
+
+    pub fn get_easydb_dsn(&self) -> String {
+        let password = peep::decrypt_default(&self.easydb_password);
+
+        format!(
+            "mysql://{username}:{password}@{host}:{port}/{database}",
+            username = self.easydb_username,
+            password = password,
+            host = self.easydb_host,
+            port = self.easydb_port,
+            database = self.easydb_database,
+        )
+    }
+
+    pub fn get_password(&self) -> String {
+        peep::decrypt_default(&self.password)
+    }
+
+    pub fn have_host_port(&self) -> bool {
+        !self.host.is_empty() && self.port > 0
+    }
+}
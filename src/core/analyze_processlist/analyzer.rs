@@ -0,0 +1,154 @@
+use crate::core::analyze_processlist::parser::CapturedSnapshot;
+use crate::core::show_processlist::summary;
+use crate::models::{ProcesslistIncident, ShowProcesslistInfo};
+use crate::utils;
+use std::collections::BTreeMap;
+
+// 按实例分析快照, 同一个实例超过阈值的快照间隔不超过 incident_gap 秒的算同一次事件
+pub fn get_incidents(
+    snapshots: Vec<CapturedSnapshot>,
+    threshold: u64,
+    incident_gap: i64,
+    top: usize,
+) -> Vec<ProcesslistIncident> {
+    let mut instance_map = BTreeMap::<String, Vec<CapturedSnapshot>>::new();
+    // 超过阈值前的快照只是上下文, 不作为事件的开始
+    for snapshot in snapshots.into_iter().filter(|v| !v.pre_trigger) {
+        if snapshot.infos.len() as u64 >= threshold {
+            instance_map
+                .entry(snapshot.instance.clone())
+                .or_default()
+                .push(snapshot);
+        }
+    }
+
+    let mut incidents = Vec::<ProcesslistIncident>::new();
+    for (_, mut snapshots) in instance_map.into_iter() {
+        // 同一个快照可能出现在多个文件中, 只保留一个
+        snapshots.sort_by_key(|snapshot| snapshot.time);
+        snapshots.dedup_by_key(|snapshot| snapshot.time);
+
+        let mut start = 0;
+        for i in 1..=snapshots.len() {
+            let is_end = i == snapshots.len()
+                || (snapshots[i].time - snapshots[i - 1].time).num_seconds() > incident_gap;
+            if is_end {
+                incidents.push(get_incident(&snapshots[start..i], top));
+                start = i;
+            }
+        }
+    }
+    incidents.sort_by(|a, b| {
+        a.start_time
+            .cmp(&b.start_time)
+            .then(a.instance.cmp(&b.instance))
+    });
+
+    incidents
+}
+
+// snapshots 不为空, 并且已经按时间排序
+fn get_incident(snapshots: &[CapturedSnapshot], top: usize) -> ProcesslistIncident {
+    let first = &snapshots[0];
+    let last = &snapshots[snapshots.len() - 1];
+    // 线程数相同时取最早的快照
+    let peak = snapshots
+        .iter()
+        .rev()
+        .max_by_key(|snapshot| snapshot.infos.len())
+        .unwrap();
+
+    // 所有快照中的线程一起统计, 线程持续时间越长出现的次数越多
+    let infos = snapshots
+        .iter()
+        .flat_map(|snapshot| snapshot.infos.iter().cloned())
+        .collect::<Vec<ShowProcesslistInfo>>();
+    let mut top_fingerprints = summary::get_fingerprint_summaries(&infos);
+    top_fingerprints.truncate(top);
+    let group_summaries = summary::get_group_summaries(&infos, top);
+
+    ProcesslistIncident {
+        instance: first.instance.clone(),
+        cluster_name: snapshots
+            .iter()
+            .find_map(|snapshot| snapshot.cluster_name.clone()),
+        start_time: first.time.format(utils::time::NORMAL_FMT).to_string(),
+        end_time: last.time.format(utils::time::NORMAL_FMT).to_string(),
+        duration: (last.time - first.time).num_seconds(),
+        snapshots: snapshots.len(),
+        peak_threads: peak.infos.len(),
+        peak_total: peak.total,
+        peak_time: peak.time.format(utils::time::NORMAL_FMT).to_string(),
+        top_fingerprints,
+        top_users: group_summaries
+            .iter()
+            .filter(|summary| summary.group_by == "user")
+            .cloned()
+            .collect(),
+        top_client_ips: group_summaries
+            .iter()
+            .filter(|summary| summary.group_by == "client_ip")
+            .cloned()
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::analyze_processlist::analyzer::get_incidents;
+    use crate::core::analyze_processlist::parser::CapturedSnapshot;
+    use crate::models::ShowProcesslistInfo;
+    use chrono::NaiveDateTime;
+
+    #[test]
+    fn test_get_incidents() {
+        let get_snapshot = |time: &str, cnt: usize| CapturedSnapshot {
+            instance: String::from("127.0.0.1:3306"),
+            cluster_name: None,
+            time: NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S").unwrap(),
+            total: cnt + 10,
+            infos: (0..cnt)
+                .map(|i| ShowProcesslistInfo {
+                    id: Some(i as u64),
+                    user: Some(String::from("app")),
+                    host: Some(format!("10.0.0.1:{}", 5000 + i)),
+                    db: Some(String::from("db1")),
+                    command: Some(String::from("Query")),
+                    time: Some(1),
+                    state: Some(String::from("Sending data")),
+                    info: Some(format!("select * from t1 where id = {}", i)),
+                    ..Default::default()
+                })
+                .collect(),
+            pre_trigger: false,
+        };
+
+        let snapshots = vec![
+            get_snapshot("2023-01-31 16:00:00", 12),
+            get_snapshot("2023-01-31 16:00:01", 20),
+            get_snapshot("2023-01-31 16:00:02", 11),
+            get_snapshot("2023-01-31 16:00:03", 2),
+            // 重复的快照
+            get_snapshot("2023-01-31 16:00:02", 11),
+            get_snapshot("2023-01-31 16:10:00", 15),
+            // 超过阈值前的快照不参与事件分析
+            CapturedSnapshot {
+                pre_trigger: true,
+                ..get_snapshot("2023-01-31 15:59:59", 15)
+            },
+        ];
+        let incidents = get_incidents(snapshots, 10, 60, 5);
+        println!("{:#?}", incidents);
+
+        assert_eq!(incidents.len(), 2);
+        assert_eq!(incidents[0].snapshots, 3);
+        assert_eq!(incidents[0].duration, 2);
+        assert_eq!(incidents[0].peak_threads, 20);
+        assert_eq!(incidents[0].peak_total, 30);
+        assert_eq!(incidents[0].peak_time, "2023-01-31 16:00:01");
+        assert_eq!(incidents[0].top_fingerprints[0].count, 43);
+        assert_eq!(incidents[0].top_client_ips[0].value, "10.0.0.1");
+        assert_eq!(incidents[1].snapshots, 1);
+        assert_eq!(incidents[1].duration, 0);
+    }
+}
@@ -0,0 +1,84 @@
+use crate::config::analyze_processlist_conf::AnalyzeProcesslistConf;
+use crate::core::analyze_processlist::{analyzer, parser};
+use crate::core::show_processlist::summary;
+use crate::error::CustomError;
+use crate::models::ProcesslistIncident;
+use crate::utils;
+
+pub async fn run(cfg: &AnalyzeProcesslistConf) -> Result<(), CustomError> {
+    log::info!("{}", utils::string::to_json_str_pretty(&cfg));
+    // 检测配置文件相关参数
+    cfg.check()?;
+    let start_time = cfg.get_start_time()?;
+    let end_time = cfg.get_end_time()?;
+
+    let files = parser::find_files(&cfg.input)?;
+    log::info!("需要分析的文件数: {}", files.len());
+
+    // 逐行解析所有文件, 解析时按时间范围和阈值过滤, 只保留需要分析的快照.
+    // 单个文件失败不影响其他文件
+    let filter = parser::SnapshotFilter {
+        start_time,
+        end_time,
+        threshold: cfg.threshold,
+    };
+    let mut snapshots = Vec::<parser::CapturedSnapshot>::new();
+    for file in files.iter() {
+        let mut cnt = 0;
+        let result = parser::parse_file(file, &filter, |snapshot| {
+            cnt += 1;
+            snapshots.push(snapshot);
+        });
+        match result {
+            Ok(_) => log::info!(
+                "解析文件完成. 文件: {file}, 需要分析的快照数: {cnt}",
+                file = file.display(),
+                cnt = cnt
+            ),
+            Err(e) => log::error!("{}", e),
+        }
+    }
+    log::info!("需要分析的快照数: {}", snapshots.len());
+
+    let incidents = analyzer::get_incidents(snapshots, cfg.threshold, cfg.incident_gap, cfg.top);
+    log::info!("事件数: {}", incidents.len());
+
+    for incident in incidents.iter() {
+        if cfg.is_jsonl() {
+            println!("{}", utils::string::to_json_str(incident));
+        } else {
+            println!("{}", get_incident_text(incident));
+        }
+    }
+
+    Ok(())
+}
+
+fn get_incident_text(incident: &ProcesslistIncident) -> String {
+    let mut data = format!(
+        "---- {instance} Cluster: {cluster_name}, Start: {start_time}, End: {end_time}, Duration: {duration}s, Snapshots: {snapshots}, Peak: {peak_threads}/{peak_total} at {peak_time} ----\n",
+        instance = &incident.instance,
+        cluster_name = incident.cluster_name.as_deref().unwrap_or(""),
+        start_time = &incident.start_time,
+        end_time = &incident.end_time,
+        duration = incident.duration,
+        snapshots = incident.snapshots,
+        peak_threads = incident.peak_threads,
+        peak_total = incident.peak_total,
+        peak_time = &incident.peak_time,
+    );
+    if !incident.top_fingerprints.is_empty() {
+        data.push_str("Top SQL Fingerprint:\n");
+        data.push_str(&summary::get_fingerprint_summary_table(
+            &incident.top_fingerprints,
+        ));
+    }
+    if !incident.top_users.is_empty() || !incident.top_client_ips.is_empty() {
+        data.push_str("Top User/Client IP:\n");
+        let mut group_summaries = incident.top_users.clone();
+        group_summaries.extend(incident.top_client_ips.iter().cloned());
+        data.push_str(&summary::get_group_summary_table(&group_summaries));
+    }
+
+    data
+}
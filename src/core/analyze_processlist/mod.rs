@@ -0,0 +1,5 @@
+pub mod analyzer;
+pub mod handler;
+pub mod parser;
+
+pub use handler::run;
@@ -0,0 +1,502 @@
+use crate::error::CustomError;
+use crate::models::snapshot_record::RECORD_TYPE_PROCESSLIST;
+use crate::models::{ShowProcesslistInfo, SnapshotRecord};
+use crate::utils;
+use chrono::NaiveDateTime;
+use flate2::read::GzDecoder;
+use regex::Regex;
+use std::fs;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use unicode_width::UnicodeWidthChar;
+
+// 需要分析的文件后缀, 滚动压缩后的文件为 {文件名}.{时间}.gz
+const FILE_EXTS: [&str; 3] = [".txt", ".log", ".jsonl"];
+const GZIP_EXT: &str = ".gz";
+// processlist 表格的列, 和 show_processlist::common::get_infos_table 一致
+const PROCESSLIST_COLUMNS: [&str; 8] = [
+    "Id", "User", "Host", "db", "Command", "Time", "State", "Info",
+];
+
+// 从文件中解析出的一次快照
+#[derive(Debug, Clone)]
+pub struct CapturedSnapshot {
+    pub instance: String, // host:port
+    pub cluster_name: Option<String>,
+    pub time: NaiveDateTime,
+    pub total: usize, // processlist 总数, jsonl 中没有记录总数, 为输出的线程数
+    pub infos: Vec<ShowProcesslistInfo>,
+    pub pre_trigger: bool, // 超过阈值前的快照, 不参与事件分析
+}
+
+// 获取需要分析的文件, 目录递归查找
+pub fn find_files(inputs: &[String]) -> Result<Vec<PathBuf>, CustomError> {
+    let mut files = Vec::<PathBuf>::new();
+    for input in inputs.iter() {
+        let path = Path::new(input);
+        if path.is_dir() {
+            find_dir_files(path, &mut files).map_err(|e| {
+                CustomError::new(format!(
+                    "读取目录失败. 目录: {dir}. {e}",
+                    dir = input,
+                    e = e
+                ))
+            })?;
+        } else if path.is_file() {
+            files.push(path.to_path_buf());
+        } else {
+            return Err(CustomError::new(format!(
+                "文件或目录不存在: {input}",
+                input = input
+            )));
+        }
+    }
+    files.sort();
+    files.dedup();
+
+    Ok(files)
+}
+
+fn find_dir_files(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_dir_files(&path, files)?;
+            continue;
+        }
+
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let name = name.strip_suffix(GZIP_EXT).unwrap_or(&name);
+        if FILE_EXTS
+            .iter()
+            .any(|ext| name.ends_with(ext) || name.contains(&format!("{ext}.")))
+        {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+// 解析时过滤快照, 只保留需要分析的快照
+#[derive(Debug, Default)]
+pub struct SnapshotFilter {
+    pub start_time: Option<NaiveDateTime>,
+    pub end_time: Option<NaiveDateTime>,
+    pub threshold: u64,
+}
+
+impl SnapshotFilter {
+    // 快照头就能判断的条件, 不满足时不需要解析快照内容.
+    // 超过阈值前的快照只是上下文, 不参与事件分析
+    fn is_header_match(&self, time: NaiveDateTime, pre_trigger: bool) -> bool {
+        !pre_trigger
+            && self.start_time.is_none_or(|t| time >= t)
+            && self.end_time.is_none_or(|t| time <= t)
+    }
+
+    fn is_match(&self, snapshot: &CapturedSnapshot) -> bool {
+        self.is_header_match(snapshot.time, snapshot.pre_trigger)
+            && snapshot.infos.len() as u64 >= self.threshold
+    }
+}
+
+// 逐行解析文件中的快照, 满足过滤条件的快照通过 on_snapshot 返回.
+// 根据文件名判断是 text 还是 jsonl 格式
+pub fn parse_file<F>(
+    path: &Path,
+    filter: &SnapshotFilter,
+    on_snapshot: F,
+) -> Result<(), CustomError>
+where
+    F: FnMut(CapturedSnapshot),
+{
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let result = open_file(path).and_then(|reader| {
+        if name.contains(".jsonl") {
+            parse_jsonl(reader, filter, on_snapshot)
+        } else {
+            parse_text(reader, filter, on_snapshot)
+        }
+    });
+
+    result.map_err(|e| {
+        CustomError::new(format!(
+            "读取文件失败. 文件: {path}. {e}",
+            path = path.display(),
+            e = e
+        ))
+    })
+}
+
+fn open_file(path: &Path) -> io::Result<Box<dyn BufRead>> {
+    let is_gzip = path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .ends_with(GZIP_EXT);
+    let file = fs::File::open(path)?;
+    if is_gzip {
+        Ok(Box::new(BufReader::new(GzDecoder::new(file))))
+    } else {
+        Ok(Box::new(BufReader::new(file)))
+    }
+}
+
+// 读取一行, 去掉换行符, 不是 utf8 的内容使用替换字符. 读到文件结尾返回 None
+fn read_line(reader: &mut impl BufRead, buf: &mut Vec<u8>) -> io::Result<Option<String>> {
+    buf.clear();
+    if reader.read_until(b'\n', buf)? == 0 {
+        return Ok(None);
+    }
+    if buf.ends_with(b"\n") {
+        buf.pop();
+        if buf.ends_with(b"\r") {
+            buf.pop();
+        }
+    }
+
+    Ok(Some(String::from_utf8_lossy(buf).into_owned()))
+}
+
+// 解析 jsonl 格式, 只需要 record_type 为 processlist 的记录, 通过 instance 和 capture_time 区分快照.
+// 同一个快照的记录是连续写入的, instance 或 capture_time 变化说明上一个快照结束
+pub fn parse_jsonl<F>(
+    mut reader: impl BufRead,
+    filter: &SnapshotFilter,
+    mut on_snapshot: F,
+) -> io::Result<()>
+where
+    F: FnMut(CapturedSnapshot),
+{
+    let mut buf = Vec::<u8>::new();
+    let mut current: Option<(String, CapturedSnapshot)> = None;
+    while let Some(line) = read_line(&mut reader, &mut buf)? {
+        let record = match serde_json::from_str::<SnapshotRecord<serde_json::Value>>(&line) {
+            Ok(v) if v.record_type == RECORD_TYPE_PROCESSLIST => v,
+            _ => continue,
+        };
+        let time = match NaiveDateTime::parse_from_str(
+            &record.capture_time,
+            utils::time::OPTIONAL_FRACTION_FMT,
+        ) {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+        if !filter.is_header_match(time, record.pre_trigger) {
+            continue;
+        }
+        let info = match serde_json::from_value::<ShowProcesslistInfo>(record.data) {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+
+        let is_same = current.as_ref().is_some_and(|(capture_time, snapshot)| {
+            snapshot.instance == record.instance && *capture_time == record.capture_time
+        });
+        if !is_same {
+            if let Some((_, snapshot)) = current.take() {
+                if filter.is_match(&snapshot) {
+                    on_snapshot(snapshot);
+                }
+            }
+            current = Some((
+                record.capture_time,
+                CapturedSnapshot {
+                    instance: record.instance,
+                    cluster_name: record.cluster_name,
+                    time,
+                    total: 0,
+                    infos: Vec::new(),
+                    pre_trigger: record.pre_trigger,
+                },
+            ));
+        }
+        if let Some((_, snapshot)) = current.as_mut() {
+            snapshot.total += 1;
+            snapshot.infos.push(info);
+        }
+    }
+    if let Some((_, snapshot)) = current {
+        if filter.is_match(&snapshot) {
+            on_snapshot(snapshot);
+        }
+    }
+
+    Ok(())
+}
+
+fn header_regex() -> &'static Regex {
+    static HEADER_REGEX: OnceLock<Regex> = OnceLock::new();
+    HEADER_REGEX.get_or_init(|| {
+        Regex::new(r"^---- (\[pre-trigger\] )?(\S+) Time: (\d{4}-\d{2}-\d{2} \d{2}:\d{2}:\d{2}(?:\.\d+)?), Total: (\d+), Filter Sleep: (\d+) ----$").unwrap()
+    })
+}
+
+// 正在解析的表格
+struct TextTable {
+    borders: Vec<usize>, // 每个 + 所在的显示列
+    is_processlist: bool,
+    is_title_done: bool,
+    is_rows: bool,
+}
+
+// 解析 text 格式. 快照以 ---- host:port Time: ... ---- 开头(超过阈值前的快照带有 [pre-trigger] 标记),
+// 只解析其中的 processlist 表格. 快照头不满足过滤条件时跳过快照内容
+pub fn parse_text<F>(
+    mut reader: impl BufRead,
+    filter: &SnapshotFilter,
+    mut on_snapshot: F,
+) -> io::Result<()>
+where
+    F: FnMut(CapturedSnapshot),
+{
+    let mut buf = Vec::<u8>::new();
+    let mut current: Option<CapturedSnapshot> = None;
+    let mut table: Option<TextTable> = None;
+
+    while let Some(line) = read_line(&mut reader, &mut buf)? {
+        let line = line.as_str();
+        if let Some(caps) = header_regex().captures(line) {
+            if let Some(snapshot) = current.take() {
+                if filter.is_match(&snapshot) {
+                    on_snapshot(snapshot);
+                }
+            }
+            table = None;
+            current = NaiveDateTime::parse_from_str(&caps[3], utils::time::OPTIONAL_FRACTION_FMT)
+                .ok()
+                .filter(|time| filter.is_header_match(*time, caps.get(1).is_some()))
+                .map(|time| CapturedSnapshot {
+                    instance: caps[2].to_string(),
+                    cluster_name: None,
+                    time,
+                    total: caps[4].parse().unwrap_or(0),
+                    infos: Vec::new(),
+                    pre_trigger: caps.get(1).is_some(),
+                });
+            continue;
+        }
+        let snapshot = match current.as_mut() {
+            Some(v) => v,
+            None => continue,
+        };
+
+        if line.starts_with('+') {
+            match table.as_mut() {
+                None => {
+                    table = Some(TextTable {
+                        borders: get_borders(line),
+                        is_processlist: false,
+                        is_title_done: false,
+                        is_rows: false,
+                    })
+                }
+                // 标题和数据之间的分隔线
+                Some(t) if t.is_title_done && !t.is_rows => t.is_rows = true,
+                // 表格结束
+                Some(_) => table = None,
+            }
+            continue;
+        }
+
+        let t = match table.as_mut() {
+            Some(t) if line.starts_with('|') => t,
+            _ => {
+                table = None;
+                continue;
+            }
+        };
+        let cells = split_cells(line, &t.borders);
+        if !t.is_title_done {
+            t.is_title_done = true;
+            t.is_processlist = cells.iter().map(|c| c.trim()).eq(PROCESSLIST_COLUMNS);
+            continue;
+        }
+        if !t.is_rows || !t.is_processlist || cells.len() != PROCESSLIST_COLUMNS.len() {
+            continue;
+        }
+
+        // Id 为空说明是上一行中多行单元格的后续内容
+        if cells[0].trim().is_empty() {
+            if let Some(info) = snapshot.infos.last_mut() {
+                append_line(&mut info.user, &cells[1]);
+                append_line(&mut info.host, &cells[2]);
+                append_line(&mut info.db, &cells[3]);
+                append_line(&mut info.command, &cells[4]);
+                append_line(&mut info.state, &cells[6]);
+                append_line(&mut info.info, &cells[7]);
+            }
+            continue;
+        }
+
+        snapshot.infos.push(ShowProcesslistInfo {
+            id: cells[0].trim().parse().ok(),
+            user: get_cell_value(&cells[1]),
+            host: get_cell_value(&cells[2]),
+            db: get_cell_value(&cells[3]),
+            command: get_cell_value(&cells[4]),
+            time: cells[5].trim().parse().ok(),
+            state: get_cell_value(&cells[6]),
+            info: get_cell_value(&cells[7]),
+            ..Default::default()
+        });
+    }
+    if let Some(snapshot) = current {
+        if filter.is_match(&snapshot) {
+            on_snapshot(snapshot);
+        }
+    }
+
+    Ok(())
+}
+
+fn get_borders(line: &str) -> Vec<usize> {
+    let mut borders = Vec::<usize>::new();
+    let mut width = 0;
+    for c in line.chars() {
+        if c == '+' {
+            borders.push(width);
+        }
+        width += c.width().unwrap_or(0);
+    }
+
+    borders
+}
+
+// 按边框的显示列切分单元格, 中文等宽字符占两列
+fn split_cells(line: &str, borders: &[usize]) -> Vec<String> {
+    let mut cells = vec![String::new(); borders.len().saturating_sub(1)];
+    let mut width = 0;
+    for c in line.chars() {
+        // 边框位置上的 | 不属于单元格
+        if !borders.contains(&width) {
+            if let Some(i) = borders.iter().rposition(|border| *border < width) {
+                if i < cells.len() {
+                    cells[i].push(c);
+                }
+            }
+        }
+        width += c.width().unwrap_or(0);
+    }
+
+    cells
+}
+
+// 单元格去掉两边的填充空格, 空字符串为 None
+fn get_cell_value(cell: &str) -> Option<String> {
+    let value = cell.trim();
+    if value.is_empty() {
+        None
+    } else {
+        Some(value.to_string())
+    }
+}
+
+fn append_line(value: &mut Option<String>, cell: &str) {
+    let line = cell.trim();
+    if line.is_empty() {
+        return;
+    }
+
+    match value.as_mut() {
+        Some(v) => {
+            v.push('\n');
+            v.push_str(line);
+        }
+        None => *value = Some(line.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::analyze_processlist::parser::{
+        header_regex, parse_text, CapturedSnapshot, SnapshotFilter,
+    };
+    use crate::core::show_processlist::common::get_infos_table;
+    use crate::models::ShowProcesslistInfo;
+
+    #[test]
+    fn test_parse_text() {
+        let infos = vec![
+            ShowProcesslistInfo {
+                id: Some(10),
+                user: Some(String::from("app")),
+                host: Some(String::from("10.0.0.1:5000")),
+                db: Some(String::from("db1")),
+                command: Some(String::from("Query")),
+                time: Some(3),
+                state: Some(String::from("Sending data")),
+                info: Some(String::from("select *\nfrom t1 where name = '中文|'")),
+                ..Default::default()
+            },
+            ShowProcesslistInfo {
+                id: Some(11),
+                user: Some(String::from("app2")),
+                host: Some(String::from("10.0.0.2:5000")),
+                db: None,
+                command: Some(String::from("Query")),
+                time: Some(1),
+                state: None,
+                info: Some(String::from("select 1")),
+                ..Default::default()
+            },
+        ];
+        let content = format!(
+            "2023-01-31 16:43:07 - INFO - handler.rs::100 - \n---- 127.0.0.1:3306 Time: 2023-01-31 16:43:07, Total: 5, Filter Sleep: 2 ----\nProcesslist:\n{table}",
+            table = get_infos_table(&infos)
+        );
+        println!("{}", content);
+
+        let mut snapshots = Vec::<CapturedSnapshot>::new();
+        parse_text(content.as_bytes(), &SnapshotFilter::default(), |v| {
+            snapshots.push(v)
+        })
+        .unwrap();
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(snapshots[0].instance, "127.0.0.1:3306");
+        assert_eq!(snapshots[0].total, 5);
+        assert_eq!(snapshots[0].infos.len(), 2);
+        assert_eq!(snapshots[0].infos[0].info, infos[0].info);
+        assert_eq!(snapshots[0].infos[1].user, infos[1].user);
+        assert_eq!(snapshots[0].infos[1].db, None);
+        assert!(!snapshots[0].pre_trigger);
+
+        let caps = header_regex()
+            .captures("---- [pre-trigger] 127.0.0.1:3306 Time: 2023-01-31 16:43:06, Total: 3, Filter Sleep: 1 ----")
+            .unwrap();
+        assert!(caps.get(1).is_some());
+        assert_eq!(&caps[2], "127.0.0.1:3306");
+
+        // 采集间隔小于 1 秒时快照时间精确到毫秒
+        let content = format!(
+            "---- 127.0.0.1:3306 Time: 2023-01-31 16:43:07.250, Total: 5, Filter Sleep: 2 ----\nProcesslist:\n{table}",
+            table = get_infos_table(&infos)
+        );
+        let mut snapshots = Vec::<CapturedSnapshot>::new();
+        parse_text(content.as_bytes(), &SnapshotFilter::default(), |v| {
+            snapshots.push(v)
+        })
+        .unwrap();
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(snapshots[0].time.and_utc().timestamp_subsec_millis(), 250);
+
+        // 线程数小于阈值或者不在时间范围内的快照解析时过滤掉
+        let filters = [
+            SnapshotFilter {
+                threshold: 3,
+                ..Default::default()
+            },
+            SnapshotFilter {
+                start_time: Some(snapshots[0].time + chrono::Duration::seconds(1)),
+                ..Default::default()
+            },
+        ];
+        for filter in filters.iter() {
+            let mut cnt = 0;
+            parse_text(content.as_bytes(), filter, |_| cnt += 1).unwrap();
+            assert_eq!(cnt, 0);
+        }
+    }
+}
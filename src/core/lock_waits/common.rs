@@ -0,0 +1,276 @@
+use crate::core::show_processlist::source;
+use crate::dao::NormalDao;
+use crate::error::CustomError;
+use crate::models::{LockWait, LockWaitEdge, LockWaitThread, ShowProcesslistInfo};
+use sqlx::{MySql, Pool};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+// 8.0 开始使用 performance_schema.data_lock_waits
+const DATA_LOCK_WAITS_VERSION: (u32, u32, u32) = (8, 0, 0);
+
+// 获取数据库版本和 processlist 后获取锁等待信息
+pub async fn get_lock_waits(db: &Pool<MySql>) -> Result<Vec<LockWait>, CustomError> {
+    let version = NormalDao::get_version(db)
+        .await
+        .map_err(|e| CustomError::new(format!("获取数据库版本失败. {e}", e = e)))?;
+    let infos = NormalDao::show_processlist(db)
+        .await
+        .map_err(|e| CustomError::new(format!("获取processlist信息失败. {e}", e = e)))?;
+
+    get_lock_waits_by_infos(db, &version, &infos).await
+}
+
+// 获取锁等待信息, 合并已经获取的 processlist 和 INNODB_TRX 中的线程信息
+pub async fn get_lock_waits_by_infos(
+    db: &Pool<MySql>,
+    version: &str,
+    infos: &[ShowProcesslistInfo],
+) -> Result<Vec<LockWait>, CustomError> {
+    // MariaDB 的锁等待表和 MySQL 不一样
+    let version_num = match source::parse_version(version) {
+        Some(v) if !version.contains("MariaDB") => v,
+        _ => {
+            return Err(CustomError::new(format!(
+                "不支持获取该版本的锁等待信息. version: {version}",
+                version = version
+            )))
+        }
+    };
+
+    // 5.7 及以下使用 information_schema.INNODB_LOCK_WAITS, 8.0 使用 performance_schema.data_lock_waits
+    let edges = if version_num < DATA_LOCK_WAITS_VERSION {
+        NormalDao::lock_wait_edges_57(db).await
+    } else {
+        NormalDao::lock_wait_edges_80(db).await
+    }
+    .map_err(|e| {
+        CustomError::new(format!(
+            "获取锁等待信息失败. version: {version}. {e}",
+            version = &version,
+            e = e
+        ))
+    })?;
+    if edges.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let trxs = NormalDao::innodb_trxs(db)
+        .await
+        .map_err(|e| CustomError::new(format!("获取 INNODB_TRX 信息失败. {e}", e = e)))?;
+
+    // 线程id -> 线程信息
+    let mut threads = HashMap::<u64, LockWaitThread>::new();
+    for info in infos.iter() {
+        if let Some(id) = info.id {
+            threads.insert(
+                id,
+                LockWaitThread {
+                    thread_id: id,
+                    user: info.user.clone(),
+                    host: info.host.clone(),
+                    db: info.db.clone(),
+                    sql: info.info.clone(),
+                    ..Default::default()
+                },
+            );
+        }
+    }
+    for trx in trxs.into_iter() {
+        if let Some(id) = trx.thread_id {
+            let thread = threads.entry(id).or_insert_with(|| LockWaitThread {
+                thread_id: id,
+                ..Default::default()
+            });
+            thread.trx_state = trx.trx_state;
+            thread.trx_age = trx.trx_age;
+            // 阻塞者经常处于 Sleep 状态, processlist 中没有 sql
+            if thread.sql.is_none() {
+                thread.sql = trx.trx_query;
+            }
+        }
+    }
+
+    Ok(merge_lock_waits(&edges, &threads))
+}
+
+fn merge_lock_waits(
+    edges: &[LockWaitEdge],
+    threads: &HashMap<u64, LockWaitThread>,
+) -> Vec<LockWait> {
+    let get_thread = |id: u64| {
+        threads.get(&id).cloned().unwrap_or(LockWaitThread {
+            thread_id: id,
+            ..Default::default()
+        })
+    };
+
+    edges
+        .iter()
+        .filter_map(
+            |edge| match (edge.blocking_thread_id, edge.waiting_thread_id) {
+                (Some(blocking), Some(waiting)) => Some(LockWait {
+                    blocking: get_thread(blocking),
+                    waiting: get_thread(waiting),
+                }),
+                _ => None,
+            },
+        )
+        .collect()
+}
+
+// 生成 阻塞者 -> 等待者 的树形结构文本
+pub fn get_lock_wait_tree(lock_waits: &[LockWait]) -> String {
+    let mut threads = HashMap::<u64, &LockWaitThread>::new();
+    let mut children = BTreeMap::<u64, BTreeSet<u64>>::new();
+    let mut waiting_ids = HashSet::<u64>::new();
+    for lock_wait in lock_waits.iter() {
+        threads.insert(lock_wait.blocking.thread_id, &lock_wait.blocking);
+        threads.insert(lock_wait.waiting.thread_id, &lock_wait.waiting);
+        children
+            .entry(lock_wait.blocking.thread_id)
+            .or_default()
+            .insert(lock_wait.waiting.thread_id);
+        waiting_ids.insert(lock_wait.waiting.thread_id);
+    }
+
+    // 根节点为没有在等待别人的阻塞者, 如果都在等待(死锁检测之前的环)则所有阻塞者都作为根节点
+    let mut roots = children
+        .keys()
+        .filter(|id| !waiting_ids.contains(*id))
+        .copied()
+        .collect::<Vec<u64>>();
+    if roots.is_empty() {
+        roots = children.keys().copied().collect();
+    }
+
+    let mut data = String::new();
+    let mut path = HashSet::<u64>::new();
+    let mut printed = HashSet::<u64>::new();
+    for root in roots.iter() {
+        write_tree_node(
+            *root,
+            0,
+            &threads,
+            &children,
+            &mut path,
+            &mut printed,
+            &mut data,
+        );
+    }
+
+    data
+}
+
+fn write_tree_node(
+    id: u64,
+    depth: usize,
+    threads: &HashMap<u64, &LockWaitThread>,
+    children: &BTreeMap<u64, BTreeSet<u64>>,
+    path: &mut HashSet<u64>,    // 当前路径上的线程, 用于检测环
+    printed: &mut HashSet<u64>, // 已经输出过的线程, 多个阻塞者等待同一个线程时不重复展开
+    data: &mut String,
+) {
+    let prefix = if depth == 0 {
+        String::from("[blocker] ")
+    } else {
+        format!("{indent}└─ [waiter] ", indent = "    ".repeat(depth - 1))
+    };
+    let thread_desc = match threads.get(&id) {
+        Some(thread) => get_thread_desc(thread),
+        None => format!("thread_id: {id}", id = id),
+    };
+
+    // 出现环或者已经输出过时不再继续展开
+    if path.contains(&id) {
+        data.push_str(&format!("{prefix}{thread_desc} (循环等待)\n"));
+        return;
+    }
+    if !printed.insert(id) {
+        data.push_str(&format!("{prefix}{thread_desc} (见上方)\n"));
+        return;
+    }
+    data.push_str(&format!("{prefix}{thread_desc}\n"));
+
+    path.insert(id);
+    if let Some(waiting_ids) = children.get(&id) {
+        for waiting_id in waiting_ids.iter() {
+            write_tree_node(
+                *waiting_id,
+                depth + 1,
+                threads,
+                children,
+                path,
+                printed,
+                data,
+            );
+        }
+    }
+    path.remove(&id);
+}
+
+fn get_thread_desc(thread: &LockWaitThread) -> String {
+    format!(
+        "thread_id: {thread_id}, user: {user}, host: {host}, db: {db}, trx_state: {trx_state}, trx_age: {trx_age}s, sql: {sql}",
+        thread_id = thread.thread_id,
+        user = thread.user.as_deref().unwrap_or(""),
+        host = thread.host.as_deref().unwrap_or(""),
+        db = thread.db.as_deref().unwrap_or(""),
+        trx_state = thread.trx_state.as_deref().unwrap_or(""),
+        trx_age = thread.trx_age.unwrap_or(0),
+        sql = thread.sql.as_deref().unwrap_or("NULL").replace('\n', " "),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::lock_waits::common::get_lock_wait_tree;
+    use crate::models::{LockWait, LockWaitThread};
+
+    fn get_lock_wait(blocking: u64, waiting: u64) -> LockWait {
+        LockWait {
+            blocking: LockWaitThread {
+                thread_id: blocking,
+                ..Default::default()
+            },
+            waiting: LockWaitThread {
+                thread_id: waiting,
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_get_lock_wait_tree() {
+        let tree = get_lock_wait_tree(&[
+            get_lock_wait(1, 2),
+            get_lock_wait(2, 3),
+            get_lock_wait(1, 4),
+        ]);
+        println!("{}", tree);
+
+        let lines = tree.lines().collect::<Vec<&str>>();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("[blocker] thread_id: 1,"));
+        assert!(lines[1].starts_with("└─ [waiter] thread_id: 2,"));
+        assert!(lines[2].starts_with("    └─ [waiter] thread_id: 3,"));
+        assert!(lines[3].starts_with("└─ [waiter] thread_id: 4,"));
+
+        // 两个阻塞者阻塞同一个线程, 不是循环等待
+        let tree = get_lock_wait_tree(&[
+            get_lock_wait(1, 3),
+            get_lock_wait(2, 3),
+            get_lock_wait(3, 4),
+        ]);
+        println!("{}", tree);
+        let lines = tree.lines().collect::<Vec<&str>>();
+        assert_eq!(lines.len(), 5);
+        assert!(!tree.contains("(循环等待)"));
+        assert!(lines[4].starts_with("└─ [waiter] thread_id: 3,"));
+        assert!(lines[4].ends_with("(见上方)"));
+
+        // 环
+        let tree = get_lock_wait_tree(&[get_lock_wait(1, 2), get_lock_wait(2, 1)]);
+        println!("{}", tree);
+        assert!(tree.contains("(循环等待)"));
+    }
+}
@@ -0,0 +1,130 @@
+use crate::config::lock_waits_conf::LockWaitsConf;
+use crate::core::lock_waits::common;
+use crate::dao::{InstanceDao, MetaClusterDao};
+use crate::error::CustomError;
+use crate::models::Instance;
+use crate::{rdbc, utils};
+use sqlx::{MySql, Pool};
+
+pub async fn run(cfg: &LockWaitsConf) -> Result<(), CustomError> {
+    log::info!("{}", utils::string::to_json_str_pretty(&cfg));
+    // 检测配置文件相关参数
+    cfg.check()?;
+
+    // 指定 host port
+    if cfg.have_host_port() {
+        let instance = Instance {
+            machine_host: Some(cfg.host.clone()),
+            port: Some(cfg.port as i32),
+            ..Default::default()
+        };
+        return print_instance_lock_waits(cfg, &instance).await;
+    }
+
+    // 链接数据库
+    let easydb_db = rdbc::get_db(&cfg.get_easydb_dsn(), cfg.is_sql_log)
+        .await
+        .map_err(|e| CustomError::new(format!("创建 EasyDB 实例链接出错. {e}", e = e)))?;
+
+    // 执行逻辑
+    if let Err(e) = start_vip_port(cfg, &easydb_db).await {
+        let _ = easydb_db.close().await;
+        return Err(e);
+    }
+
+    // 关闭数据库链接
+    let _ = easydb_db.close().await;
+
+    Ok(())
+}
+
+async fn start_vip_port(cfg: &LockWaitsConf, easydb_db: &Pool<MySql>) -> Result<(), CustomError> {
+    // 获取集群
+    let clusters = MetaClusterDao::find_by_vip_port(easydb_db, &cfg.vip_port)
+        .await
+        .map_err(|err| {
+            CustomError::new(format!(
+                "通过vip_port{vip_port}获取所有集群失败 {err}",
+                vip_port = &cfg.vip_port,
+                err = err
+            ))
+        })?;
+    log::info!("获取集群数: {}", clusters.len());
+
+    // 循环每个集群获取master实例信息
+    for cluster in clusters.iter() {
+        let masters = InstanceDao::find_master_by_meta_cluster_id(easydb_db, cluster.id.unwrap())
+            .await
+            .map_err(|err| {
+                CustomError::new(format!(
+                    "通过集群id获取master失败. {}. {}",
+                    utils::string::to_json_str(cluster),
+                    err
+                ))
+            })?;
+        log::info!(
+            "集群: {name}, 获取到master数: {cnt}",
+            name = cluster.name.as_deref().unwrap_or(""),
+            cnt = masters.len()
+        );
+
+        // 循环master获取锁等待信息
+        for master in masters.iter() {
+            if let Err(e) = print_instance_lock_waits(cfg, master).await {
+                log::error!("{}", e);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn print_instance_lock_waits(
+    cfg: &LockWaitsConf,
+    instance: &Instance,
+) -> Result<(), CustomError> {
+    let host = instance.machine_host.as_ref().unwrap();
+    let port = instance.port.unwrap();
+
+    // 链接数据库
+    let password = cfg.get_password();
+    let db = rdbc::get_db_by_default(
+        host,
+        port as i16,
+        &cfg.username,
+        &password,
+        "",
+        cfg.is_sql_log,
+    )
+    .await
+    .map_err(|e| {
+        CustomError::new(format!(
+            "创建实例链接出错. host:port:{host}:{port}. {e}",
+            host = host,
+            port = port,
+            e = e
+        ))
+    })?;
+
+    let lock_waits = common::get_lock_waits(&db).await;
+    let _ = db.close().await;
+    let lock_waits = lock_waits.map_err(|e| {
+        CustomError::new(format!(
+            "{host}:{port}, 获取锁等待信息失败. {e}",
+            host = host,
+            port = port,
+            e = e
+        ))
+    })?;
+
+    println!(
+        "---- {host}:{port} Time: {time}, Lock Waits: {cnt} ----",
+        host = host,
+        port = port,
+        time = utils::time::now_str(utils::time::NORMAL_FMT),
+        cnt = lock_waits.len()
+    );
+    print!("{}", common::get_lock_wait_tree(&lock_waits));
+
+    Ok(())
+}
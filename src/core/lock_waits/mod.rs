@@ -0,0 +1,4 @@
+pub mod common;
+pub mod handler;
+
+pub use handler::run;
@@ -1,2 +1,6 @@
+pub mod analyze_processlist;
+pub mod lock_waits;
+pub mod processlist_history;
 pub mod show_index;
 pub mod show_processlist;
+pub mod top;
@@ -0,0 +1,106 @@
+use crate::config::processlist_history_conf::ProcesslistHistoryConf;
+use crate::config::show_processlist_conf::HISTORY_STORE_SQLITE;
+use crate::core::processlist_history::store;
+use crate::dao::{ProcesslistHistoryDao, ProcesslistHistoryFilter};
+use crate::error::CustomError;
+use crate::models::ProcesslistHistory;
+use crate::utils;
+use prettytable::{format, Cell, Row, Table};
+use std::path::Path;
+
+pub async fn run(cfg: &ProcesslistHistoryConf) -> Result<(), CustomError> {
+    log::info!("{}", utils::string::to_json_str_pretty(&cfg));
+    // 检测配置文件相关参数
+    cfg.check()?;
+
+    // 查询时 sqlite 文件不存在直接报错, 不创建空文件
+    if cfg.history_store == HISTORY_STORE_SQLITE && !Path::new(&cfg.history_sqlite_file).exists() {
+        return Err(CustomError::new(format!(
+            "历史库 sqlite 文件不存在: {file}",
+            file = &cfg.history_sqlite_file
+        )));
+    }
+
+    let db = store::connect(
+        &cfg.history_store,
+        &cfg.history_sqlite_file,
+        &cfg.get_history_mysql_dsn(),
+        cfg.is_sql_log,
+    )
+    .await?;
+
+    let filter = ProcesslistHistoryFilter {
+        host_port: cfg.instance.clone(),
+        start_time: cfg.get_start_time()?,
+        end_time: cfg.get_end_time()?,
+        user: cfg.user.clone(),
+        sql: cfg.sql.clone(),
+        limit: cfg.limit,
+    };
+    let records = ProcesslistHistoryDao::find(&db, &cfg.history_table, &filter).await;
+    db.close().await;
+    let records = records.map_err(|e| {
+        CustomError::new(format!(
+            "查询历史快照失败. 表: {table}. {e}",
+            table = &cfg.history_table,
+            e = e
+        ))
+    })?;
+    log::info!("查询到历史快照记录数: {cnt}", cnt = records.len());
+
+    if cfg.is_jsonl() {
+        for record in records.iter() {
+            println!("{}", utils::string::to_json_str(record));
+        }
+    } else {
+        print!("{}", get_records_table(&records));
+    }
+
+    Ok(())
+}
+
+fn get_records_table(records: &[ProcesslistHistory]) -> String {
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+    table.set_titles(Row::new(
+        [
+            "Snapshot Time",
+            "Instance",
+            "Id",
+            "User",
+            "Host",
+            "db",
+            "Command",
+            "Time",
+            "State",
+            "Info",
+        ]
+        .iter()
+        .map(|title| Cell::new(title))
+        .collect(),
+    ));
+
+    for record in records.iter() {
+        let cells = [
+            record
+                .snapshot_time
+                .map(|v| v.format(utils::time::NORMAL_FMT).to_string())
+                .unwrap_or_default(),
+            record.host_port.clone().unwrap_or_default(),
+            record
+                .processlist_id
+                .map(|v| v.to_string())
+                .unwrap_or_default(),
+            record.user.clone().unwrap_or_default(),
+            record.host.clone().unwrap_or_default(),
+            record.db.clone().unwrap_or_default(),
+            record.command.clone().unwrap_or_default(),
+            record.time.map(|v| v.to_string()).unwrap_or_default(),
+            record.state.clone().unwrap_or_default(),
+            record.info.clone().unwrap_or_default(),
+        ];
+        table.add_row(Row::new(cells.iter().map(|cell| Cell::new(cell)).collect()));
+    }
+
+    table.to_string()
+}
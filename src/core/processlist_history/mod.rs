@@ -0,0 +1,4 @@
+pub mod handler;
+pub mod store;
+
+pub use handler::run;
@@ -0,0 +1,51 @@
+use crate::config::show_processlist_conf::HISTORY_STORE_SQLITE;
+use crate::dao::{HistoryDb, ProcesslistHistoryDao};
+use crate::error::CustomError;
+use crate::rdbc;
+
+// 创建历史库链接, sqlite 文件不存在时自动创建
+pub async fn connect(
+    history_store: &str,
+    sqlite_file: &str,
+    mysql_dsn: &str,
+    is_sql_log: bool,
+) -> Result<HistoryDb, CustomError> {
+    if history_store == HISTORY_STORE_SQLITE {
+        rdbc::get_sqlite_db(sqlite_file, is_sql_log)
+            .await
+            .map(HistoryDb::Sqlite)
+            .map_err(|e| {
+                CustomError::new(format!(
+                    "打开历史库 sqlite 文件失败. 文件: {file}. {e}",
+                    file = sqlite_file,
+                    e = e
+                ))
+            })
+    } else {
+        rdbc::get_db(mysql_dsn, is_sql_log)
+            .await
+            .map(HistoryDb::MySql)
+            .map_err(|e| CustomError::new(format!("创建历史库 mysql 链接失败. {e}", e = e)))
+    }
+}
+
+// 创建历史库链接, 并且在表不存在时创建
+pub async fn connect_and_create_table(
+    history_store: &str,
+    sqlite_file: &str,
+    mysql_dsn: &str,
+    table: &str,
+    is_sql_log: bool,
+) -> Result<HistoryDb, CustomError> {
+    let db = connect(history_store, sqlite_file, mysql_dsn, is_sql_log).await?;
+    if let Err(e) = ProcesslistHistoryDao::create_table(&db, table).await {
+        db.close().await;
+        return Err(CustomError::new(format!(
+            "创建历史快照表失败. 表: {table}. {e}",
+            table = table,
+            e = e
+        )));
+    }
+
+    Ok(db)
+}
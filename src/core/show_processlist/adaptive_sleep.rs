@@ -0,0 +1,80 @@
+use crate::config::show_processlist_conf::ShowProcesslistConf;
+
+// 自适应采集间隔: 超过 --print-cnt-threshold 时使用 --incident-sleep,
+// 恢复后每次 processlist 间隔翻倍, 直到恢复为 --sleep
+pub struct AdaptiveSleep {
+    current: Option<u64>, // 当前缩短后的间隔, None 使用 --sleep
+}
+
+impl AdaptiveSleep {
+    pub fn new() -> AdaptiveSleep {
+        AdaptiveSleep { current: None }
+    }
+
+    // 每次 processlist 后根据是否超过阈值更新间隔, 进入或者退出快速采集时记录日志
+    pub fn update(&mut self, cfg: &ShowProcesslistConf, host_port: &str, is_incident: bool) {
+        let next = get_next_sleep(self.current, is_incident, cfg.sleep, cfg.incident_sleep);
+        if self.current.is_none() && next.is_some() {
+            log::info!(
+                "{host_port}, 超过阈值, 采集间隔缩短为 {sleep}ms",
+                host_port = host_port,
+                sleep = cfg.incident_sleep
+            );
+        } else if self.current.is_some() && next.is_none() {
+            log::info!(
+                "{host_port}, 恢复正常, 采集间隔恢复为 {sleep}ms",
+                host_port = host_port,
+                sleep = cfg.sleep
+            );
+        }
+        self.current = next;
+    }
+
+    // 本次需要休眠的毫秒数
+    pub fn get_sleep(&self, cfg: &ShowProcesslistConf) -> u64 {
+        self.current.unwrap_or(cfg.sleep)
+    }
+}
+
+// incident_sleep 为 0 或者不小于 sleep 时不开启
+fn get_next_sleep(
+    current: Option<u64>,
+    is_incident: bool,
+    sleep: u64,
+    incident_sleep: u64,
+) -> Option<u64> {
+    if incident_sleep == 0 || incident_sleep >= sleep {
+        return None;
+    }
+    if is_incident {
+        return Some(incident_sleep);
+    }
+
+    current
+        .map(|current| current.saturating_mul(2))
+        .filter(|next| *next < sleep)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::show_processlist::adaptive_sleep::get_next_sleep;
+
+    #[test]
+    fn test_get_next_sleep() {
+        let mut current = None;
+        let mut sleeps = Vec::new();
+        for is_incident in [false, true, true, false, false, false, false, false, false] {
+            current = get_next_sleep(current, is_incident, 5000, 200);
+            sleeps.push(current.unwrap_or(5000));
+        }
+        println!("{:?}", sleeps);
+        assert_eq!(
+            sleeps,
+            vec![5000, 200, 200, 400, 800, 1600, 3200, 5000, 5000]
+        );
+
+        // 没有开启
+        assert_eq!(get_next_sleep(None, true, 1000, 0), None);
+        assert_eq!(get_next_sleep(Some(200), true, 100, 200), None);
+    }
+}
@@ -0,0 +1,296 @@
+use crate::config::show_processlist_conf::{
+    ShowProcesslistConf, ALERT_WEBHOOK_TYPE_DINGTALK, ALERT_WEBHOOK_TYPE_WECOM,
+};
+use crate::core::show_processlist::{shutdown, summary};
+use crate::error::CustomError;
+use crate::models::alert_message::{ALERT_STATUS_FIRING, ALERT_STATUS_RESOLVED};
+use crate::models::{AlertMessage, Instance, ShowProcesslistInfo};
+use crate::utils;
+use serde_json::json;
+
+// 告警状态变化
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertEvent {
+    Firing,
+    Resolved,
+}
+
+// 每个实例的告警状态, 同一个实例两次超过阈值的告警需要间隔 cooldown 秒
+pub struct Alerter {
+    threshold: u64,
+    cooldown: i64,
+    is_firing: bool,   // 当前是否超过阈值
+    is_notified: bool, // 这一次超过阈值是否已经发送过告警
+    last_alert_timestamp: Option<i64>,
+    client: reqwest::Client,
+}
+
+impl Alerter {
+    // 没有指定 --alert-webhook-url 返回 None
+    pub fn new(cfg: &ShowProcesslistConf) -> Result<Option<Alerter>, CustomError> {
+        if cfg.alert_webhook_url.is_empty() {
+            return Ok(None);
+        }
+
+        let client = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(cfg.alert_timeout))
+            .build()
+            .map_err(|e| CustomError::new(format!("创建告警 http 客户端失败. {e}", e = e)))?;
+
+        Ok(Some(Alerter {
+            threshold: cfg.get_alert_threshold(),
+            cooldown: cfg.alert_cooldown as i64,
+            is_firing: false,
+            is_notified: false,
+            last_alert_timestamp: None,
+            client,
+        }))
+    }
+
+    // 重新加载配置时更新阈值和告警间隔, 保留当前的告警状态
+    pub fn set_threshold(&mut self, threshold: u64, cooldown: u64) {
+        self.threshold = threshold;
+        self.cooldown = cooldown as i64;
+    }
+
+    // 根据当前活跃线程数更新告警状态, 返回需要发送的告警
+    pub fn update(&mut self, active_threads: usize, now_timestamp: i64) -> Option<AlertEvent> {
+        if active_threads as u64 >= self.threshold {
+            self.is_firing = true;
+            if let Some(last) = self.last_alert_timestamp {
+                if now_timestamp - last < self.cooldown {
+                    return None;
+                }
+            }
+
+            self.is_notified = true;
+            self.last_alert_timestamp = Some(now_timestamp);
+            return Some(AlertEvent::Firing);
+        }
+
+        // 低于阈值, 发送过告警的需要发送恢复通知
+        let is_resolved = self.is_firing && self.is_notified;
+        self.is_firing = false;
+        self.is_notified = false;
+        if is_resolved {
+            Some(AlertEvent::Resolved)
+        } else {
+            None
+        }
+    }
+}
+
+// 检测是否需要告警, 需要则异步发送, 不阻塞 processlist 循环.
+// infos 为满足 --threshold-filter 的线程
+pub fn check_and_notify(
+    cfg: &ShowProcesslistConf,
+    alerter: &mut Alerter,
+    instance: &Instance,
+    infos: &[ShowProcesslistInfo],
+) {
+    let event = match alerter.update(infos.len(), utils::time::now_timestamp()) {
+        Some(v) => v,
+        None => return,
+    };
+
+    let top_fingerprints = if event == AlertEvent::Firing {
+        let mut summaries = summary::get_fingerprint_summaries(infos);
+        summaries.truncate(cfg.alert_top_fingerprints);
+        summaries
+    } else {
+        Vec::new()
+    };
+    let message = AlertMessage {
+        status: match event {
+            AlertEvent::Firing => String::from(ALERT_STATUS_FIRING),
+            AlertEvent::Resolved => String::from(ALERT_STATUS_RESOLVED),
+        },
+        time: utils::time::now_str(utils::time::NORMAL_FMT),
+        instance: format!(
+            "{host}:{port}",
+            host = instance.machine_host.as_ref().unwrap(),
+            port = instance.port.unwrap()
+        ),
+        cluster_name: instance.cluster_name.clone(),
+        active_threads: infos.len(),
+        threshold: alerter.threshold,
+        top_fingerprints,
+    };
+
+    let client = alerter.client.clone();
+    let url = cfg.alert_webhook_url.clone();
+    let webhook_type = cfg.alert_webhook_type.clone();
+    // 退出前需要等待告警发送完成
+    shutdown::spawn(async move {
+        match send(&client, &url, &webhook_type, &message).await {
+            Ok(_) => log::info!(
+                "{instance}, 发送告警成功. status: {status}",
+                instance = &message.instance,
+                status = &message.status
+            ),
+            Err(e) => log::error!("{instance}, {e}", instance = &message.instance, e = e),
+        }
+    });
+}
+
+// 按 webhook 类型生成消息并发送
+pub async fn send(
+    client: &reqwest::Client,
+    url: &str,
+    webhook_type: &str,
+    message: &AlertMessage,
+) -> Result<(), CustomError> {
+    let body = match webhook_type {
+        ALERT_WEBHOOK_TYPE_DINGTALK => json!({
+            "msgtype": "markdown",
+            "markdown": {
+                "title": get_title(message),
+                "text": get_markdown_text(message),
+            },
+        }),
+        ALERT_WEBHOOK_TYPE_WECOM => json!({
+            "msgtype": "markdown",
+            "markdown": {
+                "content": get_markdown_text(message),
+            },
+        }),
+        _ => serde_json::to_value(message)
+            .map_err(|e| CustomError::new(format!("告警消息序列化失败. {e}", e = e)))?,
+    };
+
+    let resp = client.post(url).json(&body).send().await.map_err(|e| {
+        CustomError::new(format!("发送告警失败. url: {url}. {e}", url = url, e = e))
+    })?;
+    if !resp.status().is_success() {
+        return Err(CustomError::new(format!(
+            "发送告警失败. url: {url}, status: {status}",
+            url = url,
+            status = resp.status()
+        )));
+    }
+
+    Ok(())
+}
+
+fn get_title(message: &AlertMessage) -> String {
+    let status = if message.status == ALERT_STATUS_FIRING {
+        "告警"
+    } else {
+        "恢复"
+    };
+
+    format!(
+        "[{status}] {instance} 活跃线程数: {active_threads}",
+        status = status,
+        instance = &message.instance,
+        active_threads = message.active_threads
+    )
+}
+
+fn get_markdown_text(message: &AlertMessage) -> String {
+    let mut text = format!(
+        "### {title}\n\n- 集群: {cluster_name}\n- 时间: {time}\n- 阈值: {threshold}\n",
+        title = get_title(message),
+        cluster_name = message.cluster_name.as_deref().unwrap_or(""),
+        time = &message.time,
+        threshold = message.threshold
+    );
+
+    if !message.top_fingerprints.is_empty() {
+        text.push_str("\n**Top SQL Fingerprint:**\n\n");
+        for (i, summary) in message.top_fingerprints.iter().enumerate() {
+            text.push_str(&format!(
+                "{no}. count: {count}, max_time: {max_time}s, `{fingerprint}`\n",
+                no = i + 1,
+                count = summary.count,
+                max_time = summary.max_time,
+                fingerprint = &summary.fingerprint
+            ));
+        }
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::show_processlist::alert::{send, AlertEvent, Alerter};
+    use crate::models::alert_message::ALERT_STATUS_FIRING;
+    use crate::models::{AlertMessage, FingerprintSummary};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_alerter_update() {
+        let mut alerter = Alerter {
+            threshold: 10,
+            cooldown: 60,
+            is_firing: false,
+            is_notified: false,
+            last_alert_timestamp: None,
+            client: reqwest::Client::new(),
+        };
+
+        assert_eq!(alerter.update(5, 0), None);
+        assert_eq!(alerter.update(10, 1), Some(AlertEvent::Firing));
+        // 冷却时间内不重复告警
+        assert_eq!(alerter.update(20, 30), None);
+        assert_eq!(alerter.update(20, 61), Some(AlertEvent::Firing));
+        assert_eq!(alerter.update(5, 62), Some(AlertEvent::Resolved));
+        assert_eq!(alerter.update(5, 63), None);
+        // 冷却时间内再次超过阈值, 不告警也不发送恢复
+        assert_eq!(alerter.update(20, 70), None);
+        assert_eq!(alerter.update(5, 71), None);
+    }
+
+    #[tokio::test]
+    async fn test_send_dingtalk() {
+        // 本地 http 服务代替钉钉机器人
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/robot/send", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::<u8>::new();
+            let mut buf = [0u8; 4096];
+            // 读到请求体结束
+            while !String::from_utf8_lossy(&request).ends_with('}') {
+                let n = stream.read(&mut buf).await.unwrap();
+                if n == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..n]);
+            }
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n{}")
+                .await
+                .unwrap();
+            String::from_utf8_lossy(&request).to_string()
+        });
+
+        let message = AlertMessage {
+            status: String::from(ALERT_STATUS_FIRING),
+            time: String::from("2023-01-31 16:43:07"),
+            instance: String::from("127.0.0.1:3306"),
+            cluster_name: Some(String::from("cluster1")),
+            active_threads: 100,
+            threshold: 50,
+            top_fingerprints: vec![FingerprintSummary {
+                fingerprint: String::from("select * from t1 where id = ?"),
+                count: 80,
+                max_time: 10,
+                avg_time: 5.0,
+                users: vec![String::from("app")],
+                dbs: vec![String::from("db1")],
+            }],
+        };
+        send(&reqwest::Client::new(), &url, "dingtalk", &message)
+            .await
+            .unwrap();
+
+        let request = server.await.unwrap();
+        println!("{}", request);
+        assert!(request.starts_with("POST /robot/send"));
+        assert!(request.contains(r#""msgtype":"markdown""#));
+        assert!(request.contains("select * from t1 where id = ?"));
+    }
+}
@@ -1,17 +1,16 @@
 use crate::config::show_processlist_conf::ShowProcesslistConf;
-use crate::core::show_processlist::common;
-use crate::dao::{InstanceDao, MetaClusterDao, NormalDao};
+use crate::core::show_processlist::collector::{self, OutputSink};
+use crate::core::show_processlist::{connection_budget, metrics, reload, shutdown};
+use crate::dao::{InstanceDao, MetaClusterDao};
 use crate::error::CustomError;
-use crate::models::{Instance, ShowProcesslistInfo};
+use crate::models::Instance;
 use crate::{rdbc, utils};
 use sqlx::{MySql, Pool};
 use std::collections::{HashMap, HashSet};
-use std::fs;
-use std::io::Write;
-use std::path::Path;
 use std::sync::{Arc, RwLock};
 use tokio::sync::mpsc;
 use tokio::sync::mpsc::Sender;
+use tokio::task::JoinSet;
 
 pub async fn run(cfg: &ShowProcesslistConf) -> Result<(), CustomError> {
     // 检测参数
@@ -26,32 +25,53 @@ pub async fn run(cfg: &ShowProcesslistConf) -> Result<(), CustomError> {
         ))
     })?;
 
+    // 初始化链接预算和新建链接速率
+    connection_budget::init(cfg);
+    // 链接预算不够所有实例使用时直接退出
+    if cfg.connection_budget > 0 {
+        let instances = find_all_instances(cfg).await?;
+        connection_budget::check_instance_count(instances.len())?;
+    }
+
     // 创建一个全局共享map
     let old_instance_set = Arc::new(RwLock::new(HashSet::<String>::new()));
 
     let (tx, mut rx) = mpsc::channel::<Instance>(100);
     // 获取所有的实例
     let tx_product = tx.clone();
-    let cfg_product = cfg.clone();
     let old_instance_set_product = old_instance_set.clone();
     tokio::spawn(async move {
         // 产生所有实例
-        if let Err(e) = product_instance(&cfg_product, &tx_product, &old_instance_set_product).await
-        {
+        if let Err(e) = product_instance(&tx_product, &old_instance_set_product).await {
             log::error!("异步产生实例出错. {e}", e = e.to_string())
         }
     });
 
-    while let Some(instance) = rx.recv().await {
+    // 所有实例的采集任务, 收到退出信号后需要等待全部结束
+    let mut tasks = JoinSet::<()>::new();
+    loop {
+        let instance = tokio::select! {
+            v = rx.recv() => match v {
+                Some(v) => v,
+                None => break,
+            },
+            // 回收已经结束的任务
+            Some(_) = tasks.join_next(), if !tasks.is_empty() => continue,
+            _ = shutdown::wait() => break,
+        };
+
         let tmp_old_instance_set = old_instance_set.clone();
-        let tmp_cfg = cfg.clone();
-        tokio::spawn(async move {
+        // 使用最新配置中实例对应的阈值覆盖配置
+        let tmp_cfg = reload::current().get_instance_cfg(&instance);
+        tasks.spawn(async move {
             // 添加实例
             add_instance(&tmp_old_instance_set, &instance);
             log::info!(
-                "接收到实例: {host}:{port}, 并且添加到 set 成功",
+                "接收到实例: {host}:{port}, 并且添加到 set 成功, print_cnt_threshold: {threshold}, sleep: {sleep}",
                 host = &instance.machine_host.as_ref().unwrap(),
                 port = &instance.port.unwrap(),
+                threshold = tmp_cfg.print_cnt_threshold,
+                sleep = tmp_cfg.sleep,
             );
 
             // 开始执行 processlist
@@ -74,22 +94,31 @@ pub async fn run(cfg: &ShowProcesslistConf) -> Result<(), CustomError> {
             );
 
             // 删除结束的实例
-            delete_instance(&old_instance_set, &instance);
+            delete_instance(&tmp_old_instance_set, &instance);
+            metrics::remove_instance(&instance);
         });
     }
 
+    log::info!(
+        "停止接收新实例, 等待 {cnt} 个实例的采集任务结束",
+        cnt = tasks.len()
+    );
+    while tasks.join_next().await.is_some() {}
+
     Ok(())
 }
 
 // 产生实例
 async fn product_instance(
-    cfg: &ShowProcesslistConf,
     tx: &Sender<Instance>,
     old_instance_set: &Arc<RwLock<HashSet<String>>>,
 ) -> Result<(), CustomError> {
+    let mut reload_rx = reload::subscribe();
     // 获取所有实例
     loop {
-        let instances = match find_all_instances(cfg).await {
+        // 每次使用最新的配置, 重新加载后忽略的实例等配置立即生效
+        let cfg = reload_rx.borrow_and_update().cfg.clone();
+        let instances = match find_all_instances(&cfg).await {
             Ok(v) => v,
             Err(e) => {
                 log::error!("获取所有实例失败: {e}", e = e.to_string());
@@ -109,7 +138,7 @@ async fn product_instance(
         });
 
         // 获取需要新增的实例 现在 - 老的
-        let need_add_instances = get_need_add_instance(old_instance_set, &instance_map);
+        let mut need_add_instances = get_need_add_instance(old_instance_set, &instance_map);
 
         // 获取需要删除的实例 老的 - 现在
         let need_remove_instances = get_need_remove_instance(old_instance_set, &instance_map);
@@ -117,6 +146,18 @@ async fn product_instance(
         // 对实例进行删除
         delete_instance_by_vec(old_instance_set, &need_remove_instances);
 
+        // 超过链接预算的新实例本次不发送, 等待其他实例移除后下次再发送
+        let capacity =
+            connection_budget::get_instance_capacity(old_instance_set.read().unwrap().len());
+        if need_add_instances.len() > capacity {
+            log::error!(
+                "新增实例数 {cnt} 超过 --connection-budget 剩余可用数 {capacity}, 超出的实例暂不采集",
+                cnt = need_add_instances.len(),
+                capacity = capacity
+            );
+            need_add_instances.truncate(capacity);
+        }
+
         // 将需要新增的实例进行发送
         for instance in need_add_instances.into_iter() {
             let key = format!(
@@ -131,11 +172,13 @@ async fn product_instance(
             log::info!("{key} 发送成功.", key = &key)
         }
 
-        // 休眠多少秒
-        let _ = tokio::time::sleep(std::time::Duration::from_secs(
-            cfg.product_instance_duration,
-        ))
-        .await;
+        // 休眠多少秒, 配置重新加载后立即重新生成实例
+        tokio::select! {
+            _ = tokio::time::sleep(std::time::Duration::from_secs(cfg.product_instance_duration)) => {}
+            _ = reload::changed(&mut reload_rx) => {
+                log::info!("配置已重新加载, 重新生成实例");
+            }
+        }
     }
 }
 
@@ -219,6 +262,12 @@ async fn find_all_instances_op(easydb: &Pool<MySql>) -> Result<Vec<Instance>, Cu
             instance_count = tmp_instances.len()
         );
 
+        // 填充集群名称和业务线
+        tmp_instances.iter_mut().for_each(|instance| {
+            instance.cluster_name = cluster.name.clone();
+            instance.business_line = cluster.business_line.clone();
+        });
+
         instances.append(&mut tmp_instances);
     }
 
@@ -272,6 +321,7 @@ fn delete_instance_by_vec(
         old_set.remove(key);
         log::info!("{}, 已经从 set 中移除", key)
     }
+    metrics::set_monitored_instances(old_set.len());
 }
 
 fn delete_instance(old_set: &Arc<RwLock<HashSet<String>>>, instance: &Instance) {
@@ -282,7 +332,8 @@ fn delete_instance(old_set: &Arc<RwLock<HashSet<String>>>, instance: &Instance)
     );
     let mut old_set = old_set.write().unwrap();
     old_set.remove(&key);
-    log::info!("{}, 已经从 set 中移除", key)
+    log::info!("{}, 已经从 set 中移除", key);
+    metrics::set_monitored_instances(old_set.len());
 }
 
 fn add_instance(old_set: &Arc<RwLock<HashSet<String>>>, instance: &Instance) {
@@ -294,6 +345,7 @@ fn add_instance(old_set: &Arc<RwLock<HashSet<String>>>, instance: &Instance) {
 
     let mut old_set = old_set.write().unwrap();
     old_set.insert(key);
+    metrics::set_monitored_instances(old_set.len());
 }
 
 fn exists_instance(old_set: &Arc<RwLock<HashSet<String>>>, instance: &Instance) -> bool {
@@ -311,140 +363,27 @@ async fn show_processlist_by_instance(
     cfg: &ShowProcesslistConf,
     instance: &Instance,
 ) -> Result<(), CustomError> {
-    let password = cfg.get_password();
-    // 创建数据库链接
-    let db = rdbc::get_db_by_default(
-        instance.machine_host.as_ref().unwrap(),
-        instance.port.unwrap() as i16,
-        &cfg.username,
-        &password,
-        "",
-        cfg.is_sql_log,
-    )
-    .await
-    .map_err(|e| {
-        CustomError::new(format!(
-            "创建需要执行 processlist 数据库链接失败. host:port:{host}:{port}. {e}",
-            host = instance.machine_host.as_ref().unwrap(),
-            port = instance.port.unwrap(),
-            e = e.to_string()
-        ))
-    })?;
-
-    let mut clean_timestamp = utils::time::now_datetime().timestamp();
-
-    loop {
-        if let Err(e) = start_processlist(cfg, instance, &db, &mut clean_timestamp).await {
-            log::error!(
-                "{host}:{port}, 执行 show processlist 出错. {e}",
-                host = instance.machine_host.as_ref().unwrap(),
-                port = instance.port.unwrap(),
-                e = e.to_string()
-            );
-            // 休眠多少毫秒, 出错后需要睡眠60秒后再重新
-            let _ = tokio::time::sleep(std::time::Duration::from_millis(60 * 1000)).await;
-        }
-
-        // 实例已经不存在, 被移除了就不跑了
-        if !exists_instance(old_set, instance) {
-            break;
-        }
-
-        // 休眠多少毫秒
-        let _ = tokio::time::sleep(std::time::Duration::from_millis(cfg.sleep)).await;
+    let host_port = format!(
+        "{host}:{port}",
+        host = instance.machine_host.as_ref().unwrap(),
+        port = instance.port.unwrap()
+    );
+    // 等待链接预算(实例数不超过预算, 只需要等待临时链接释放), 许可在函数返回关闭链接后释放
+    let _permit = tokio::select! {
+        v = connection_budget::acquire(&host_port) => v,
+        _ = shutdown::wait() => return Ok(()),
+    };
+    // 等待期间实例可能已经被移除
+    if !exists_instance(old_set, instance) {
+        return Ok(());
     }
 
-    let _ = db.close().await;
+    // 错开各个实例第一次执行的时间
+    shutdown::sleep(connection_budget::get_start_delay(cfg)).await;
 
-    Ok(())
-}
-
-async fn start_processlist(
-    cfg: &ShowProcesslistConf,
-    instance: &Instance,
-    db: &Pool<MySql>,
-    clean_timestamp: &mut i64,
-) -> Result<(), CustomError> {
-    let infos = NormalDao::show_processlist(&db).await.map_err(|e| {
-        CustomError::new(format!(
-            "{host}:{port}, 获取processlist信息失败. {e}",
-            host = instance.machine_host.as_ref().unwrap(),
-            port = instance.port.unwrap(),
-            e = e.to_string()
-        ))
-    })?;
-
-    // 过滤 processlist 信息, 过滤掉 command=Sleep
-    let filter_infos_sleep = infos
-        .iter()
-        .map(|info| info.clone())
-        .filter(|info| info.command.as_ref().unwrap() != "Sleep")
-        .collect::<Vec<ShowProcesslistInfo>>();
-
-    // 在 过滤掉 command=Sleep 基础上过滤掉 user=system user
-    let fitler_infos_system_user = filter_infos_sleep
-        .iter()
-        .map(|info| info.clone())
-        .filter(|info| info.user.as_ref().unwrap() != "system user")
-        .collect::<Vec<ShowProcesslistInfo>>();
-
-    // 除了 Sleep 和 system user 外的processlist 超过了指定数需要进行记录
-    if fitler_infos_system_user.len() >= cfg.print_cnt_threshold as usize {
-        let infos_table = common::get_infos_table(&filter_infos_sleep);
-
-        let log_data = format!(
-                "\n---- {host}:{port} Time: {time}, Total: {total}, Filter Sleep: {filter_sleep} ----\n{infos_table}",
-                host = instance.machine_host.as_ref().unwrap(),
-                port = instance.port.unwrap(),
-                time = &utils::time::now_str(utils::time::NORMAL_FMT),
-                total=infos.len(),
-                filter_sleep = filter_infos_sleep.len(),
-                infos_table = infos_table,
-            );
-
-        let now_timestamp = utils::time::now_datetime().timestamp();
-
-        // 打开文件, 并且追加内容
-        let file_path = format!(
-            "{dir}/{host}_{port}.txt",
-            dir = &cfg.output_dir,
-            host = instance.machine_host.as_ref().unwrap(),
-            port = instance.port.unwrap()
-        );
-
-        let mut open_ops = fs::OpenOptions::new();
-        if !Path::new(&file_path).exists() {
-            open_ops.create_new(true).append(true);
-        } else {
-            // 达到需要清理文件的时间
-            if now_timestamp - *clean_timestamp >= cfg.clear_file_duration {
-                open_ops.write(true).truncate(true);
-
-                // 清理后清理时间变成当前时间
-                *clean_timestamp = now_timestamp;
-            } else {
-                // append打开
-                open_ops.append(true);
-            }
-        }
-
-        // 打开文件
-        let mut file = open_ops.open(&file_path).map_err(|e| {
-            CustomError::new(format!(
-                "打开文件出错. 路径: {file_path}. {e}",
-                file_path = &file_path,
-                e = e.to_string()
-            ))
-        })?;
-        // processlist写入文件
-        file.write_all(log_data.as_bytes()).map_err(|e| {
-            CustomError::new(format!(
-                "写入 processlist 信息失败, 文件: {file_path}, {e}",
-                file_path = &file_path,
-                e = e.to_string()
-            ))
-        })?;
-    }
-
-    Ok(())
+    // 实例已经不存在, 被移除了就不跑了
+    collector::run_instance(cfg, instance, OutputSink::File, || {
+        exists_instance(old_set, instance)
+    })
+    .await
 }
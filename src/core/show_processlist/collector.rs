@@ -0,0 +1,286 @@
+use crate::config::show_processlist_conf::ShowProcesslistConf;
+use crate::core::show_processlist::collector_state::CollectorState;
+use crate::core::show_processlist::handler::print_data;
+use crate::core::show_processlist::health::InstanceHealth;
+use crate::core::show_processlist::output_file::OutputKind;
+use crate::core::show_processlist::source::ProcesslistSource;
+use crate::core::show_processlist::{
+    alert, common, connection_budget, diagnostic, explain, history, killer, metrics, output_file,
+    pre_trigger, reload, saturation, shutdown,
+};
+use crate::error::CustomError;
+use crate::models::{ConnectionSaturation, Instance, ShowProcesslistInfo};
+use crate::utils;
+use sqlx::{MySql, Pool};
+
+// 采集结果的输出方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputSink {
+    Print,  // --host --port, --vip-port: text 格式记录日志, jsonl 格式输出到标准输出
+    File,   // --all: 写入实例对应的文件
+    Merged, // --merged-view: sql 事件直接输出, 快照在所有实例完成后合并输出
+}
+
+impl OutputSink {
+    async fn write_query_events(&self, cfg: &ShowProcesslistConf, instance: &Instance, data: &str) {
+        match self {
+            OutputSink::File => {
+                if let Err(e) =
+                    output_file::append(cfg, instance, OutputKind::QueryEvents, data).await
+                {
+                    log::error!("写入 sql 事件失败. {e}", e = e);
+                }
+            }
+            _ => print_data(cfg, data),
+        }
+    }
+
+    async fn write_snapshot(
+        &self,
+        cfg: &ShowProcesslistConf,
+        instance: &Instance,
+        state: &mut CollectorState,
+        data: &str,
+    ) -> Result<(), CustomError> {
+        match self {
+            OutputSink::File => {
+                output_file::write_processlist(cfg, instance, &mut state.clean_timestamp, data)
+                    .await
+            }
+            _ => {
+                print_data(cfg, data);
+                Ok(())
+            }
+        }
+    }
+}
+
+// 一次 processlist 的结果
+#[derive(Debug, Clone, Default)]
+pub struct PollResult {
+    pub total: usize,
+    pub output_infos: Vec<ShowProcesslistInfo>,
+    pub threshold: usize,
+    pub saturation: Option<ConnectionSaturation>,
+    pub is_incident: bool,
+}
+
+// 单个实例的采集循环, 链接失败或者连续失败达到 --down-threshold 后重新链接.
+// is_running 返回 false (例如实例被移除) 或者收到退出信号后结束
+pub async fn run_instance<F>(
+    cfg: &ShowProcesslistConf,
+    instance: &Instance,
+    sink: OutputSink,
+    is_running: F,
+) -> Result<(), CustomError>
+where
+    F: Fn() -> bool,
+{
+    let host_port = format!(
+        "{host}:{port}",
+        host = instance.machine_host.as_ref().unwrap(),
+        port = instance.port.unwrap()
+    );
+    let mut health = InstanceHealth::new(cfg, &host_port);
+    let mut state = CollectorState::new(cfg)?;
+    // 重新加载配置后使用新的配置, 不需要重新链接
+    let mut cfg = cfg.clone();
+    let mut reload_rx = reload::subscribe();
+
+    while is_running() && !shutdown::is_shutdown() {
+        health.on_connecting();
+        let connect_result = tokio::select! {
+            v = common::connect_instance(&cfg, instance) => v,
+            _ = shutdown::wait() => break,
+        };
+        let db = match connect_result {
+            Ok(v) => v,
+            Err(e) => {
+                metrics::record_error(instance);
+                shutdown::sleep(health.on_connect_error(&e)).await;
+                continue;
+            }
+        };
+        (state.source, state.version) = ProcesslistSource::detect(&cfg, &db, instance).await;
+
+        // 循环执行 processlist, 当前这一次执行完成后才检查退出信号, 保证快照完整输出
+        while is_running() && !shutdown::is_shutdown() {
+            reload::apply_changed(&mut reload_rx, instance, &mut cfg, &mut state);
+            match poll_processlist(&cfg, instance, &db, &mut state, sink).await {
+                Ok(_) => {
+                    state.poll_cnt += 1;
+                    health.on_success();
+                    // 休眠多少毫秒
+                    shutdown::sleep(connection_budget::get_poll_sleep(
+                        &cfg,
+                        state.poll_sleep.get_sleep(&cfg),
+                    ))
+                    .await;
+                }
+                Err(e) => {
+                    metrics::record_error(instance);
+                    match health.on_error(&e) {
+                        Some(delay) => {
+                            shutdown::sleep(delay).await;
+                        }
+                        None => break,
+                    }
+                }
+            }
+        }
+
+        let _ = db.close().await;
+    }
+    shutdown::record_collector(state.poll_cnt, state.snapshot_cnt);
+
+    Ok(())
+}
+
+// 执行一次 processlist, 并处理 kill, 事件, 告警, 链接饱和, 超过阈值时输出快照和采集诊断信息.
+// 合并模式只返回结果, 快照在所有实例完成后合并输出
+pub async fn poll_processlist(
+    cfg: &ShowProcesslistConf,
+    instance: &Instance,
+    db: &Pool<MySql>,
+    state: &mut CollectorState,
+    sink: OutputSink,
+) -> Result<PollResult, CustomError> {
+    let host_port = format!(
+        "{host}:{port}",
+        host = instance.machine_host.as_ref().unwrap(),
+        port = instance.port.unwrap()
+    );
+    let infos = state.source.show_processlist(db).await.map_err(|e| {
+        CustomError::new(format!(
+            "{host_port}, 获取processlist信息失败. {e}",
+            host_port = &host_port,
+            e = e
+        ))
+    })?;
+    metrics::record_success(instance, &infos);
+
+    // kill 匹配规则的线程
+    if let Some(rule) = &state.kill_rule {
+        killer::kill_by_rule(
+            rule,
+            &mut state.kill_audited,
+            db,
+            instance.machine_host.as_ref().unwrap(),
+            instance.port.unwrap(),
+            &infos,
+        )
+        .await;
+    }
+
+    // 跟踪 sql 开始/结束
+    if let Some(tracker) = state.query_tracker.as_mut() {
+        let events = tracker.update(&infos, utils::time::now_datetime());
+        if !events.is_empty() {
+            let events_data = common::get_query_events_data(
+                instance.machine_host.as_ref().unwrap(),
+                instance.port.unwrap(),
+                instance.cluster_name.as_ref(),
+                &events,
+            );
+            sink.write_query_events(cfg, instance, &events_data).await;
+        }
+    }
+
+    // 过滤 processlist 信息, 需要输出的线程
+    let output_infos = state.filter.filter_output(&infos);
+
+    // 计入阈值的线程
+    let threshold_infos = state.filter.filter_threshold(&infos);
+
+    // 活跃线程数超过告警阈值发送告警
+    if let Some(alerter) = state.alerter.as_mut() {
+        alert::check_and_notify(cfg, alerter, instance, &threshold_infos);
+    }
+
+    // 链接接近饱和
+    let saturation = saturation::check_saturation(cfg, db, instance, &infos).await;
+
+    // 满足 --threshold-filter 的processlist 超过了指定数或者链接接近饱和需要进行记录
+    let is_incident =
+        threshold_infos.len() >= cfg.print_cnt_threshold as usize || saturation.is_some();
+    let poll = PollResult {
+        total: infos.len(),
+        output_infos,
+        threshold: threshold_infos.len(),
+        saturation,
+        is_incident,
+    };
+
+    // 合并模式由集群统一调整采集间隔和输出快照
+    if sink != OutputSink::Merged {
+        // 超过阈值或者链接接近饱和时缩短采集间隔
+        state.poll_sleep.update(cfg, &host_port, is_incident);
+
+        if is_incident {
+            write_snapshot(cfg, instance, db, state, sink, &infos, &poll).await?;
+        } else if let Some(buffer) = state.pre_trigger.as_mut() {
+            buffer.push(pre_trigger::BufferedSnapshot {
+                capture_time: utils::time::now_str(utils::time::MILLIS_FMT),
+                all_infos: infos,
+                infos: poll.output_infos.clone(),
+            });
+        }
+    }
+
+    // 采集诊断信息, 输出到文件时写入实例对应的诊断文件
+    if is_incident && state.need_diagnostic() {
+        diagnostic::spawn_capture(cfg, instance, sink == OutputSink::File);
+    }
+
+    Ok(poll)
+}
+
+// 输出超过阈值的快照, 先输出超过阈值前保存的快照
+async fn write_snapshot(
+    cfg: &ShowProcesslistConf,
+    instance: &Instance,
+    db: &Pool<MySql>,
+    state: &mut CollectorState,
+    sink: OutputSink,
+    infos: &[ShowProcesslistInfo],
+    poll: &PollResult,
+) -> Result<(), CustomError> {
+    let mut snapshot_data = match state.pre_trigger.as_mut() {
+        Some(buffer) => pre_trigger::get_pre_trigger_data(cfg, instance, &buffer.take()),
+        None => String::new(),
+    };
+    let snapshot = common::Snapshot {
+        instance,
+        capture_time: utils::time::now_str(utils::time::MILLIS_FMT),
+        all_infos: infos,
+        infos: &poll.output_infos,
+        lock_waits: common::get_snapshot_lock_waits(
+            cfg,
+            db,
+            instance,
+            state.version.as_deref(),
+            infos,
+        )
+        .await,
+        saturation: poll.saturation.clone(),
+        explains: explain::capture(
+            cfg,
+            db,
+            instance,
+            state.explain.as_mut(),
+            state.version.as_deref(),
+            &poll.output_infos,
+        )
+        .await,
+        pre_trigger: false,
+    };
+    snapshot_data.push_str(&common::get_snapshot_data(cfg, &snapshot));
+
+    sink.write_snapshot(cfg, instance, state, &snapshot_data)
+        .await?;
+    state.snapshot_cnt += 1;
+    // 保存到历史库
+    history::save(instance, &poll.output_infos);
+
+    Ok(())
+}
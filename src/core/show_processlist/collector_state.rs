@@ -0,0 +1,134 @@
+use crate::config::show_processlist_conf::ShowProcesslistConf;
+use crate::core::show_processlist::adaptive_sleep::AdaptiveSleep;
+use crate::core::show_processlist::alert::Alerter;
+use crate::core::show_processlist::diagnostic::DiagnosticLimiter;
+use crate::core::show_processlist::explain::ExplainCapturer;
+use crate::core::show_processlist::filter::ProcesslistFilter;
+use crate::core::show_processlist::killer::KillRule;
+use crate::core::show_processlist::lifecycle::QueryTracker;
+use crate::core::show_processlist::pre_trigger::PreTriggerBuffer;
+use crate::core::show_processlist::source::ProcesslistSource;
+use crate::error::CustomError;
+use crate::utils;
+use std::collections::HashMap;
+
+// 每个实例 processlist 循环之间需要保留的状态
+pub struct CollectorState {
+    pub clean_timestamp: i64,      // 上一次清理输出文件的时间
+    pub source: ProcesslistSource, // 每次创建链接后根据版本重新检测
+    pub version: Option<String>,   // 每次创建链接后检测的数据库版本
+    pub filter: ProcesslistFilter,
+    pub kill_rule: Option<KillRule>,
+    pub kill_audited: HashMap<u64, String>, // dry-run 已经记录过的线程 id 和 sql
+    pub query_tracker: Option<QueryTracker>,
+    pub diagnostic_limiter: Option<DiagnosticLimiter>,
+    pub alerter: Option<Alerter>,
+    pub explain: Option<ExplainCapturer>,
+    pub pre_trigger: Option<PreTriggerBuffer>, // 超过阈值前的快照
+    pub poll_sleep: AdaptiveSleep,             // 超过阈值时缩短采集间隔
+    pub poll_cnt: u64,                         // 成功执行 processlist 的次数
+    pub snapshot_cnt: u64,                     // 输出快照的次数
+}
+
+impl CollectorState {
+    pub fn new(cfg: &ShowProcesslistConf) -> Result<CollectorState, CustomError> {
+        let query_tracker = if cfg.track_queries {
+            Some(QueryTracker::new(cfg.track_min_time))
+        } else {
+            None
+        };
+
+        let diagnostic_limiter = if cfg.diagnostic {
+            Some(DiagnosticLimiter::new(cfg.diagnostic_interval))
+        } else {
+            None
+        };
+
+        Ok(CollectorState {
+            clean_timestamp: utils::time::now_timestamp(),
+            source: ProcesslistSource::InformationSchema,
+            version: None,
+            filter: ProcesslistFilter::new(cfg)?,
+            kill_rule: KillRule::new(cfg)?,
+            kill_audited: HashMap::new(),
+            query_tracker,
+            diagnostic_limiter,
+            alerter: Alerter::new(cfg)?,
+            explain: ExplainCapturer::new(cfg),
+            pre_trigger: PreTriggerBuffer::new(cfg.pre_trigger_snapshots),
+            poll_sleep: AdaptiveSleep::new(),
+            poll_cnt: 0,
+            snapshot_cnt: 0,
+        })
+    }
+
+    // 重新加载配置后更新过滤表达式, kill 规则等, 保留链接和数据来源.
+    // 配置有误时返回错误, 状态保持不变
+    pub fn reload(
+        &mut self,
+        old_cfg: &ShowProcesslistConf,
+        cfg: &ShowProcesslistConf,
+    ) -> Result<(), CustomError> {
+        let filter = ProcesslistFilter::new(cfg)?;
+        let kill_rule = KillRule::new(cfg)?;
+
+        // 告警 webhook 没有变化时保留告警状态, 避免重复告警
+        let alerter = match self.alerter.take() {
+            Some(mut alerter)
+                if cfg.alert_webhook_url == old_cfg.alert_webhook_url
+                    && cfg.alert_timeout == old_cfg.alert_timeout =>
+            {
+                alerter.set_threshold(cfg.get_alert_threshold(), cfg.alert_cooldown);
+                Some(alerter)
+            }
+            alerter => match Alerter::new(cfg) {
+                Ok(v) => v,
+                Err(e) => {
+                    self.alerter = alerter;
+                    return Err(e);
+                }
+            },
+        };
+
+        if cfg.track_queries != old_cfg.track_queries
+            || cfg.track_min_time != old_cfg.track_min_time
+        {
+            self.query_tracker = if cfg.track_queries {
+                Some(QueryTracker::new(cfg.track_min_time))
+            } else {
+                None
+            };
+        }
+        if cfg.diagnostic != old_cfg.diagnostic
+            || cfg.diagnostic_interval != old_cfg.diagnostic_interval
+        {
+            self.diagnostic_limiter = if cfg.diagnostic {
+                Some(DiagnosticLimiter::new(cfg.diagnostic_interval))
+            } else {
+                None
+            };
+        }
+        if cfg.explain_min_time != old_cfg.explain_min_time
+            || cfg.explain_window != old_cfg.explain_window
+            || cfg.explain_limit != old_cfg.explain_limit
+        {
+            self.explain = ExplainCapturer::new(cfg);
+        }
+        if cfg.pre_trigger_snapshots != old_cfg.pre_trigger_snapshots {
+            self.pre_trigger = PreTriggerBuffer::new(cfg.pre_trigger_snapshots);
+        }
+        self.filter = filter;
+        self.kill_rule = kill_rule;
+        self.alerter = alerter;
+
+        Ok(())
+    }
+
+    // 是否需要采集诊断信息, 没有开启或者被限流返回 false
+    pub fn need_diagnostic(&mut self) -> bool {
+        match self.diagnostic_limiter.as_mut() {
+            Some(limiter) => limiter.try_acquire(utils::time::now_timestamp()),
+            None => false,
+        }
+    }
+}
@@ -1,7 +1,277 @@
-use crate::models::ShowProcesslistInfo;
+use crate::config::show_processlist_conf::ShowProcesslistConf;
+use crate::core::lock_waits;
+use crate::core::show_processlist::{explain, saturation, summary};
+use crate::error::CustomError;
+use crate::models::snapshot_record::{
+    RECORD_TYPE_CONNECTION_SATURATION, RECORD_TYPE_EXPLAIN, RECORD_TYPE_FINGERPRINT_SUMMARY,
+    RECORD_TYPE_GROUP_SUMMARY, RECORD_TYPE_LOCK_WAIT, RECORD_TYPE_PROCESSLIST,
+    RECORD_TYPE_QUERY_EVENT,
+};
+use crate::models::{
+    ConnectionSaturation, DynamicRows, ExplainPlan, Instance, LockWait, QueryEvent,
+    ShowProcesslistInfo, SnapshotRecord,
+};
+use crate::{rdbc, utils};
 use prettytable::{format, Cell, Row, Table};
+use serde::Serialize;
+use sqlx::{MySql, Pool};
 
-pub fn get_infos_table(infos: &Vec<ShowProcesslistInfo>) -> String {
+// 一次需要输出的 processlist 快照
+pub struct Snapshot<'a> {
+    pub instance: &'a Instance,
+    pub capture_time: String,
+    pub all_infos: &'a [ShowProcesslistInfo], // 所有 processlist
+    pub infos: &'a [ShowProcesslistInfo],     // 过滤掉 Sleep 后的 processlist
+    pub lock_waits: Vec<LockWait>,
+    pub saturation: Option<ConnectionSaturation>, // 链接接近饱和时的信息
+    pub explains: Vec<ExplainPlan>,
+    pub pre_trigger: bool, // 超过阈值前保存的快照
+}
+
+// 创建实例的 processlist 链接
+pub async fn connect_instance(
+    cfg: &ShowProcesslistConf,
+    instance: &Instance,
+) -> Result<Pool<MySql>, CustomError> {
+    let password = cfg.get_password();
+    rdbc::get_db_by_default(
+        instance.machine_host.as_ref().unwrap(),
+        instance.port.unwrap() as i16,
+        &cfg.username,
+        &password,
+        "",
+        cfg.is_sql_log,
+    )
+    .await
+    .map_err(|e| {
+        CustomError::new(format!(
+            "创建需要执行 processlist 数据库链接失败. host:port:{host}:{port}. {e}",
+            host = instance.machine_host.as_ref().unwrap(),
+            port = instance.port.unwrap(),
+            e = e
+        ))
+    })
+}
+
+// 开启 --lock-waits 时获取快照中的锁等待信息, 使用本次 processlist 的结果和链接时检测的版本.
+// 获取失败只记录日志
+pub async fn get_snapshot_lock_waits(
+    cfg: &ShowProcesslistConf,
+    db: &Pool<MySql>,
+    instance: &Instance,
+    version: Option<&str>,
+    infos: &[ShowProcesslistInfo],
+) -> Vec<LockWait> {
+    if !cfg.lock_waits {
+        return Vec::new();
+    }
+
+    let result = match version {
+        Some(version) => lock_waits::common::get_lock_waits_by_infos(db, version, infos).await,
+        None => Err(CustomError::new(String::from("没有获取到数据库版本"))),
+    };
+    match result {
+        Ok(v) => v,
+        Err(e) => {
+            log::error!(
+                "{host}:{port}, 获取锁等待信息失败. {e}",
+                host = instance.machine_host.as_ref().unwrap(),
+                port = instance.port.unwrap(),
+                e = e
+            );
+            Vec::new()
+        }
+    }
+}
+
+// 生成一次 processlist 快照需要输出的内容, 根据 --output-format 输出 text 或 jsonl
+pub fn get_snapshot_data(cfg: &ShowProcesslistConf, snapshot: &Snapshot) -> String {
+    let time = &snapshot.capture_time;
+    let instance = format!(
+        "{host}:{port}",
+        host = snapshot.instance.machine_host.as_ref().unwrap(),
+        port = snapshot.instance.port.unwrap()
+    );
+    let cluster_name = snapshot.instance.cluster_name.as_ref();
+    let infos = snapshot.infos;
+
+    // sql 指纹汇总
+    let fingerprint_summaries = if cfg.fingerprint_summary {
+        let mut summaries = summary::get_fingerprint_summaries(infos);
+        summaries.truncate(cfg.fingerprint_summary_limit);
+        summaries
+    } else {
+        Vec::new()
+    };
+
+    // 分组线程数, 包含 Sleep 线程
+    let group_summaries = if cfg.group_summary {
+        summary::get_group_summaries(snapshot.all_infos, cfg.group_summary_limit)
+    } else {
+        Vec::new()
+    };
+
+    if cfg.is_jsonl() {
+        let mut data = build_records_jsonl(
+            RECORD_TYPE_FINGERPRINT_SUMMARY,
+            &instance,
+            cluster_name,
+            time,
+            &fingerprint_summaries,
+            snapshot.pre_trigger,
+        );
+        data.push_str(&build_records_jsonl(
+            RECORD_TYPE_GROUP_SUMMARY,
+            &instance,
+            cluster_name,
+            time,
+            &group_summaries,
+            snapshot.pre_trigger,
+        ));
+        data.push_str(&build_records_jsonl(
+            RECORD_TYPE_CONNECTION_SATURATION,
+            &instance,
+            cluster_name,
+            time,
+            snapshot.saturation.as_slice(),
+            snapshot.pre_trigger,
+        ));
+        data.push_str(&build_records_jsonl(
+            RECORD_TYPE_LOCK_WAIT,
+            &instance,
+            cluster_name,
+            time,
+            &snapshot.lock_waits,
+            snapshot.pre_trigger,
+        ));
+        data.push_str(&build_records_jsonl(
+            RECORD_TYPE_EXPLAIN,
+            &instance,
+            cluster_name,
+            time,
+            &snapshot.explains,
+            snapshot.pre_trigger,
+        ));
+        data.push_str(&build_records_jsonl(
+            RECORD_TYPE_PROCESSLIST,
+            &instance,
+            cluster_name,
+            time,
+            infos,
+            snapshot.pre_trigger,
+        ));
+        return data;
+    }
+
+    let mut data = format!(
+        "\n---- {pre_trigger}{instance} Time: {time}, Total: {total}, Filter Sleep: {filter_sleep} ----\n",
+        pre_trigger = if snapshot.pre_trigger {
+            "[pre-trigger] "
+        } else {
+            ""
+        },
+        instance = &instance,
+        time = time,
+        total = snapshot.all_infos.len(),
+        filter_sleep = infos.len(),
+    );
+    if let Some(saturation) = &snapshot.saturation {
+        data.push_str("Connection Saturation:\n");
+        data.push_str(&saturation::get_saturation_text(saturation));
+    }
+    if !fingerprint_summaries.is_empty() {
+        data.push_str("Top SQL Fingerprint:\n");
+        data.push_str(&summary::get_fingerprint_summary_table(
+            &fingerprint_summaries,
+        ));
+    }
+    if !group_summaries.is_empty() {
+        data.push_str("Group Summary:\n");
+        data.push_str(&summary::get_group_summary_table(&group_summaries));
+    }
+    if !snapshot.lock_waits.is_empty() {
+        data.push_str("Lock Waits:\n");
+        data.push_str(&lock_waits::common::get_lock_wait_tree(
+            &snapshot.lock_waits,
+        ));
+    }
+    if !snapshot.explains.is_empty() {
+        data.push_str("Explain:\n");
+        data.push_str(&explain::get_explain_text(&snapshot.explains));
+    }
+    if snapshot.saturation.is_some()
+        || !snapshot.explains.is_empty()
+        || !fingerprint_summaries.is_empty()
+        || !group_summaries.is_empty()
+        || !snapshot.lock_waits.is_empty()
+    {
+        data.push_str("Processlist:\n");
+    }
+    data.push_str(&get_infos_table(infos));
+
+    data
+}
+
+// sql 开始/结束事件, 每个事件一行 json
+pub fn get_query_events_data(
+    host: &str,
+    port: i32,
+    cluster_name: Option<&String>,
+    events: &[QueryEvent],
+) -> String {
+    get_records_jsonl(
+        RECORD_TYPE_QUERY_EVENT,
+        &format!("{host}:{port}", host = host, port = port),
+        cluster_name,
+        &utils::time::now_str(utils::time::NORMAL_FMT),
+        events,
+    )
+}
+
+// 每一个元素生成一个 json 对象, 一个对象一行
+pub fn get_records_jsonl<T: Serialize + Clone>(
+    record_type: &str,
+    instance: &str,
+    cluster_name: Option<&String>,
+    capture_time: &str,
+    items: &[T],
+) -> String {
+    build_records_jsonl(
+        record_type,
+        instance,
+        cluster_name,
+        capture_time,
+        items,
+        false,
+    )
+}
+
+fn build_records_jsonl<T: Serialize + Clone>(
+    record_type: &str,
+    instance: &str,
+    cluster_name: Option<&String>,
+    capture_time: &str,
+    items: &[T],
+    pre_trigger: bool,
+) -> String {
+    let mut data = String::new();
+    for item in items.iter() {
+        let record = SnapshotRecord {
+            record_type: record_type.to_string(),
+            instance: instance.to_string(),
+            cluster_name: cluster_name.cloned(),
+            capture_time: capture_time.to_string(),
+            pre_trigger,
+            data: item.clone(),
+        };
+        data.push_str(&utils::string::to_json_str(&record));
+        data.push('\n');
+    }
+
+    data
+}
+
+pub fn get_infos_table(infos: &[ShowProcesslistInfo]) -> String {
     let mut table = Table::new();
     table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
     // 设置title
@@ -17,49 +287,43 @@ pub fn get_infos_table(infos: &Vec<ShowProcesslistInfo>) -> String {
     ]));
 
     for info in infos.iter() {
-        let mut info_vec = Vec::new();
-        info_vec.push(Cell::new(&info.id.clone().unwrap().to_string()));
-        info_vec.push(Cell::new(
-            info.user
-                .clone()
-                .or(Some(String::from("")))
-                .as_ref()
-                .unwrap(),
-        ));
-        info_vec.push(Cell::new(
-            info.host
-                .clone()
-                .or(Some(String::from("")))
-                .as_ref()
-                .unwrap(),
-        ));
-        info_vec.push(Cell::new(
-            info.db.clone().or(Some(String::from(""))).as_ref().unwrap(),
-        ));
-        info_vec.push(Cell::new(
-            info.command
-                .clone()
-                .or(Some(String::from("")))
-                .as_ref()
-                .unwrap(),
-        ));
-        info_vec.push(Cell::new(&info.time.clone().unwrap().to_string()));
-        info_vec.push(Cell::new(
-            info.state
-                .clone()
-                .or(Some(String::from("")))
-                .as_ref()
-                .unwrap(),
-        ));
-        info_vec.push(Cell::new(
-            info.info
-                .clone()
-                .or(Some(String::from("")))
-                .as_ref()
-                .unwrap(),
-        ));
+        table.add_row(Row::new(get_info_cells(info)));
+    }
 
-        table.add_row(Row::new(info_vec));
+    table.to_string()
+}
+
+// processlist 表格一行的单元格: Id, User, Host, db, Command, Time, State, Info
+pub fn get_info_cells(info: &ShowProcesslistInfo) -> Vec<Cell> {
+    vec![
+        Cell::new(&info.id.unwrap_or_default().to_string()),
+        Cell::new(info.user.as_deref().unwrap_or("")),
+        Cell::new(info.host.as_deref().unwrap_or("")),
+        Cell::new(info.db.as_deref().unwrap_or("")),
+        Cell::new(info.command.as_deref().unwrap_or("")),
+        Cell::new(&info.time.unwrap_or_default().to_string()),
+        Cell::new(info.state.as_deref().unwrap_or("")),
+        Cell::new(info.info.as_deref().unwrap_or("")),
+    ]
+}
+
+pub fn get_dynamic_rows_table(rows: &DynamicRows) -> String {
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+    // 设置title
+    table.set_titles(Row::new(
+        rows.columns
+            .iter()
+            .map(|column| Cell::new(column))
+            .collect(),
+    ));
+
+    for row in rows.rows.iter() {
+        table.add_row(Row::new(
+            row.iter()
+                .map(|value| Cell::new(value.as_deref().unwrap_or("NULL")))
+                .collect(),
+        ));
     }
 
     table.to_string()
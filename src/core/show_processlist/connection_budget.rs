@@ -0,0 +1,128 @@
+use crate::config::show_processlist_conf::ShowProcesslistConf;
+use crate::error::CustomError;
+use rand::Rng;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::sync::{Mutex, OwnedSemaphorePermit, Semaphore};
+use tokio::time::Instant;
+
+// 全局链接预算, 没有指定 --connection-budget 时为空, 获取链接许可直接返回 None
+static BUDGET: OnceLock<Budget> = OnceLock::new();
+// 全局新建链接速率, 没有指定 --connect-rate 时为空
+static CONNECT_RATE: OnceLock<ConnectRate> = OnceLock::new();
+
+struct Budget {
+    semaphore: Arc<Semaphore>,
+    size: usize,
+}
+
+struct ConnectRate {
+    interval: Duration,
+    next: Mutex<Instant>, // 下一次允许新建链接的时间
+}
+
+// 使用 --all 参数时初始化链接预算和新建链接速率
+pub fn init(cfg: &ShowProcesslistConf) {
+    if cfg.connection_budget > 0 {
+        BUDGET.get_or_init(|| Budget {
+            semaphore: Arc::new(Semaphore::new(cfg.connection_budget)),
+            size: cfg.connection_budget,
+        });
+    }
+    if cfg.connect_rate > 0 {
+        CONNECT_RATE.get_or_init(|| ConnectRate {
+            interval: Duration::from_secs(1) / cfg.connect_rate,
+            next: Mutex::new(Instant::now()),
+        });
+    }
+}
+
+// 新建实例链接前调用, 等待链接预算和新建链接速率. 返回的许可在链接关闭后才能释放
+pub async fn acquire(host_port: &str) -> Option<OwnedSemaphorePermit> {
+    let permit = match BUDGET.get() {
+        Some(budget) => {
+            if budget.semaphore.available_permits() == 0 {
+                log::warn!(
+                    "{host_port}, 打开的链接数达到 --connection-budget 上限, 等待其他实例释放链接",
+                    host_port = host_port
+                );
+            }
+            // 信号量不会被关闭
+            budget.semaphore.clone().acquire_owned().await.ok()
+        }
+        None => None,
+    };
+
+    if let Some(rate) = CONNECT_RATE.get() {
+        let wait_until = {
+            let mut next = rate.next.lock().await;
+            let wait_until = (*next).max(Instant::now());
+            *next = wait_until + rate.interval;
+            wait_until
+        };
+        tokio::time::sleep_until(wait_until).await;
+    }
+
+    permit
+}
+
+// 每个实例的采集任务一直占用一个链接, 链接预算小于实例数时超出的实例会一直等待, 启动时直接报错
+pub fn check_instance_count(cnt: usize) -> Result<(), CustomError> {
+    match BUDGET.get() {
+        Some(budget) if cnt > budget.size => Err(CustomError::new(format!(
+            "实例数 {cnt} 超过 --connection-budget {size}, 每个实例需要一个链接, 请调大 --connection-budget",
+            cnt = cnt,
+            size = budget.size
+        ))),
+        _ => Ok(()),
+    }
+}
+
+// 已经采集 monitored 个实例时, 还可以新增多少个实例, 没有指定 --connection-budget 时不限制
+pub fn get_instance_capacity(monitored: usize) -> usize {
+    match BUDGET.get() {
+        Some(budget) => budget.size.saturating_sub(monitored),
+        None => usize::MAX,
+    }
+}
+
+// 临时链接(采集诊断信息)使用, 不等待, 预算不足直接返回错误
+pub fn try_acquire(cnt: u32) -> Result<Option<OwnedSemaphorePermit>, CustomError> {
+    match BUDGET.get() {
+        Some(budget) => budget
+            .semaphore
+            .clone()
+            .try_acquire_many_owned(cnt)
+            .map(Some)
+            .map_err(|_| {
+                CustomError::new(format!(
+                    "打开的链接数达到 --connection-budget 上限, 剩余: {available}, 需要: {cnt}",
+                    available = budget.semaphore.available_permits(),
+                    cnt = cnt
+                ))
+            }),
+        None => Ok(None),
+    }
+}
+
+// 实例第一次执行 processlist 前随机等待 0 ~ sleep 毫秒, 错开各个实例执行的时间点
+pub fn get_start_delay(cfg: &ShowProcesslistConf) -> Duration {
+    if cfg.sleep == 0 {
+        return Duration::ZERO;
+    }
+
+    Duration::from_millis(rand::thread_rng().gen_range(0..cfg.sleep))
+}
+
+// 每次 processlist 之间的休眠时间, sleep 加上 0 ~ poll_jitter 毫秒的随机值.
+// 随机值不超过 sleep, 避免缩短采集间隔时随机值占大部分
+pub fn get_poll_sleep(cfg: &ShowProcesslistConf, sleep: u64) -> Duration {
+    let max_jitter = cfg.poll_jitter.min(sleep);
+    let jitter = if max_jitter > 0 {
+        rand::thread_rng().gen_range(0..=max_jitter)
+    } else {
+        0
+    };
+
+    Duration::from_millis(sleep + jitter)
+}
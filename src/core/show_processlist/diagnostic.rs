@@ -0,0 +1,196 @@
+use crate::config::show_processlist_conf::ShowProcesslistConf;
+use crate::core::show_processlist::output_file::OutputKind;
+use crate::core::show_processlist::{common, connection_budget, output_file, shutdown};
+use crate::dao::NormalDao;
+use crate::error::CustomError;
+use crate::models::snapshot_record::RECORD_TYPE_DIAGNOSTIC;
+use crate::models::{DynamicRows, Instance};
+use crate::{rdbc, utils};
+use serde::{Deserialize, Serialize};
+use sqlx::{MySql, Pool};
+
+// 并行采集的 sql 数, 诊断信息使用单独的链接池
+const DIAGNOSTIC_MAX_CONNECTIONS: u32 = 4;
+
+const INNODB_STATUS_SQL: &str = "SHOW ENGINE INNODB STATUS";
+const INNODB_TRX_SQL: &str = "SELECT * FROM information_schema.INNODB_TRX";
+const GLOBAL_STATUS_SQL: &str = "SHOW GLOBAL STATUS";
+// sys.innodb_lock_waits 在 5.7 和 8.0 都存在
+const LOCK_WAITS_SQL: &str = "SELECT * FROM sys.innodb_lock_waits";
+
+// 诊断信息中的一项
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct DiagnosticSection {
+    pub name: String,
+    pub error: Option<String>,
+    #[serde(flatten)]
+    pub rows: DynamicRows,
+}
+
+// 诊断信息采集限流, 同一个实例两次采集需要间隔 min_interval 秒
+pub struct DiagnosticLimiter {
+    min_interval: i64,
+    last_capture_timestamp: Option<i64>,
+}
+
+impl DiagnosticLimiter {
+    pub fn new(min_interval: u64) -> DiagnosticLimiter {
+        DiagnosticLimiter {
+            min_interval: min_interval as i64,
+            last_capture_timestamp: None,
+        }
+    }
+
+    // 是否可以进行采集, 可以采集时记录采集时间
+    pub fn try_acquire(&mut self, now_timestamp: i64) -> bool {
+        if let Some(last) = self.last_capture_timestamp {
+            if now_timestamp - last < self.min_interval {
+                return false;
+            }
+        }
+
+        self.last_capture_timestamp = Some(now_timestamp);
+        true
+    }
+}
+
+// 异步采集诊断信息, 不阻塞 processlist 循环.
+// to_file 为 false 时, text 格式记录日志, jsonl 格式输出到标准输出
+pub fn spawn_capture(cfg: &ShowProcesslistConf, instance: &Instance, to_file: bool) {
+    let cfg = cfg.clone();
+    let instance = instance.clone();
+
+    // 退出前需要等待诊断信息写完
+    shutdown::spawn(async move {
+        let host = instance.machine_host.as_ref().unwrap();
+        let port = instance.port.unwrap();
+        let sections = match capture(&cfg, host, port).await {
+            Ok(v) => v,
+            Err(e) => {
+                log::error!(
+                    "{host}:{port}, 采集诊断信息失败. {e}",
+                    host = host,
+                    port = port,
+                    e = e
+                );
+                return;
+            }
+        };
+
+        let data = get_diagnostic_data(&cfg, host, port, instance.cluster_name.as_ref(), &sections);
+        if to_file {
+            if let Err(e) =
+                output_file::append(&cfg, &instance, OutputKind::Diagnostic, &data).await
+            {
+                log::error!("写入诊断信息失败. {e}", e = e);
+            }
+        } else if cfg.is_jsonl() {
+            print!("{}", data);
+        } else {
+            log::info!("{}", data);
+        }
+    });
+}
+
+// 在同一个实例上并行执行所有诊断 sql
+pub async fn capture(
+    cfg: &ShowProcesslistConf,
+    host: &str,
+    port: i32,
+) -> Result<Vec<DiagnosticSection>, CustomError> {
+    // 诊断信息链接也计入链接预算, 预算不足时跳过
+    let _permit = connection_budget::try_acquire(DIAGNOSTIC_MAX_CONNECTIONS)?;
+    let password = cfg.get_password();
+    let db = rdbc::get_db_by_default_with_max_connections(
+        host,
+        port as i16,
+        &cfg.username,
+        &password,
+        "",
+        cfg.is_sql_log,
+        DIAGNOSTIC_MAX_CONNECTIONS,
+    )
+    .await
+    .map_err(|e| {
+        CustomError::new(format!(
+            "创建采集诊断信息数据库链接失败. host:port:{host}:{port}. {e}",
+            host = host,
+            port = port,
+            e = e
+        ))
+    })?;
+
+    let (innodb_status, innodb_trx, global_status, lock_waits) = tokio::join!(
+        query_section(&db, "INNODB STATUS", INNODB_STATUS_SQL),
+        query_section(&db, "INNODB_TRX", INNODB_TRX_SQL),
+        query_section(&db, "GLOBAL STATUS", GLOBAL_STATUS_SQL),
+        query_section(&db, "LOCK WAITS", LOCK_WAITS_SQL),
+    );
+
+    let _ = db.close().await;
+
+    Ok(vec![innodb_status, innodb_trx, global_status, lock_waits])
+}
+
+async fn query_section(db: &Pool<MySql>, name: &str, query: &str) -> DiagnosticSection {
+    match NormalDao::query_dynamic(db, query).await {
+        Ok(rows) => DiagnosticSection {
+            name: name.to_string(),
+            error: None,
+            rows,
+        },
+        Err(e) => DiagnosticSection {
+            name: name.to_string(),
+            error: Some(e.to_string()),
+            rows: DynamicRows::default(),
+        },
+    }
+}
+
+pub fn get_diagnostic_data(
+    cfg: &ShowProcesslistConf,
+    host: &str,
+    port: i32,
+    cluster_name: Option<&String>,
+    sections: &[DiagnosticSection],
+) -> String {
+    let instance = format!("{host}:{port}", host = host, port = port);
+    let time = utils::time::now_str(utils::time::NORMAL_FMT);
+    if cfg.is_jsonl() {
+        return common::get_records_jsonl(
+            RECORD_TYPE_DIAGNOSTIC,
+            &instance,
+            cluster_name,
+            &time,
+            sections,
+        );
+    }
+
+    let mut data = format!(
+        "\n==== {instance} Time: {time}, Diagnostic ====\n",
+        instance = &instance,
+        time = &time
+    );
+    for section in sections.iter() {
+        data.push_str(&format!("---- {name} ----\n", name = &section.name));
+        if let Some(e) = section.error.as_ref() {
+            data.push_str(&format!("采集失败: {e}\n", e = e));
+            continue;
+        }
+
+        // SHOW ENGINE INNODB STATUS 的 Status 是一大段文本, 直接输出
+        if section.name == "INNODB STATUS" {
+            for row in section.rows.rows.iter() {
+                if let Some(Some(status)) = row.last() {
+                    data.push_str(status);
+                    data.push('\n');
+                }
+            }
+            continue;
+        }
+
+        data.push_str(&common::get_dynamic_rows_table(&section.rows));
+    }
+
+    data
+}
@@ -0,0 +1,469 @@
+use crate::config::show_processlist_conf::ShowProcesslistConf;
+use crate::core::show_processlist::{common, connection_budget, source};
+use crate::dao::NormalDao;
+use crate::error::CustomError;
+use crate::models::explain_plan::{EXPLAIN_METHOD_FOR_CONNECTION, EXPLAIN_METHOD_STATEMENT};
+use crate::models::{DynamicRows, ExplainPlan, Instance, ShowProcesslistInfo};
+use crate::utils::fingerprint;
+use crate::{rdbc, utils};
+use sqlx::{MySql, Pool};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::OwnedSemaphorePermit;
+use tokio::time::Instant;
+
+// EXPLAIN FOR CONNECTION 支持的语句, 失败或者版本不支持时只有 SELECT 使用 sql 文本执行 EXPLAIN
+const FOR_CONNECTION_STATEMENTS: [&str; 3] = ["select", "update", "delete"];
+const STATEMENT_SELECT: &str = "select";
+// 一次快照获取执行计划的最长时间, 避免阻塞 processlist 循环
+const CAPTURE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// 选择需要获取执行计划的 sql, 同一个 db 的同一个 sql 指纹在 window 秒内只获取一次
+pub struct ExplainCapturer {
+    min_time: i64,
+    window: i64,
+    limit: usize,
+    captured: HashMap<(String, String), i64>, // (db, sql 指纹) -> 获取执行计划的时间
+}
+
+impl ExplainCapturer {
+    // 没有指定 --explain-min-time 返回 None
+    pub fn new(cfg: &ShowProcesslistConf) -> Option<ExplainCapturer> {
+        if cfg.explain_min_time == 0 {
+            return None;
+        }
+
+        Some(ExplainCapturer {
+            min_time: cfg.explain_min_time as i64,
+            window: cfg.explain_window as i64,
+            limit: cfg.explain_limit,
+            captured: HashMap::new(),
+        })
+    }
+
+    // 返回需要获取执行计划的线程和 sql 指纹, 按执行时间倒序, 最多 limit 个.
+    // 不支持 EXPLAIN FOR CONNECTION 时只选择 SELECT
+    fn select<'a>(
+        &mut self,
+        infos: &'a [ShowProcesslistInfo],
+        now_timestamp: i64,
+        is_for_connection: bool,
+    ) -> Vec<(&'a ShowProcesslistInfo, String)> {
+        self.captured
+            .retain(|_, timestamp| now_timestamp - *timestamp < self.window);
+
+        let mut candidates = infos
+            .iter()
+            .filter(|info| info.time.unwrap_or(0) as i64 >= self.min_time)
+            .filter(|info| {
+                info.info
+                    .as_deref()
+                    .and_then(get_statement_type)
+                    .map(|v| {
+                        if is_for_connection {
+                            FOR_CONNECTION_STATEMENTS.contains(&v.as_str())
+                        } else {
+                            v == STATEMENT_SELECT
+                        }
+                    })
+                    .unwrap_or(false)
+            })
+            .collect::<Vec<&ShowProcesslistInfo>>();
+        candidates.sort_by_key(|info| Reverse(info.time));
+
+        let mut selected = Vec::new();
+        for info in candidates {
+            if selected.len() >= self.limit {
+                break;
+            }
+            let fingerprint = fingerprint::fingerprint(info.info.as_deref().unwrap_or_default());
+            let key = (info.db.clone().unwrap_or_default(), fingerprint.clone());
+            if self.captured.contains_key(&key) {
+                continue;
+            }
+            self.captured.insert(key, now_timestamp);
+            selected.push((info, fingerprint));
+        }
+
+        selected
+    }
+}
+
+// sql 的语句类型, 跳过开头的空白和注释, 返回小写的第一个单词
+fn get_statement_type(sql: &str) -> Option<String> {
+    let mut sql = sql.trim_start();
+    while let Some(rest) = sql.strip_prefix("/*") {
+        sql = rest.split_once("*/")?.1.trim_start();
+    }
+
+    let word = sql
+        .split(|c: char| !c.is_ascii_alphabetic())
+        .next()
+        .unwrap_or_default();
+    if word.is_empty() {
+        None
+    } else {
+        Some(word.to_lowercase())
+    }
+}
+
+// 使用 sql 文本执行 EXPLAIN 前检查 sql, 只允许一条完整的 SELECT, 不满足时跳过.
+// 链接开启了多语句, 引号外的 ; 后面的语句也会被执行; processlist 中的 sql 可能被截断.
+// 注释中的 ; 也当作多条语句处理(/*! */ 中的内容会被执行)
+fn check_explain_sql(sql: &str) -> Result<(), String> {
+    const MULTI_STATEMENTS: &str = "sql 中引号外有 ;, 可能包含多条语句";
+    const TRUNCATED: &str = "sql 中的引号, 注释或者括号没有结束, 可能被截断";
+
+    if get_statement_type(sql).as_deref() != Some(STATEMENT_SELECT) {
+        return Err(String::from("只对 SELECT 使用 sql 文本执行 EXPLAIN"));
+    }
+
+    let mut chars = sql.chars().peekable();
+    let mut depth = 0;
+    while let Some(c) = chars.next() {
+        match c {
+            // 引号中 \ 转义下一个字符(反引号除外), 两个连续的引号表示引号本身
+            '\'' | '"' | '`' => {
+                let mut is_closed = false;
+                while let Some(v) = chars.next() {
+                    if v == '\\' && c != '`' {
+                        chars.next();
+                    } else if v == c {
+                        if chars.peek() == Some(&c) {
+                            chars.next();
+                        } else {
+                            is_closed = true;
+                            break;
+                        }
+                    }
+                }
+                if !is_closed {
+                    return Err(String::from(TRUNCATED));
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = ' ';
+                let mut is_closed = false;
+                for v in chars.by_ref() {
+                    if v == ';' {
+                        return Err(String::from(MULTI_STATEMENTS));
+                    }
+                    if prev == '*' && v == '/' {
+                        is_closed = true;
+                        break;
+                    }
+                    prev = v;
+                }
+                if !is_closed {
+                    return Err(String::from(TRUNCATED));
+                }
+            }
+            // -- 后面需要有空白字符才是注释
+            '-' if chars.peek() == Some(&'-') => {
+                chars.next();
+                if chars.peek().is_none_or(|v| v.is_whitespace()) {
+                    for v in chars.by_ref().take_while(|v| *v != '\n') {
+                        if v == ';' {
+                            return Err(String::from(MULTI_STATEMENTS));
+                        }
+                    }
+                }
+            }
+            '#' => {
+                for v in chars.by_ref().take_while(|v| *v != '\n') {
+                    if v == ';' {
+                        return Err(String::from(MULTI_STATEMENTS));
+                    }
+                }
+            }
+            ';' => return Err(String::from(MULTI_STATEMENTS)),
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            _ => {}
+        }
+    }
+    if depth != 0 {
+        return Err(String::from(TRUNCATED));
+    }
+
+    Ok(())
+}
+
+// 使用 sql 文本执行 EXPLAIN 的临时链接, 一次获取中所有 sql 共用
+struct StatementDb {
+    db: Pool<MySql>,
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+// 输出快照时获取执行时间较长的 sql 的执行计划, 最多执行 CAPTURE_TIMEOUT, 超时后剩余的 sql 不再获取.
+// 5.7 及以上版本优先使用 EXPLAIN FOR CONNECTION 获取实际使用的执行计划, 失败或者版本不支持时 SELECT 在对应的 DB 上执行 EXPLAIN
+pub async fn capture(
+    cfg: &ShowProcesslistConf,
+    db: &Pool<MySql>,
+    instance: &Instance,
+    capturer: Option<&mut ExplainCapturer>,
+    version: Option<&str>,
+    infos: &[ShowProcesslistInfo],
+) -> Vec<ExplainPlan> {
+    let capturer = match capturer {
+        Some(v) => v,
+        None => return Vec::new(),
+    };
+    let is_for_connection = version
+        .map(source::is_explain_for_connection_supported)
+        .unwrap_or(false);
+
+    let deadline = Instant::now() + CAPTURE_TIMEOUT;
+    let mut statement_db: Option<StatementDb> = None;
+    let mut is_timeout = false;
+    let mut plans = Vec::new();
+    for (info, fingerprint) in
+        capturer.select(infos, utils::time::now_timestamp(), is_for_connection)
+    {
+        let mut plan = ExplainPlan {
+            processlist_id: info.id,
+            user: info.user.clone(),
+            db: info.db.clone(),
+            time: info.time,
+            fingerprint,
+            sql: info.info.clone().unwrap_or_default(),
+            method: EXPLAIN_METHOD_FOR_CONNECTION.to_string(),
+            ..Default::default()
+        };
+
+        if !is_timeout {
+            let result = tokio::time::timeout_at(
+                deadline,
+                explain_plan(
+                    cfg,
+                    db,
+                    instance,
+                    &mut statement_db,
+                    is_for_connection,
+                    info,
+                    &mut plan,
+                ),
+            )
+            .await;
+            is_timeout = result.is_err();
+        }
+        if is_timeout {
+            plan.error = Some(format!(
+                "获取执行计划超过 {timeout}s, 跳过",
+                timeout = CAPTURE_TIMEOUT.as_secs()
+            ));
+        }
+        plans.push(plan);
+    }
+
+    // 超时时临时链接上可能还有没有返回的语句, 不等待关闭
+    if let Some(statement_db) = statement_db {
+        if !is_timeout {
+            statement_db.db.close().await;
+        }
+    }
+
+    plans
+}
+
+// 获取一个线程的执行计划, 结果和错误信息写入 plan
+async fn explain_plan(
+    cfg: &ShowProcesslistConf,
+    db: &Pool<MySql>,
+    instance: &Instance,
+    statement_db: &mut Option<StatementDb>,
+    is_for_connection: bool,
+    info: &ShowProcesslistInfo,
+    plan: &mut ExplainPlan,
+) {
+    let for_connection_error = match info.id {
+        _ if !is_for_connection => String::from("数据库版本不支持 EXPLAIN FOR CONNECTION"),
+        Some(id) => {
+            match NormalDao::query_dynamic(db, &format!("EXPLAIN FOR CONNECTION {id}", id = id))
+                .await
+            {
+                Ok(rows) if !rows.rows.is_empty() => {
+                    plan.plan = rows;
+                    return;
+                }
+                Ok(_) => String::from("EXPLAIN FOR CONNECTION 没有返回执行计划"),
+                Err(e) => e.to_string(),
+            }
+        }
+        None => String::from("线程 id 为空"),
+    };
+
+    // 语句已经结束或者版本不支持 EXPLAIN FOR CONNECTION
+    if get_statement_type(&plan.sql).as_deref() == Some(STATEMENT_SELECT) {
+        plan.method = EXPLAIN_METHOD_STATEMENT.to_string();
+        match explain_statement(
+            cfg,
+            instance,
+            statement_db,
+            info.db.as_deref().unwrap_or_default(),
+            &plan.sql,
+        )
+        .await
+        {
+            Ok(rows) => plan.plan = rows,
+            Err(e) => plan.error = Some(e.to_string()),
+        }
+    } else {
+        plan.error = Some(for_connection_error);
+    }
+}
+
+// 在临时链接上切换到 database 后执行 EXPLAIN, 不修改 processlist 链接的默认数据库.
+// 临时链接第一次使用时创建. database 为空时线程的 sql 中的表都带有库名, 不需要切换
+async fn explain_statement(
+    cfg: &ShowProcesslistConf,
+    instance: &Instance,
+    statement_db: &mut Option<StatementDb>,
+    database: &str,
+    sql: &str,
+) -> Result<DynamicRows, CustomError> {
+    check_explain_sql(sql).map_err(|e| CustomError::new(format!("{e}, 跳过", e = e)))?;
+
+    let statement_db = match statement_db {
+        Some(v) => v,
+        None => {
+            let permit = connection_budget::try_acquire(1)?;
+            let password = cfg.get_password();
+            let db = rdbc::get_db_by_default(
+                instance.machine_host.as_ref().unwrap(),
+                instance.port.unwrap() as i16,
+                &cfg.username,
+                &password,
+                "",
+                cfg.is_sql_log,
+            )
+            .await
+            .map_err(|e| {
+                CustomError::new(format!("创建执行 EXPLAIN 的数据库链接失败. {e}", e = e))
+            })?;
+            statement_db.insert(StatementDb {
+                db,
+                _permit: permit,
+            })
+        }
+    };
+
+    if !database.is_empty() {
+        NormalDao::use_database(&statement_db.db, database)
+            .await
+            .map_err(|e| CustomError::new(e.to_string()))?;
+    }
+    NormalDao::query_dynamic(&statement_db.db, &format!("EXPLAIN {sql}", sql = sql))
+        .await
+        .map_err(|e| CustomError::new(e.to_string()))
+}
+
+// 快照中执行计划部分的内容
+pub fn get_explain_text(plans: &[ExplainPlan]) -> String {
+    let mut data = String::new();
+    for plan in plans.iter() {
+        data.push_str(&format!(
+            "Id: {id}, User: {user}, db: {db}, Time: {time}, Method: {method}\n{sql}\n",
+            id = plan.processlist_id.unwrap_or_default(),
+            user = plan.user.as_deref().unwrap_or_default(),
+            db = plan.db.as_deref().unwrap_or_default(),
+            time = plan.time.unwrap_or_default(),
+            method = &plan.method,
+            sql = &plan.sql,
+        ));
+        match &plan.error {
+            Some(e) => data.push_str(&format!("获取执行计划失败. {e}\n", e = e)),
+            None => data.push_str(&common::get_dynamic_rows_table(&plan.plan)),
+        }
+    }
+
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::show_processlist_conf::parse_test_args;
+    use crate::core::show_processlist::explain::{
+        check_explain_sql, get_statement_type, ExplainCapturer,
+    };
+    use crate::models::ShowProcesslistInfo;
+
+    #[test]
+    fn test_get_statement_type() {
+        assert_eq!(
+            get_statement_type("  /* app */ SELECT * FROM t").as_deref(),
+            Some("select")
+        );
+        assert_eq!(
+            get_statement_type("update t set a = 1").as_deref(),
+            Some("update")
+        );
+        assert_eq!(get_statement_type("/* not closed"), None);
+        assert_eq!(get_statement_type(""), None);
+    }
+
+    #[test]
+    fn test_check_explain_sql() {
+        assert!(check_explain_sql("select * from t where name = 'a;b' and id in (1, 2)").is_ok());
+        assert!(check_explain_sql("select * from t where name = 'it''s \\';' -- 注释").is_ok());
+        assert!(check_explain_sql("select 1 - -1, 2--1").is_ok());
+
+        // 多条语句
+        assert!(check_explain_sql("select 1; drop table t").is_err());
+        assert!(check_explain_sql("select 1 /*!; drop table t */").is_err());
+        assert!(check_explain_sql("select 1 # ;\n").is_err());
+        // 引号中的注释符号不影响判断
+        assert!(check_explain_sql("select '/*' ; select '*/'").is_err());
+        // 可能被截断
+        assert!(check_explain_sql("select * from t where name = 'abc").is_err());
+        assert!(check_explain_sql("select * from t where id in (1, 2").is_err());
+        assert!(check_explain_sql("select * from t /* abc").is_err());
+        // 只允许 SELECT
+        assert!(check_explain_sql("delete from t").is_err());
+    }
+
+    #[test]
+    fn test_explain_capturer_select() {
+        let cfg = parse_test_args(&[
+            "--host=127.0.0.1",
+            "--port=3306",
+            "--explain-min-time=10",
+            "--explain-window=60",
+            "--explain-limit=2",
+        ]);
+        let mut capturer = ExplainCapturer::new(&cfg).unwrap();
+
+        let get_info = |id: u64, db: &str, time: i32, sql: &str| ShowProcesslistInfo {
+            id: Some(id),
+            db: Some(db.to_string()),
+            time: Some(time),
+            info: Some(sql.to_string()),
+            ..Default::default()
+        };
+        let infos = vec![
+            get_info(1, "db1", 20, "select * from t where id = 1"),
+            get_info(2, "db1", 30, "select * from t where id = 2"),
+            get_info(3, "db1", 5, "select * from t2"),
+            get_info(4, "db1", 15, "insert into t values (1)"),
+            get_info(5, "db1", 12, "delete from t3 where id = 1"),
+            get_info(6, "db2", 11, "select * from t where id = 3"),
+        ];
+
+        let ids = |selected: Vec<(&ShowProcesslistInfo, String)>| {
+            selected
+                .iter()
+                .map(|(info, _)| info.id.unwrap())
+                .collect::<Vec<u64>>()
+        };
+        // 同一个 db 的相同指纹只获取一次
+        assert_eq!(ids(capturer.select(&infos, 100, true)), vec![2, 5]);
+        assert_eq!(ids(capturer.select(&infos, 110, true)), vec![6]);
+        assert!(capturer.select(&infos, 120, true).is_empty());
+        // 超过 window 后重新获取
+        assert_eq!(ids(capturer.select(&infos, 160, true)), vec![2, 5]);
+        // 不支持 EXPLAIN FOR CONNECTION 时只获取 SELECT
+        assert_eq!(ids(capturer.select(&infos, 220, false)), vec![2, 6]);
+    }
+}
@@ -0,0 +1,524 @@
+use crate::config::show_processlist_conf::ShowProcesslistConf;
+use crate::error::CustomError;
+use crate::models::ShowProcesslistInfo;
+use regex::Regex;
+
+// processlist 过滤表达式, 例如:
+// command != 'Sleep' and time > 5 and user not in ('repl', 'monitor')
+//
+// expr       := and_expr ('or' and_expr)*
+// and_expr   := not_expr ('and' not_expr)*
+// not_expr   := 'not' not_expr | '(' expr ')' | comparison
+// comparison := field op value
+//             | field ['not'] 'in' '(' value (',' value)* ')'
+//             | field ['not'] 'like' string
+//             | field ['not'] 'regexp' string
+//             | field 'is' ['not'] 'null'
+// field      := id | user | host | db | command | time | state | info
+//             | thread_id | digest | execution_engine
+// op         := = | != | <> | > | >= | < | <=
+//
+// 关键字和字段名不区分大小写. 字段值为 NULL 时按空字符串比较, 需要判断 NULL 使用 is null
+#[derive(Debug, Clone)]
+pub enum FilterExpr {
+    And(Box<FilterExpr>, Box<FilterExpr>),
+    Or(Box<FilterExpr>, Box<FilterExpr>),
+    Not(Box<FilterExpr>),
+    Compare(Field, CompareOp, Value),
+    In(Field, Vec<Value>),
+    Regex(Field, Regex), // like 转换为正则表达式
+    IsNull(Field),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Id,
+    User,
+    Host,
+    Db,
+    Command,
+    Time,
+    State,
+    Info,
+    ThreadId,
+    Digest,
+    ExecutionEngine,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Str(String),
+    Num(i64),
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Field> {
+        let field = match name.to_lowercase().as_str() {
+            "id" => Field::Id,
+            "user" => Field::User,
+            "host" => Field::Host,
+            "db" => Field::Db,
+            "command" => Field::Command,
+            "time" => Field::Time,
+            "state" => Field::State,
+            "info" => Field::Info,
+            "thread_id" => Field::ThreadId,
+            "digest" => Field::Digest,
+            "execution_engine" => Field::ExecutionEngine,
+            _ => return None,
+        };
+
+        Some(field)
+    }
+
+    fn is_null(&self, info: &ShowProcesslistInfo) -> bool {
+        match self {
+            Field::Id => info.id.is_none(),
+            Field::User => info.user.is_none(),
+            Field::Host => info.host.is_none(),
+            Field::Db => info.db.is_none(),
+            Field::Command => info.command.is_none(),
+            Field::Time => info.time.is_none(),
+            Field::State => info.state.is_none(),
+            Field::Info => info.info.is_none(),
+            Field::ThreadId => info.thread_id.is_none(),
+            Field::Digest => info.digest.is_none(),
+            Field::ExecutionEngine => info.execution_engine.is_none(),
+        }
+    }
+
+    fn get_value(&self, info: &ShowProcesslistInfo) -> Value {
+        let str_value = |v: &Option<String>| Value::Str(v.clone().unwrap_or_default());
+        match self {
+            Field::Id => Value::Num(info.id.unwrap_or(0) as i64),
+            Field::Time => Value::Num(info.time.unwrap_or(0) as i64),
+            Field::ThreadId => Value::Num(info.thread_id.unwrap_or(0) as i64),
+            Field::User => str_value(&info.user),
+            Field::Host => str_value(&info.host),
+            Field::Db => str_value(&info.db),
+            Field::Command => str_value(&info.command),
+            Field::State => str_value(&info.state),
+            Field::Info => str_value(&info.info),
+            Field::Digest => str_value(&info.digest),
+            Field::ExecutionEngine => str_value(&info.execution_engine),
+        }
+    }
+}
+
+impl Value {
+    fn to_str(&self) -> String {
+        match self {
+            Value::Str(v) => v.clone(),
+            Value::Num(v) => v.to_string(),
+        }
+    }
+
+    // 都是数字按数字比较, 否则按字符串比较
+    fn compare(&self, other: &Value) -> std::cmp::Ordering {
+        match (self, other) {
+            (Value::Num(a), Value::Num(b)) => a.cmp(b),
+            (Value::Num(a), Value::Str(b)) => match b.parse::<i64>() {
+                Ok(b) => a.cmp(&b),
+                Err(_) => a.to_string().cmp(b),
+            },
+            _ => self.to_str().cmp(&other.to_str()),
+        }
+    }
+}
+
+impl FilterExpr {
+    pub fn parse(expr: &str) -> Result<FilterExpr, CustomError> {
+        let tokens = tokenize(expr).map_err(|e| {
+            CustomError::new(format!("过滤表达式不合法: {expr}. {e}", expr = expr, e = e))
+        })?;
+
+        let mut parser = Parser { tokens, pos: 0 };
+        let result = parser.parse_or().and_then(|filter| match parser.peek() {
+            None => Ok(filter),
+            Some(token) => Err(format!("多余的内容: {token:?}")),
+        });
+
+        result.map_err(|e| {
+            CustomError::new(format!("过滤表达式不合法: {expr}. {e}", expr = expr, e = e))
+        })
+    }
+
+    pub fn is_match(&self, info: &ShowProcesslistInfo) -> bool {
+        match self {
+            FilterExpr::And(a, b) => a.is_match(info) && b.is_match(info),
+            FilterExpr::Or(a, b) => a.is_match(info) || b.is_match(info),
+            FilterExpr::Not(a) => !a.is_match(info),
+            FilterExpr::Compare(field, op, value) => {
+                let ordering = field.get_value(info).compare(value);
+                match op {
+                    CompareOp::Eq => ordering.is_eq(),
+                    CompareOp::Ne => ordering.is_ne(),
+                    CompareOp::Gt => ordering.is_gt(),
+                    CompareOp::Ge => ordering.is_ge(),
+                    CompareOp::Lt => ordering.is_lt(),
+                    CompareOp::Le => ordering.is_le(),
+                }
+            }
+            FilterExpr::In(field, values) => {
+                let field_value = field.get_value(info);
+                values
+                    .iter()
+                    .any(|value| field_value.compare(value).is_eq())
+            }
+            FilterExpr::Regex(field, regex) => regex.is_match(&field.get_value(info).to_str()),
+            FilterExpr::IsNull(field) => field.is_null(info),
+        }
+    }
+}
+
+// 根据配置生成的过滤条件
+pub struct ProcesslistFilter {
+    threshold: FilterExpr, // 哪些线程计入 --print-cnt-threshold
+    output: FilterExpr,    // 哪些线程输出到快照
+}
+
+impl ProcesslistFilter {
+    pub fn new(cfg: &ShowProcesslistConf) -> Result<ProcesslistFilter, CustomError> {
+        Ok(ProcesslistFilter {
+            threshold: FilterExpr::parse(&cfg.threshold_filter)?,
+            output: FilterExpr::parse(&cfg.output_filter)?,
+        })
+    }
+
+    // 计入阈值的线程
+    pub fn filter_threshold(&self, infos: &[ShowProcesslistInfo]) -> Vec<ShowProcesslistInfo> {
+        filter_infos(&self.threshold, infos)
+    }
+
+    // 需要输出的线程
+    pub fn filter_output(&self, infos: &[ShowProcesslistInfo]) -> Vec<ShowProcesslistInfo> {
+        filter_infos(&self.output, infos)
+    }
+}
+
+fn filter_infos(filter: &FilterExpr, infos: &[ShowProcesslistInfo]) -> Vec<ShowProcesslistInfo> {
+    infos
+        .iter()
+        .filter(|info| filter.is_match(info))
+        .cloned()
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String), // 字段名和关键字
+    Str(String),
+    Num(i64),
+    Op(CompareOp),
+    LParen,
+    RParen,
+    Comma,
+}
+
+fn tokenize(expr: &str) -> Result<Vec<Token>, String> {
+    let chars = expr.chars().collect::<Vec<char>>();
+    let mut tokens = Vec::<Token>::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            _ if c.is_whitespace() => i += 1,
+            '(' => {
+                tokens.push(Token::LParen);
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token::RParen);
+                i += 1;
+            }
+            ',' => {
+                tokens.push(Token::Comma);
+                i += 1;
+            }
+            '=' => {
+                tokens.push(Token::Op(CompareOp::Eq));
+                i += 1;
+            }
+            '!' | '<' | '>' => {
+                let next = chars.get(i + 1).copied();
+                let (op, len) = match (c, next) {
+                    ('!', Some('=')) => (CompareOp::Ne, 2),
+                    ('<', Some('>')) => (CompareOp::Ne, 2),
+                    ('<', Some('=')) => (CompareOp::Le, 2),
+                    ('>', Some('=')) => (CompareOp::Ge, 2),
+                    ('<', _) => (CompareOp::Lt, 1),
+                    ('>', _) => (CompareOp::Gt, 1),
+                    _ => return Err(format!("位置 {i} 不支持的字符: {c}")),
+                };
+                tokens.push(Token::Op(op));
+                i += len;
+            }
+            '\'' | '"' => {
+                // 字符串, 支持 \ 转义和两个引号转义
+                let quote = c;
+                let mut value = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => return Err(String::from("字符串没有结束的引号")),
+                        Some('\\') if i + 1 < chars.len() => {
+                            value.push(chars[i + 1]);
+                            i += 2;
+                        }
+                        Some(&ch) if ch == quote => {
+                            if chars.get(i + 1) == Some(&quote) {
+                                value.push(quote);
+                                i += 2;
+                            } else {
+                                i += 1;
+                                break;
+                            }
+                        }
+                        Some(&ch) => {
+                            value.push(ch);
+                            i += 1;
+                        }
+                    }
+                }
+                tokens.push(Token::Str(value));
+            }
+            _ if c.is_ascii_digit() || c == '-' => {
+                let start = i;
+                i += 1;
+                while i < chars.len() && chars[i].is_ascii_digit() {
+                    i += 1;
+                }
+                let num = chars[start..i].iter().collect::<String>();
+                let num = num
+                    .parse::<i64>()
+                    .map_err(|_| format!("位置 {start} 不合法的数字: {num}"))?;
+                tokens.push(Token::Num(num));
+            }
+            _ if c.is_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                tokens.push(Token::Ident(chars[start..i].iter().collect()));
+            }
+            _ => return Err(format!("位置 {i} 不支持的字符: {c}")),
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    // 下一个 token 是指定关键字则跳过
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if let Some(Token::Ident(v)) = self.peek() {
+            if v.eq_ignore_ascii_case(keyword) {
+                self.pos += 1;
+                return true;
+            }
+        }
+
+        false
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), String> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            token => Err(format!("期望 {expected:?}, 实际为 {token:?}")),
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<FilterExpr, String> {
+        let mut left = self.parse_and()?;
+        while self.eat_keyword("or") {
+            let right = self.parse_and()?;
+            left = FilterExpr::Or(Box::new(left), Box::new(right));
+        }
+
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<FilterExpr, String> {
+        let mut left = self.parse_not()?;
+        while self.eat_keyword("and") {
+            let right = self.parse_not()?;
+            left = FilterExpr::And(Box::new(left), Box::new(right));
+        }
+
+        Ok(left)
+    }
+
+    fn parse_not(&mut self) -> Result<FilterExpr, String> {
+        if self.eat_keyword("not") {
+            return Ok(FilterExpr::Not(Box::new(self.parse_not()?)));
+        }
+
+        if self.peek() == Some(&Token::LParen) {
+            self.pos += 1;
+            let expr = self.parse_or()?;
+            self.expect(Token::RParen)?;
+            return Ok(expr);
+        }
+
+        self.parse_comparison()
+    }
+
+    fn parse_comparison(&mut self) -> Result<FilterExpr, String> {
+        let field = match self.next() {
+            Some(Token::Ident(name)) => {
+                Field::parse(&name).ok_or_else(|| format!("不支持的字段: {name}"))?
+            }
+            token => return Err(format!("期望字段名, 实际为 {token:?}")),
+        };
+
+        if let Some(Token::Op(op)) = self.peek() {
+            let op = *op;
+            self.pos += 1;
+            return Ok(FilterExpr::Compare(field, op, self.parse_value()?));
+        }
+
+        if self.eat_keyword("is") {
+            let is_not = self.eat_keyword("not");
+            if !self.eat_keyword("null") {
+                return Err(String::from("is 后面需要是 null 或 not null"));
+            }
+            let expr = FilterExpr::IsNull(field);
+            return Ok(if is_not {
+                FilterExpr::Not(Box::new(expr))
+            } else {
+                expr
+            });
+        }
+
+        let is_not = self.eat_keyword("not");
+        let expr = if self.eat_keyword("in") {
+            self.expect(Token::LParen)?;
+            let mut values = vec![self.parse_value()?];
+            while self.peek() == Some(&Token::Comma) {
+                self.pos += 1;
+                values.push(self.parse_value()?);
+            }
+            self.expect(Token::RParen)?;
+            FilterExpr::In(field, values)
+        } else if self.eat_keyword("like") {
+            let pattern = self.parse_string()?;
+            FilterExpr::Regex(field, like_to_regex(&pattern)?)
+        } else if self.eat_keyword("regexp") {
+            let pattern = self.parse_string()?;
+            let regex =
+                Regex::new(&pattern).map_err(|e| format!("正则表达式不合法: {pattern}. {e}"))?;
+            FilterExpr::Regex(field, regex)
+        } else {
+            return Err(format!(
+                "字段 {field:?} 后面需要是比较符, in, like, regexp 或 is null"
+            ));
+        };
+
+        Ok(if is_not {
+            FilterExpr::Not(Box::new(expr))
+        } else {
+            expr
+        })
+    }
+
+    fn parse_value(&mut self) -> Result<Value, String> {
+        match self.next() {
+            Some(Token::Str(v)) => Ok(Value::Str(v)),
+            Some(Token::Num(v)) => Ok(Value::Num(v)),
+            token => Err(format!("期望字符串或数字, 实际为 {token:?}")),
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        match self.next() {
+            Some(Token::Str(v)) => Ok(v),
+            token => Err(format!("期望字符串, 实际为 {token:?}")),
+        }
+    }
+}
+
+// like 转换为正则表达式, % 匹配任意多个字符, _ 匹配一个字符, 和 MySQL 一样不区分大小写
+fn like_to_regex(pattern: &str) -> Result<Regex, String> {
+    let mut regex = String::from("(?is)^");
+    for c in pattern.chars() {
+        match c {
+            '%' => regex.push_str(".*"),
+            '_' => regex.push('.'),
+            _ => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+
+    Regex::new(&regex).map_err(|e| format!("like 表达式不合法: {pattern}. {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::show_processlist::filter::FilterExpr;
+    use crate::models::ShowProcesslistInfo;
+
+    fn get_info(user: &str, command: &str, time: i32, info: Option<&str>) -> ShowProcesslistInfo {
+        ShowProcesslistInfo {
+            id: Some(1),
+            user: Some(user.to_string()),
+            host: Some(String::from("127.0.0.1:5000")),
+            db: None,
+            command: Some(command.to_string()),
+            time: Some(time),
+            state: Some(String::from("Sending data")),
+            info: info.map(String::from),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_filter_expr() {
+        let filter = FilterExpr::parse(
+            "command != 'Sleep' and time > 5 and user not in ('repl', 'monitor')",
+        )
+        .unwrap();
+        assert!(filter.is_match(&get_info("app", "Query", 10, Some("select 1"))));
+        assert!(!filter.is_match(&get_info("app", "Sleep", 10, None)));
+        assert!(!filter.is_match(&get_info("app", "Query", 5, Some("select 1"))));
+        assert!(!filter.is_match(&get_info("monitor", "Query", 10, Some("select 1"))));
+
+        let filter = FilterExpr::parse(
+            "NOT (command LIKE 'binlog dump%' OR info IS NULL) or db is not null",
+        )
+        .unwrap();
+        assert!(filter.is_match(&get_info("app", "Query", 10, Some("select 1"))));
+        assert!(!filter.is_match(&get_info("repl", "Binlog Dump GTID", 10, Some("x"))));
+        assert!(!filter.is_match(&get_info("app", "Query", 10, None)));
+
+        assert!(FilterExpr::parse("command = ").is_err());
+        assert!(FilterExpr::parse("unknown = 1").is_err());
+        assert!(FilterExpr::parse("time > 1 1").is_err());
+        assert!(FilterExpr::parse("user = 'abc").is_err());
+    }
+}
@@ -1,16 +1,39 @@
 use crate::config::show_processlist_conf::ShowProcesslistConf;
-use crate::core::show_processlist::{all_cluster_handler, common};
-use crate::dao::{InstanceDao, MetaClusterDao, NormalDao};
+use crate::core::show_processlist::collector::{self, OutputSink};
+use crate::core::show_processlist::{
+    all_cluster_handler, health, history, merged_handler, metrics, reload, shutdown,
+};
+use crate::dao::{InstanceDao, MetaClusterDao};
 use crate::error::CustomError;
-use crate::models::{Instance, ShowProcesslistInfo};
+use crate::models::Instance;
 use crate::{rdbc, utils};
 use sqlx::{MySql, Pool};
+use std::time::Instant;
 use tokio::sync::mpsc;
 
 pub async fn run(cfg: &ShowProcesslistConf) -> Result<(), CustomError> {
+    // 合并 --config-file 配置文件, 检测配置文件相关参数, 提前检测过滤表达式, 避免每个实例启动时才报错
+    let loaded = reload::init(cfg)?;
+    let cfg = &loaded.cfg;
     log::info!("配置文件: {}", utils::string::to_json_str_pretty(cfg));
-    // 检测配置文件相关参数
-    cfg.check()?;
+    let start_time = Instant::now();
+
+    // 收到 SIGINT/SIGTERM 后所有实例完成当前这一次 processlist 后退出
+    shutdown::spawn_signal_handler();
+
+    // 收到 SIGHUP 或者配置文件修改后重新加载配置
+    reload::spawn_reload_handler();
+
+    // 开启 prometheus 指标服务
+    if !cfg.metrics_addr.is_empty() {
+        metrics::start_server(&cfg.metrics_addr).await?;
+    }
+
+    // 开启历史库时创建历史快照表
+    history::init(cfg).await?;
+
+    // 定时输出实例健康状态汇总
+    health::spawn_summary_log(cfg.health_log_interval);
 
     // 指定 host port
     if cfg.have_host_port() {
@@ -24,6 +47,9 @@ pub async fn run(cfg: &ShowProcesslistConf) -> Result<(), CustomError> {
         all_cluster_handler::run(cfg).await?;
     }
 
+    // 等待后台任务, 关闭历史库, 刷新输出并打印汇总信息
+    shutdown::finish(start_time).await;
+
     Ok(())
 }
 
@@ -35,10 +61,16 @@ async fn start_vip_port(cfg: &ShowProcesslistConf) -> Result<(), CustomError> {
         utils::string::to_json_str_pretty(&instances)
     );
 
+    // 所有实例同步执行, 合并输出
+    if cfg.merged_view {
+        return merged_handler::run(instances).await;
+    }
+
     let (tx, mut rx) = mpsc::channel::<String>(instances.len());
     for instance in instances {
         let tmp_tx = tx.clone();
-        let tmp_cfg = cfg.clone();
+        // 使用实例对应的阈值覆盖配置
+        let tmp_cfg = reload::current().get_instance_cfg(&instance);
         tokio::spawn(async move {
             // 开始执行 processlist
             if let Err(e) = start_processlist_by_instance(&tmp_cfg, &instance).await {
@@ -111,7 +143,7 @@ async fn find_instances_op(
     );
 
     // 通过集群 id 获取所有实例
-    InstanceDao::find_by_meta_cluster_id(easydb, cluster.id.unwrap())
+    let mut instances = InstanceDao::find_by_meta_cluster_id(easydb, cluster.id.unwrap())
         .await
         .map_err(|e| {
             CustomError::new(format!(
@@ -120,7 +152,15 @@ async fn find_instances_op(
                 vip_port=&cfg.vip_port,
                 e=e.to_string()
             ))
-        })
+        })?;
+
+    // 填充集群名称和业务线
+    instances.iter_mut().for_each(|instance| {
+        instance.cluster_name = cluster.name.clone();
+        instance.business_line = cluster.business_line.clone();
+    });
+
+    Ok(instances)
 }
 
 // 开始执行实例级别processlist
@@ -128,147 +168,25 @@ async fn start_processlist_by_instance(
     cfg: &ShowProcesslistConf,
     instance: &Instance,
 ) -> Result<(), CustomError> {
-    let password = cfg.get_password();
-    // 创建数据库链接
-    let db = rdbc::get_db_by_default(
-        instance.machine_host.as_ref().unwrap(),
-        instance.port.unwrap() as i16,
-        &cfg.username,
-        &password,
-        "",
-        cfg.is_sql_log,
-    )
-    .await
-    .map_err(|e| {
-        CustomError::new(format!(
-            "创建需要执行 processlist 数据库链接失败. host:port:{host}:{port}. {e}",
-            host = instance.machine_host.as_ref().unwrap(),
-            port = instance.port.unwrap(),
-            e = e.to_string()
-        ))
-    })?;
-
-    // 循环执行 processlist
-    loop {
-        let infos = match NormalDao::show_processlist(&db).await {
-            Ok(v) => v,
-            Err(e) => {
-                log::error!(
-                    "{host}:{port}, 获取processlist信息失败. {e}",
-                    host = instance.machine_host.as_ref().unwrap(),
-                    port = instance.port.unwrap(),
-                    e = e.to_string()
-                );
-                // 休眠多少毫秒
-                let _ = tokio::time::sleep(std::time::Duration::from_millis(cfg.sleep)).await;
-                continue;
-            }
-        };
-
-        // 过滤 processlist 信息, 过滤掉 command=Sleep
-        let filter_infos_sleep = infos
-            .iter()
-            .map(|info| info.clone())
-            .filter(|info| info.command.as_ref().unwrap() != "Sleep")
-            .collect::<Vec<ShowProcesslistInfo>>();
-
-        // 在 过滤掉 command=Sleep 基础上过滤掉 user=system user
-        let fitler_infos_system_user = filter_infos_sleep
-            .iter()
-            .map(|info| info.clone())
-            .filter(|info| info.user.as_ref().unwrap() != "system user")
-            .collect::<Vec<ShowProcesslistInfo>>();
-
-        // 除了 Sleep 和 system user 外的processlist 超过了指定数需要进行记录
-        if fitler_infos_system_user.len() >= cfg.print_cnt_threshold as usize {
-            let infos_table = common::get_infos_table(&filter_infos_sleep);
-
-            // 记录日志
-            log::info!(
-                "\n---- {host}:{port} Time: {time}, Total: {total}, Filter Sleep: {filter_sleep} ----\n{infos_table}",
-                host = instance.machine_host.as_ref().unwrap(),
-                port = instance.port.unwrap(),
-                time = &utils::time::now_str(utils::time::NORMAL_FMT),
-                total=infos.len(),
-                filter_sleep = filter_infos_sleep.len(),
-                infos_table = infos_table,
-            );
-        }
-
-        // 休眠多少毫秒
-        let _ = tokio::time::sleep(std::time::Duration::from_millis(cfg.sleep)).await;
-    }
+    collector::run_instance(cfg, instance, OutputSink::Print, || true).await
 }
 
 async fn start_host_port(cfg: &ShowProcesslistConf) -> Result<(), CustomError> {
-    let password = cfg.get_password();
-    // 创建数据库链接
-    let db = rdbc::get_db_by_default(
-        &cfg.host,
-        cfg.port as i16,
-        &cfg.username,
-        &password,
-        "",
-        cfg.is_sql_log,
-    )
-    .await
-    .map_err(|e| {
-        CustomError::new(format!(
-            "创建需要执行 processlist 数据库链接失败. host:port:{host}:{port}. {e}",
-            host = &cfg.host,
-            port = cfg.port,
-            e = e.to_string()
-        ))
-    })?;
-
-    // 循环执行 processlist
-    loop {
-        let infos = match NormalDao::show_processlist(&db).await {
-            Ok(v) => v,
-            Err(e) => {
-                log::error!(
-                    "{host}:{port}, 获取processlist信息失败. {e}",
-                    host = &cfg.host,
-                    port = cfg.port,
-                    e = e.to_string()
-                );
-                // 休眠多少毫秒
-                let _ = tokio::time::sleep(std::time::Duration::from_millis(cfg.sleep)).await;
-                continue;
-            }
-        };
-
-        // 过滤 processlist 信息, 过滤掉 command=Sleep
-        let filter_infos_sleep = infos
-            .iter()
-            .map(|info| info.clone())
-            .filter(|info| info.command.as_ref().unwrap() != "Sleep")
-            .collect::<Vec<ShowProcesslistInfo>>();
-
-        // 在 过滤掉 command=Sleep 基础上过滤掉 user=system user
-        let fitler_infos_system_user = filter_infos_sleep
-            .iter()
-            .map(|info| info.clone())
-            .filter(|info| info.user.as_ref().unwrap() != "system user")
-            .collect::<Vec<ShowProcesslistInfo>>();
-
-        // 除了 Sleep 和 system user 外的processlist 超过了指定数需要进行记录
-        if fitler_infos_system_user.len() >= cfg.print_cnt_threshold as usize {
-            let infos_table = common::get_infos_table(&filter_infos_sleep);
+    // 通过 --host --port 生成实例, 和 vip port 使用相同的逻辑
+    let instance = Instance {
+        machine_host: Some(cfg.host.clone()),
+        port: Some(cfg.port as i32),
+        ..Default::default()
+    };
 
-            // 记录日志
-            log::info!(
-                "\n---- {host}:{port} Time: {time}, Total: {total}, Filter Sleep: {filter_sleep} ----\n{infos_table}",
-                host = &cfg.host,
-                port = cfg.port,
-                time = &utils::time::now_str(utils::time::NORMAL_FMT),
-                total=infos.len(),
-                filter_sleep = filter_infos_sleep.len(),
-                infos_table = infos_table,
-            );
-        }
+    start_processlist_by_instance(cfg, &instance).await
+}
 
-        // 休眠多少毫秒
-        let _ = tokio::time::sleep(std::time::Duration::from_millis(cfg.sleep)).await;
+// 输出快照, 事件等信息, text 格式记录日志, jsonl 格式直接输出到标准输出方便管道给 jq 等工具使用
+pub fn print_data(cfg: &ShowProcesslistConf, data: &str) {
+    if cfg.is_jsonl() {
+        print!("{}", data);
+    } else {
+        log::info!("{}", data);
     }
 }
@@ -0,0 +1,243 @@
+use crate::config::show_processlist_conf::ShowProcesslistConf;
+use crate::error::CustomError;
+use rand::Rng;
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
+use std::time::Duration;
+
+// 所有实例的健康状态, host:port -> 状态, 用于定时输出汇总日志
+static HEALTH: OnceLock<RwLock<HashMap<String, HealthState>>> = OnceLock::new();
+
+// 实例健康状态:
+// connecting: 正在创建链接
+// healthy: 最近一次 processlist 成功
+// degraded: processlist 连续失败, 但没有达到 --down-threshold, 使用原来的链接重试
+// down: 创建链接失败或者 processlist 连续失败达到 --down-threshold, 关闭链接后重新链接
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HealthState {
+    Connecting,
+    Healthy,
+    Degraded,
+    Down,
+}
+
+impl HealthState {
+    pub fn name(&self) -> &'static str {
+        match self {
+            HealthState::Connecting => "connecting",
+            HealthState::Healthy => "healthy",
+            HealthState::Degraded => "degraded",
+            HealthState::Down => "down",
+        }
+    }
+}
+
+// 指数退避, 第 n 次失败等待 min(max, initial * 2^n), 再随机取其中 50% ~ 100%
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    initial: u64, // 单位毫秒
+    max: u64,     // 单位毫秒
+    attempt: u32,
+}
+
+impl Backoff {
+    pub fn new(initial: u64, max: u64) -> Backoff {
+        Backoff {
+            initial,
+            max: max.max(initial),
+            attempt: 0,
+        }
+    }
+
+    // 不带随机值的等待时间
+    fn get_base_delay(&self) -> u64 {
+        self.initial
+            .saturating_mul(1u64.checked_shl(self.attempt).unwrap_or(u64::MAX))
+            .min(self.max)
+    }
+
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = self.get_base_delay();
+        self.attempt = self.attempt.saturating_add(1);
+
+        Duration::from_millis(rand::thread_rng().gen_range(delay / 2..=delay))
+    }
+
+    pub fn reset(&mut self) {
+        self.attempt = 0;
+    }
+}
+
+// 每个实例的健康状态, 释放时从全局状态中移除
+pub struct InstanceHealth {
+    host_port: String,
+    state: HealthState,
+    consecutive_errors: u64,
+    down_threshold: u64,
+    backoff: Backoff,
+}
+
+impl InstanceHealth {
+    pub fn new(cfg: &ShowProcesslistConf, host_port: &str) -> InstanceHealth {
+        let health = InstanceHealth {
+            host_port: host_port.to_string(),
+            state: HealthState::Connecting,
+            consecutive_errors: 0,
+            down_threshold: cfg.down_threshold.max(1),
+            backoff: Backoff::new(cfg.reconnect_backoff_initial, cfg.reconnect_backoff_max),
+        };
+        health.register();
+
+        health
+    }
+
+    fn register(&self) {
+        HEALTH
+            .get_or_init(|| RwLock::new(HashMap::new()))
+            .write()
+            .unwrap()
+            .insert(self.host_port.clone(), self.state);
+    }
+
+    // 状态变化时记录日志并更新全局状态
+    fn set_state(&mut self, state: HealthState) {
+        if self.state == state {
+            return;
+        }
+
+        log::info!(
+            "{host_port}, 健康状态: {from} -> {to}",
+            host_port = &self.host_port,
+            from = self.state.name(),
+            to = state.name()
+        );
+        self.state = state;
+        self.register();
+    }
+
+    // down 的实例重新链接时保持 down, 链接成功并且 processlist 成功后才恢复
+    pub fn on_connecting(&mut self) {
+        if self.state != HealthState::Down {
+            self.set_state(HealthState::Connecting);
+        }
+    }
+
+    // 创建链接失败, 返回重新链接前需要等待的时间
+    pub fn on_connect_error(&mut self, e: &CustomError) -> Duration {
+        self.consecutive_errors += 1;
+        self.set_state(HealthState::Down);
+        let delay = self.backoff.next_delay();
+        log::error!(
+            "{e}. 连续失败次数: {errors}, {delay}ms 后重新链接",
+            e = e,
+            errors = self.consecutive_errors,
+            delay = delay.as_millis()
+        );
+
+        delay
+    }
+
+    pub fn on_success(&mut self) {
+        self.consecutive_errors = 0;
+        self.backoff.reset();
+        self.set_state(HealthState::Healthy);
+    }
+
+    // processlist 失败, 返回使用原来的链接重试前需要等待的时间. 返回 None 需要关闭链接重新链接
+    pub fn on_error(&mut self, e: &CustomError) -> Option<Duration> {
+        self.consecutive_errors += 1;
+        let delay = self.backoff.next_delay();
+        let is_down = self.consecutive_errors >= self.down_threshold;
+        self.set_state(if is_down {
+            HealthState::Down
+        } else {
+            HealthState::Degraded
+        });
+        log::error!(
+            "{host_port}, 执行 show processlist 出错. 连续失败次数: {errors}, {action}. {e}",
+            host_port = &self.host_port,
+            errors = self.consecutive_errors,
+            action = if is_down {
+                String::from("重新链接")
+            } else {
+                format!("{delay}ms 后重试", delay = delay.as_millis())
+            },
+            e = e
+        );
+
+        if is_down {
+            None
+        } else {
+            Some(delay)
+        }
+    }
+}
+
+impl Drop for InstanceHealth {
+    fn drop(&mut self) {
+        if let Some(health) = HEALTH.get() {
+            health.write().unwrap().remove(&self.host_port);
+        }
+    }
+}
+
+// 定时输出各个健康状态的实例数, interval 为 0 不输出
+pub fn spawn_summary_log(interval: u64) {
+    if interval == 0 {
+        return;
+    }
+
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(Duration::from_secs(interval)).await;
+            log::info!("实例健康状态汇总: {summary}", summary = get_summary());
+        }
+    });
+}
+
+fn get_summary() -> String {
+    let mut counts = HashMap::<HealthState, usize>::new();
+    if let Some(health) = HEALTH.get() {
+        for state in health.read().unwrap().values() {
+            *counts.entry(*state).or_default() += 1;
+        }
+    }
+
+    [
+        HealthState::Connecting,
+        HealthState::Healthy,
+        HealthState::Degraded,
+        HealthState::Down,
+    ]
+    .iter()
+    .map(|state| {
+        format!(
+            "{name}: {cnt}",
+            name = state.name(),
+            cnt = counts.get(state).copied().unwrap_or(0)
+        )
+    })
+    .collect::<Vec<String>>()
+    .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::show_processlist::health::Backoff;
+
+    #[test]
+    fn test_backoff() {
+        let mut backoff = Backoff::new(1000, 8000);
+        let delays = (0..6)
+            .map(|_| backoff.next_delay().as_millis() as u64)
+            .collect::<Vec<u64>>();
+        println!("{:?}", delays);
+
+        for (delay, base) in delays.iter().zip([1000, 2000, 4000, 8000, 8000, 8000]) {
+            assert!(*delay >= base / 2 && *delay <= base);
+        }
+
+        backoff.reset();
+        assert!(backoff.next_delay().as_millis() <= 1000);
+    }
+}
@@ -0,0 +1,141 @@
+use crate::config::show_processlist_conf::ShowProcesslistConf;
+use crate::core::processlist_history::store;
+use crate::dao::{HistoryDb, ProcesslistHistoryDao};
+use crate::error::CustomError;
+use crate::models::{Instance, ProcesslistHistory, ShowProcesslistInfo};
+use crate::utils;
+use chrono::Timelike;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+// 等待写入的快照数上限, 历史库写入跟不上时丢弃新的快照
+const HISTORY_QUEUE_SIZE: usize = 100;
+
+// 历史库, 没有指定 --history-store 时为空, 保存快照直接返回
+static HISTORY: OnceLock<History> = OnceLock::new();
+
+// 所有实例的快照通过队列交给同一个写入任务, 按顺序写入历史库
+struct History {
+    sender: Mutex<Option<mpsc::Sender<Vec<ProcesslistHistory>>>>, // 退出时释放, 写入任务写完剩余快照后结束
+    writer: Mutex<Option<JoinHandle<()>>>,
+}
+
+// 指定了 --history-store 时创建历史库链接和表
+pub async fn init(cfg: &ShowProcesslistConf) -> Result<(), CustomError> {
+    if cfg.history_store.is_empty() {
+        return Ok(());
+    }
+
+    let db = store::connect_and_create_table(
+        &cfg.history_store,
+        &cfg.history_sqlite_file,
+        &cfg.get_history_mysql_dsn(),
+        &cfg.history_table,
+        cfg.is_sql_log,
+    )
+    .await?;
+    log::info!(
+        "快照同时保存到历史库: {history_store}, 表: {table}",
+        history_store = &cfg.history_store,
+        table = &cfg.history_table
+    );
+    let table = cfg.history_table.clone();
+    HISTORY.get_or_init(|| {
+        let (sender, receiver) = mpsc::channel(HISTORY_QUEUE_SIZE);
+        History {
+            sender: Mutex::new(Some(sender)),
+            writer: Mutex::new(Some(tokio::spawn(write(db, table, receiver)))),
+        }
+    });
+
+    Ok(())
+}
+
+// 写入任务, 队列关闭并且写完剩余快照后关闭历史库链接
+async fn write(
+    db: HistoryDb,
+    table: String,
+    mut receiver: mpsc::Receiver<Vec<ProcesslistHistory>>,
+) {
+    while let Some(records) = receiver.recv().await {
+        if let Err(e) = ProcesslistHistoryDao::insert(&db, &table, &records).await {
+            log::error!(
+                "{host_port}, 保存历史快照失败. {e}",
+                host_port = records[0].host_port.as_deref().unwrap_or_default(),
+                e = e
+            );
+        }
+    }
+
+    db.close().await;
+}
+
+// 快照放入写入队列, 队列已满或者已经关闭时只记录日志
+pub fn save(instance: &Instance, infos: &[ShowProcesslistInfo]) {
+    let sender = match HISTORY.get() {
+        Some(history) => match history.sender.lock().unwrap().as_ref() {
+            Some(v) => v.clone(),
+            None => return,
+        },
+        None => return,
+    };
+    if infos.is_empty() {
+        return;
+    }
+
+    let records = get_records(instance, infos);
+    if let Err(e) = sender.try_send(records) {
+        log::warn!(
+            "{host}:{port}, 历史快照写入队列失败, 丢弃本次快照. {e}",
+            host = instance.machine_host.as_ref().unwrap(),
+            port = instance.port.unwrap(),
+            e = e
+        );
+    }
+}
+
+// 退出时关闭写入队列, 最多等待 timeout 让写入任务写完剩余快照并关闭历史库链接
+pub async fn close(timeout: Duration) {
+    let history = match HISTORY.get() {
+        Some(v) => v,
+        None => return,
+    };
+
+    history.sender.lock().unwrap().take();
+    let writer = history.writer.lock().unwrap().take();
+    if let Some(writer) = writer {
+        if tokio::time::timeout(timeout, writer).await.is_err() {
+            log::warn!("等待保存历史快照超时, 没有写入的快照将丢失");
+        }
+    }
+}
+
+fn get_records(instance: &Instance, infos: &[ShowProcesslistInfo]) -> Vec<ProcesslistHistory> {
+    // 只保留到秒, 和查询时指定的时间格式一致
+    let snapshot_time = utils::time::now_datetime().with_nanosecond(0);
+    let host_port = format!(
+        "{host}:{port}",
+        host = instance.machine_host.as_ref().unwrap(),
+        port = instance.port.unwrap()
+    );
+
+    infos
+        .iter()
+        .map(|info| ProcesslistHistory {
+            snapshot_time,
+            host_port: Some(host_port.clone()),
+            cluster_name: instance.cluster_name.clone(),
+            processlist_id: info.id.map(|v| v as i64),
+            user: info.user.clone(),
+            host: info.host.clone(),
+            db: info.db.clone(),
+            command: info.command.clone(),
+            time: info.time.map(|v| v as i64),
+            state: info.state.clone(),
+            info: info.info.clone(),
+            digest: info.digest.clone(),
+        })
+        .collect()
+}
@@ -0,0 +1,254 @@
+use crate::config::show_processlist_conf::{ShowProcesslistConf, KILL_TYPE_QUERY};
+use crate::dao::NormalDao;
+use crate::error::CustomError;
+use crate::models::{KillAuditRecord, ShowProcesslistInfo};
+use crate::utils;
+use regex::Regex;
+use sqlx::{MySql, Pool};
+use std::collections::{HashMap, HashSet};
+
+// 复制相关线程和系统用户永远不 kill
+const PROTECTED_COMMANDS: [&str; 2] = ["Binlog Dump", "Binlog Dump GTID"];
+const PROTECTED_USERS: [&str; 3] = ["system user", "event_scheduler", "repl"];
+const COMMAND_SLEEP: &str = "Sleep";
+
+// kill 规则, 所有指定的条件都满足才会 kill
+pub struct KillRule {
+    is_query: bool,
+    dry_run: bool,
+    min_time: u64,
+    commands: HashSet<String>,
+    users: HashSet<String>,
+    dbs: HashSet<String>,
+    info_regex: Option<Regex>,
+    protected_users: HashSet<String>,
+    audit_file: String,
+}
+
+impl KillRule {
+    // 没有开启 --kill 返回 None
+    pub fn new(cfg: &ShowProcesslistConf) -> Result<Option<KillRule>, CustomError> {
+        if !cfg.kill {
+            return Ok(None);
+        }
+        cfg.check_kill()?;
+
+        let info_regex = if cfg.kill_info_regex.is_empty() {
+            None
+        } else {
+            Some(Regex::new(&cfg.kill_info_regex).map_err(|e| {
+                CustomError::new(format!(
+                    "--kill-info-regex 正则表达式不合法: {regex}. {e}",
+                    regex = &cfg.kill_info_regex,
+                    e = e
+                ))
+            })?)
+        };
+
+        Ok(Some(KillRule {
+            is_query: cfg.kill_type == KILL_TYPE_QUERY,
+            dry_run: !cfg.kill_execute,
+            min_time: cfg.kill_min_time,
+            commands: cfg.kill_commands.iter().cloned().collect(),
+            users: cfg.kill_users.iter().cloned().collect(),
+            dbs: cfg.kill_dbs.iter().cloned().collect(),
+            info_regex,
+            protected_users: PROTECTED_USERS
+                .iter()
+                .map(|v| v.to_string())
+                .chain(cfg.kill_protected_users.iter().cloned())
+                .collect(),
+            audit_file: cfg.kill_audit_file.clone(),
+        }))
+    }
+
+    // 判断该线程是否需要 kill
+    pub fn is_match(&self, info: &ShowProcesslistInfo) -> bool {
+        let user = info.user.as_deref().unwrap_or("");
+        let command = info.command.as_deref().unwrap_or("");
+
+        // 受保护的线程
+        if self.protected_users.contains(user) || PROTECTED_COMMANDS.contains(&command) {
+            return false;
+        }
+
+        if self.commands.is_empty() {
+            if command == COMMAND_SLEEP {
+                return false;
+            }
+        } else if !self.commands.contains(command) {
+            return false;
+        }
+
+        if (info.time.unwrap_or(0).max(0) as u64) < self.min_time {
+            return false;
+        }
+
+        if !self.users.is_empty() && !self.users.contains(user) {
+            return false;
+        }
+
+        if !self.dbs.is_empty() && !self.dbs.contains(info.db.as_deref().unwrap_or("")) {
+            return false;
+        }
+
+        if let Some(info_regex) = &self.info_regex {
+            if !info_regex.is_match(info.info.as_deref().unwrap_or("")) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    fn get_statement(&self, id: u64) -> String {
+        if self.is_query {
+            format!("KILL QUERY {id}", id = id)
+        } else {
+            format!("KILL {id}", id = id)
+        }
+    }
+}
+
+// 对匹配规则的线程执行 kill, 并记录审计文件, 返回 kill 的线程数.
+// 跳过采集使用的链接本身, 获取不到链接 id 时本次不 kill.
+// dry-run 时 audited 保存已经记录过的线程 id 和 sql, 同一个线程只有 sql 变化时才再次记录
+pub async fn kill_by_rule(
+    rule: &KillRule,
+    audited: &mut HashMap<u64, String>,
+    db: &Pool<MySql>,
+    host: &str,
+    port: i32,
+    infos: &[ShowProcesslistInfo],
+) -> usize {
+    let instance = format!("{host}:{port}", host = host, port = port);
+    let mut kill_cnt = 0;
+
+    let connection_id = match NormalDao::get_connection_id(db).await {
+        Ok(v) => v,
+        Err(e) => {
+            log::error!(
+                "{instance}, 获取当前链接 id 失败, 跳过本次 kill. {e}",
+                instance = &instance,
+                e = e
+            );
+            return kill_cnt;
+        }
+    };
+
+    let mut matched_ids = HashSet::new();
+    for info in infos.iter().filter(|info| rule.is_match(info)) {
+        let id = match info.id {
+            Some(v) if v != connection_id => v,
+            _ => continue,
+        };
+        if rule.dry_run {
+            matched_ids.insert(id);
+            let sql = info.info.clone().unwrap_or_default();
+            if audited.get(&id) == Some(&sql) {
+                continue;
+            }
+            audited.insert(id, sql);
+        }
+        let statement = rule.get_statement(id);
+
+        let result = if rule.dry_run {
+            String::from("dry-run")
+        } else {
+            match NormalDao::kill(db, id, rule.is_query).await {
+                Ok(_) => {
+                    kill_cnt += 1;
+                    String::from("success")
+                }
+                Err(e) => format!("failed: {e}", e = e),
+            }
+        };
+
+        log::warn!(
+            "{instance}, {statement}, 结果: {result}, processlist: {info}",
+            instance = &instance,
+            statement = &statement,
+            result = &result,
+            info = utils::string::to_json_str(info)
+        );
+
+        // 记录审计文件
+        let record = KillAuditRecord {
+            time: utils::time::now_str(utils::time::NORMAL_FMT),
+            instance: instance.clone(),
+            dry_run: rule.dry_run,
+            statement,
+            result,
+            processlist: info.clone(),
+        };
+        let data = format!("{}\n", utils::string::to_json_str(&record));
+        if let Err(e) = utils::file::append_file(&rule.audit_file, &data) {
+            log::error!(
+                "写入 kill 审计文件失败. 文件: {file}, 内容: {data}. {e}",
+                file = &rule.audit_file,
+                data = &data,
+                e = e
+            );
+        }
+    }
+
+    // 线程已经结束或者不再匹配规则, 之后再次匹配时重新记录
+    audited.retain(|id, _| {
+        let is_matched = matched_ids.contains(id);
+        if !is_matched {
+            log::info!(
+                "{instance}, dry-run 记录过的线程 {id} 已结束或不再匹配 kill 规则",
+                instance = &instance,
+                id = id
+            );
+        }
+        is_matched
+    });
+
+    kill_cnt
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::show_processlist_conf::parse_test_args;
+    use crate::core::show_processlist::killer::KillRule;
+    use crate::models::ShowProcesslistInfo;
+
+    fn get_rule(args: &[&str]) -> KillRule {
+        let mut all_args = vec!["--kill"];
+        all_args.extend_from_slice(args);
+        KillRule::new(&parse_test_args(&all_args)).unwrap().unwrap()
+    }
+
+    fn get_info(user: &str, command: &str, time: i32, info: &str) -> ShowProcesslistInfo {
+        ShowProcesslistInfo {
+            id: Some(1),
+            user: Some(user.to_string()),
+            host: Some(String::from("127.0.0.1:5000")),
+            db: Some(String::from("db1")),
+            command: Some(command.to_string()),
+            time: Some(time),
+            state: Some(String::from("Sending data")),
+            info: Some(info.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_kill_rule_is_match() {
+        let rule = get_rule(&["--kill-min-time=10", "--kill-info-regex=(?i)^select"]);
+
+        assert!(rule.is_match(&get_info("app", "Query", 20, "SELECT * FROM t1")));
+        assert!(!rule.is_match(&get_info("app", "Query", 5, "SELECT * FROM t1")));
+        assert!(!rule.is_match(&get_info("app", "Query", 20, "UPDATE t1 SET a = 1")));
+        assert!(!rule.is_match(&get_info("app", "Sleep", 20, "SELECT * FROM t1")));
+        assert!(!rule.is_match(&get_info("system user", "Query", 20, "SELECT 1")));
+        assert!(!rule.is_match(&get_info("app", "Binlog Dump GTID", 20, "SELECT 1")));
+
+        // 指定的受保护用户是追加到默认用户上的
+        let rule = get_rule(&["--kill-min-time=10", "--kill-protected-users=admin"]);
+        assert!(!rule.is_match(&get_info("admin", "Query", 20, "SELECT 1")));
+        assert!(!rule.is_match(&get_info("repl", "Query", 20, "SELECT 1")));
+        assert!(rule.is_match(&get_info("app", "Query", 20, "SELECT 1")));
+    }
+}
@@ -0,0 +1,168 @@
+use crate::models::query_event::{QUERY_EVENT_FINISHED, QUERY_EVENT_STARTED};
+use crate::models::{QueryEvent, ShowProcesslistInfo};
+use crate::utils;
+use chrono::{Duration, NaiveDateTime};
+use std::collections::{HashMap, HashSet};
+
+struct TrackedQuery {
+    info: ShowProcesslistInfo,
+    first_seen: NaiveDateTime,
+    last_seen: NaiveDateTime,
+    last_time: i64,    // 最后一次观察到的 TIME
+    is_reported: bool, // 是否已经输出过开始事件
+}
+
+// 在多次 processlist 之间跟踪每个线程正在执行的 sql, key 为 线程ID + INFO
+pub struct QueryTracker {
+    min_time: i64, // 执行时间达到多少秒的 sql 才输出事件
+    queries: HashMap<(u64, String), TrackedQuery>,
+}
+
+impl QueryTracker {
+    pub fn new(min_time: u64) -> QueryTracker {
+        QueryTracker {
+            min_time: min_time as i64,
+            queries: HashMap::new(),
+        }
+    }
+
+    // 使用最新的 processlist 更新状态, 返回这次产生的事件
+    pub fn update(&mut self, infos: &[ShowProcesslistInfo], now: NaiveDateTime) -> Vec<QueryEvent> {
+        let mut events = Vec::<QueryEvent>::new();
+        let mut current_keys = Vec::<(u64, String)>::with_capacity(infos.len());
+
+        for info in infos.iter() {
+            let (id, sql) = match (info.id, info.info.as_ref()) {
+                (Some(id), Some(sql)) if !sql.is_empty() => (id, sql.clone()),
+                _ => continue,
+            };
+            let time = info.time.unwrap_or(0) as i64;
+            let key = (id, sql);
+
+            // TIME 变小说明同一个线程重新执行了相同的 sql, 上一次执行已经结束
+            let is_restarted = self
+                .queries
+                .get(&key)
+                .is_some_and(|query| time < query.last_time);
+            if is_restarted {
+                if let Some(query) = self.queries.remove(&key) {
+                    if query.is_reported {
+                        events.push(get_event(QUERY_EVENT_FINISHED, &query));
+                    }
+                }
+            }
+
+            let query = self
+                .queries
+                .entry(key.clone())
+                .or_insert_with(|| TrackedQuery {
+                    info: info.clone(),
+                    first_seen: now,
+                    last_seen: now,
+                    last_time: time,
+                    is_reported: false,
+                });
+            query.info = info.clone();
+            query.last_seen = now;
+            query.last_time = time;
+
+            if !query.is_reported && time >= self.min_time {
+                query.is_reported = true;
+                events.push(get_event(QUERY_EVENT_STARTED, query));
+            }
+
+            current_keys.push(key);
+        }
+
+        // 这次没有观察到的 sql 已经执行结束
+        let current_keys = current_keys.into_iter().collect::<HashSet<(u64, String)>>();
+        let finished_keys = self
+            .queries
+            .keys()
+            .filter(|key| !current_keys.contains(*key))
+            .cloned()
+            .collect::<Vec<(u64, String)>>();
+        for key in finished_keys.iter() {
+            if let Some(query) = self.queries.remove(key) {
+                if query.is_reported {
+                    events.push(get_event(QUERY_EVENT_FINISHED, &query));
+                }
+            }
+        }
+
+        events
+    }
+}
+
+fn get_event(event: &str, query: &TrackedQuery) -> QueryEvent {
+    let start_time = query.last_seen - Duration::seconds(query.last_time);
+
+    QueryEvent {
+        event: event.to_string(),
+        id: query.info.id.unwrap_or(0),
+        user: query.info.user.clone(),
+        host: query.info.host.clone(),
+        db: query.info.db.clone(),
+        command: query.info.command.clone(),
+        info: query.info.info.clone().unwrap_or_default(),
+        start_time: start_time.format(utils::time::NORMAL_FMT).to_string(),
+        first_seen: query.first_seen.format(utils::time::NORMAL_FMT).to_string(),
+        last_seen: query.last_seen.format(utils::time::NORMAL_FMT).to_string(),
+        observed_duration: query.last_time,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::show_processlist::lifecycle::QueryTracker;
+    use crate::models::query_event::{QUERY_EVENT_FINISHED, QUERY_EVENT_STARTED};
+    use crate::models::ShowProcesslistInfo;
+    use crate::utils::time::now_datetime;
+    use chrono::Duration;
+
+    fn get_info(id: u64, time: i32, sql: &str) -> ShowProcesslistInfo {
+        ShowProcesslistInfo {
+            id: Some(id),
+            user: Some(String::from("app")),
+            host: Some(String::from("127.0.0.1:5000")),
+            db: Some(String::from("db1")),
+            command: Some(String::from("Query")),
+            time: Some(time),
+            state: Some(String::from("Sending data")),
+            info: Some(sql.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_query_tracker_update() {
+        let now = now_datetime();
+        let mut tracker = QueryTracker::new(2);
+
+        // 执行时间没有达到 2s 不输出事件
+        let events = tracker.update(&[get_info(1, 1, "select sleep(10)")], now);
+        assert!(events.is_empty());
+
+        let events = tracker.update(
+            &[get_info(1, 3, "select sleep(10)")],
+            now + Duration::seconds(2),
+        );
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event, QUERY_EVENT_STARTED);
+
+        // 同一个线程重新执行了相同的 sql
+        let events = tracker.update(
+            &[get_info(1, 2, "select sleep(10)")],
+            now + Duration::seconds(10),
+        );
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].event, QUERY_EVENT_FINISHED);
+        assert_eq!(events[0].observed_duration, 3);
+        assert_eq!(events[1].event, QUERY_EVENT_STARTED);
+
+        let events = tracker.update(&[], now + Duration::seconds(11));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event, QUERY_EVENT_FINISHED);
+        assert_eq!(events[0].observed_duration, 2);
+    }
+}
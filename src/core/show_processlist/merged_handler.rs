@@ -0,0 +1,474 @@
+use crate::config::show_processlist_conf::ShowProcesslistConf;
+use crate::core::show_processlist::adaptive_sleep::AdaptiveSleep;
+use crate::core::show_processlist::collector::{self, OutputSink, PollResult};
+use crate::core::show_processlist::collector_state::CollectorState;
+use crate::core::show_processlist::handler::print_data;
+use crate::core::show_processlist::health::InstanceHealth;
+use crate::core::show_processlist::source::ProcesslistSource;
+use crate::core::show_processlist::{
+    common, connection_budget, history, metrics, reload, saturation, shutdown,
+};
+use crate::error::CustomError;
+use crate::models::snapshot_record::{
+    RECORD_TYPE_CLUSTER_SUMMARY, RECORD_TYPE_CONNECTION_SATURATION, RECORD_TYPE_INSTANCE_SUMMARY,
+    RECORD_TYPE_PROCESSLIST,
+};
+use crate::models::{ClusterSummary, Instance, InstanceSummary};
+use crate::utils;
+use prettytable::{format, Cell, Row, Table};
+use sqlx::{MySql, Pool};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+
+const ROLE_MASTER: &str = "master";
+// 一个实例创建链接或者一次 processlist 的超时时间, 避免一个实例卡住导致整个集群无法输出
+const POLL_TIMEOUT: Duration = Duration::from_secs(10);
+
+// 合并模式下一个实例的采集状态, 每次 processlist 时移动到单独的任务中执行
+struct MergedInstance {
+    instance: Instance,
+    host_port: String,
+    role: String,
+    cfg: ShowProcesslistConf, // 实例对应的阈值覆盖配置
+    reload_rx: watch::Receiver<Arc<reload::LoadedConfig>>,
+    health: InstanceHealth,
+    state: CollectorState,
+    db: Option<Pool<MySql>>,
+    reconnect_at: Option<Instant>, // 创建链接失败后, 到达该时间才重新链接
+    result: Result<PollResult, String>, // 最近一次 processlist 的结果
+}
+
+impl MergedInstance {
+    fn new(instance: Instance) -> Result<MergedInstance, CustomError> {
+        let host_port = format!(
+            "{host}:{port}",
+            host = instance.machine_host.as_ref().unwrap(),
+            port = instance.port.unwrap()
+        );
+        let instance_cfg = reload::current().get_instance_cfg(&instance);
+
+        Ok(MergedInstance {
+            role: instance.role.clone().unwrap_or_default(),
+            health: InstanceHealth::new(&instance_cfg, &host_port),
+            state: CollectorState::new(&instance_cfg)?,
+            reload_rx: reload::subscribe(),
+            cfg: instance_cfg,
+            instance,
+            host_port,
+            db: None,
+            reconnect_at: None,
+            result: Err(String::from("没有执行 processlist")),
+        })
+    }
+}
+
+// --merged-view: 集群所有实例同步执行 processlist, 任意一个实例超过阈值时合并输出所有实例的 processlist
+pub async fn run(instances: Vec<Instance>) -> Result<(), CustomError> {
+    let mut merged_instances = Vec::<MergedInstance>::new();
+    for instance in instances {
+        merged_instances.push(MergedInstance::new(instance)?);
+    }
+    // 主库在前, 其他实例保持原来的顺序
+    merged_instances.sort_by_key(|v| v.role != ROLE_MASTER);
+
+    let mut poll_sleep = AdaptiveSleep::new();
+    while !shutdown::is_shutdown() {
+        merged_instances = poll_all(merged_instances).await;
+
+        let loaded = reload::current();
+        let cfg = &loaded.cfg;
+        let is_incident = merged_instances
+            .iter()
+            .any(|v| matches!(&v.result, Ok(poll) if poll.is_incident));
+        poll_sleep.update(cfg, &cfg.vip_port, is_incident);
+
+        // 任意一个实例满足 --threshold-filter 的线程数超过 --print-cnt-threshold 时输出
+        if is_incident {
+            print_data(cfg, &get_merged_data(cfg, &merged_instances));
+            save_snapshots(&mut merged_instances);
+        }
+
+        shutdown::sleep(connection_budget::get_poll_sleep(
+            cfg,
+            poll_sleep.get_sleep(cfg),
+        ))
+        .await;
+    }
+
+    for merged in merged_instances.iter() {
+        if let Some(db) = &merged.db {
+            db.close().await;
+        }
+        shutdown::record_collector(merged.state.poll_cnt, merged.state.snapshot_cnt);
+    }
+
+    Ok(())
+}
+
+// 所有实例同时执行一次 processlist, 全部完成后返回, 保持原来的顺序.
+// 任务异常退出时重新创建该实例的采集状态, 下一次重新链接
+async fn poll_all(merged_instances: Vec<MergedInstance>) -> Vec<MergedInstance> {
+    let instances = merged_instances
+        .iter()
+        .map(|v| v.instance.clone())
+        .collect::<Vec<Instance>>();
+    let mut tasks = JoinSet::new();
+    for (idx, merged) in merged_instances.into_iter().enumerate() {
+        tasks.spawn(async move { (idx, poll_instance(merged).await) });
+    }
+
+    let mut results = instances
+        .iter()
+        .map(|_| None)
+        .collect::<Vec<Option<MergedInstance>>>();
+    while let Some(result) = tasks.join_next().await {
+        match result {
+            Ok((idx, merged)) => results[idx] = Some(merged),
+            Err(e) => log::error!("执行 processlist 任务异常退出. {e}", e = e),
+        }
+    }
+
+    results
+        .into_iter()
+        .zip(instances)
+        .filter_map(|(result, instance)| match result {
+            Some(v) => Some(v),
+            None => match MergedInstance::new(instance) {
+                Ok(mut merged) => {
+                    merged.result = Err(String::from("执行 processlist 任务异常退出"));
+                    Some(merged)
+                }
+                Err(e) => {
+                    log::error!("重新创建实例采集状态失败. {e}", e = e);
+                    None
+                }
+            },
+        })
+        .collect()
+}
+
+async fn poll_instance(mut merged: MergedInstance) -> MergedInstance {
+    reload::apply_changed(
+        &mut merged.reload_rx,
+        &merged.instance,
+        &mut merged.cfg,
+        &mut merged.state,
+    );
+
+    let db = match merged.db.take() {
+        Some(v) => v,
+        None => match connect(&mut merged).await {
+            Some(v) => v,
+            None => return merged,
+        },
+    };
+
+    let result = tokio::time::timeout(
+        POLL_TIMEOUT,
+        collector::poll_processlist(
+            &merged.cfg,
+            &merged.instance,
+            &db,
+            &mut merged.state,
+            OutputSink::Merged,
+        ),
+    )
+    .await;
+    match result {
+        Ok(Ok(poll)) => {
+            merged.state.poll_cnt += 1;
+            merged.health.on_success();
+            merged.result = Ok(poll);
+            merged.db = Some(db);
+        }
+        Ok(Err(e)) => {
+            metrics::record_error(&merged.instance);
+            merged.result = Err(e.to_string());
+            // 使用原来的链接重试时不等待, 下一次同步执行时重试
+            match merged.health.on_error(&e) {
+                Some(_) => merged.db = Some(db),
+                None => db.close().await,
+            }
+        }
+        Err(_) => {
+            // 链接上可能还有没有返回的语句, 不等待关闭, 下一次重新链接
+            let e = get_timeout_error(&merged.host_port);
+            metrics::record_error(&merged.instance);
+            merged.health.on_error(&e);
+            merged.result = Err(e.to_string());
+        }
+    }
+
+    merged
+}
+
+fn get_timeout_error(host_port: &str) -> CustomError {
+    CustomError::new(format!(
+        "{host_port}, 超过 {timeout}s 没有返回",
+        host_port = host_port,
+        timeout = POLL_TIMEOUT.as_secs()
+    ))
+}
+
+// 没有链接时创建链接, 失败后等待退避时间再重新链接, 期间保留最近一次的错误信息
+async fn connect(merged: &mut MergedInstance) -> Option<Pool<MySql>> {
+    if let Some(reconnect_at) = merged.reconnect_at {
+        if Instant::now() < reconnect_at {
+            return None;
+        }
+    }
+
+    merged.health.on_connecting();
+    let result = tokio::time::timeout(POLL_TIMEOUT, async {
+        let db = common::connect_instance(&merged.cfg, &merged.instance).await?;
+        let (source, version) = ProcesslistSource::detect(&merged.cfg, &db, &merged.instance).await;
+        Ok((db, source, version))
+    })
+    .await
+    .unwrap_or_else(|_| Err(get_timeout_error(&merged.host_port)));
+    match result {
+        Ok((db, source, version)) => {
+            merged.reconnect_at = None;
+            merged.state.source = source;
+            merged.state.version = version;
+            Some(db)
+        }
+        Err(e) => {
+            metrics::record_error(&merged.instance);
+            merged.reconnect_at = Some(Instant::now() + merged.health.on_connect_error(&e));
+            merged.result = Err(e.to_string());
+            None
+        }
+    }
+}
+
+// 输出后保存到历史库, 记录快照次数
+fn save_snapshots(merged_instances: &mut [MergedInstance]) {
+    for merged in merged_instances.iter_mut() {
+        if let Ok(poll) = &merged.result {
+            merged.state.snapshot_cnt += 1;
+            history::save(&merged.instance, &poll.output_infos);
+        }
+    }
+}
+
+fn get_instance_summary(merged: &MergedInstance) -> InstanceSummary {
+    match &merged.result {
+        Ok(poll) => InstanceSummary {
+            role: merged.role.clone(),
+            total: poll.total,
+            filter_sleep: poll.output_infos.len(),
+            threshold: poll.threshold,
+            error: None,
+        },
+        Err(e) => InstanceSummary {
+            role: merged.role.clone(),
+            error: Some(e.clone()),
+            ..Default::default()
+        },
+    }
+}
+
+fn get_cluster_summary(summaries: &[InstanceSummary], incident_cnt: usize) -> ClusterSummary {
+    ClusterSummary {
+        instance_cnt: summaries.len(),
+        error_cnt: summaries.iter().filter(|v| v.error.is_some()).count(),
+        incident_cnt,
+        total: summaries.iter().map(|v| v.total).sum(),
+        filter_sleep: summaries.iter().map(|v| v.filter_sleep).sum(),
+        threshold: summaries.iter().map(|v| v.threshold).sum(),
+    }
+}
+
+// 生成合并输出的内容: 一行集群汇总, 每个实例的小计, 所有实例的 processlist.
+// text 格式只用于查看, analyze-processlist 只能解析 jsonl 格式中每个实例的 processlist 记录
+fn get_merged_data(cfg: &ShowProcesslistConf, merged_instances: &[MergedInstance]) -> String {
+    let time = utils::time::now_str(utils::time::MILLIS_FMT);
+    let cluster_name = merged_instances
+        .first()
+        .and_then(|v| v.instance.cluster_name.as_ref());
+    let summaries = merged_instances
+        .iter()
+        .map(get_instance_summary)
+        .collect::<Vec<InstanceSummary>>();
+    let incident_cnt = merged_instances
+        .iter()
+        .filter(|v| matches!(&v.result, Ok(poll) if poll.is_incident))
+        .count();
+    let cluster_summary = get_cluster_summary(&summaries, incident_cnt);
+
+    if cfg.is_jsonl() {
+        let mut data = common::get_records_jsonl(
+            RECORD_TYPE_CLUSTER_SUMMARY,
+            &cfg.vip_port,
+            cluster_name,
+            &time,
+            &[cluster_summary],
+        );
+        for (merged, summary) in merged_instances.iter().zip(summaries) {
+            data.push_str(&common::get_records_jsonl(
+                RECORD_TYPE_INSTANCE_SUMMARY,
+                &merged.host_port,
+                cluster_name,
+                &time,
+                &[summary],
+            ));
+        }
+        for merged in merged_instances.iter() {
+            if let Ok(poll) = &merged.result {
+                data.push_str(&common::get_records_jsonl(
+                    RECORD_TYPE_CONNECTION_SATURATION,
+                    &merged.host_port,
+                    cluster_name,
+                    &time,
+                    poll.saturation.as_slice(),
+                ));
+                data.push_str(&common::get_records_jsonl(
+                    RECORD_TYPE_PROCESSLIST,
+                    &merged.host_port,
+                    cluster_name,
+                    &time,
+                    &poll.output_infos,
+                ));
+            }
+        }
+        return data;
+    }
+
+    let mut data = format!(
+        "\n---- Cluster: {cluster_name}({vip_port}) Time: {time}, Instances: {instance_cnt}(Error: {error_cnt}, Over Threshold: {incident_cnt}), Total: {total}, Filter Sleep: {filter_sleep}, Threshold: {threshold} ----\n",
+        cluster_name = cluster_name.map(|v| v.as_str()).unwrap_or_default(),
+        vip_port = &cfg.vip_port,
+        time = &time,
+        instance_cnt = cluster_summary.instance_cnt,
+        error_cnt = cluster_summary.error_cnt,
+        incident_cnt = cluster_summary.incident_cnt,
+        total = cluster_summary.total,
+        filter_sleep = cluster_summary.filter_sleep,
+        threshold = cluster_summary.threshold,
+    );
+    data.push_str("Instance Summary:\n");
+    data.push_str(&get_instance_summary_table(merged_instances, &summaries));
+    for merged in merged_instances.iter() {
+        if let Ok(PollResult {
+            saturation: Some(saturation),
+            ..
+        }) = &merged.result
+        {
+            data.push_str(&format!(
+                "Connection Saturation({host_port}):\n",
+                host_port = &merged.host_port
+            ));
+            data.push_str(&saturation::get_saturation_text(saturation));
+        }
+    }
+    data.push_str("Processlist:\n");
+    data.push_str(&get_merged_infos_table(merged_instances));
+
+    data
+}
+
+fn get_instance_summary_table(
+    merged_instances: &[MergedInstance],
+    summaries: &[InstanceSummary],
+) -> String {
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+    table.set_titles(Row::new(vec![
+        Cell::new("Instance"),
+        Cell::new("Role"),
+        Cell::new("Total"),
+        Cell::new("Filter Sleep"),
+        Cell::new("Threshold"),
+        Cell::new("Error"),
+    ]));
+    for (merged, summary) in merged_instances.iter().zip(summaries) {
+        table.add_row(Row::new(vec![
+            Cell::new(&merged.host_port),
+            Cell::new(&summary.role),
+            Cell::new(&summary.total.to_string()),
+            Cell::new(&summary.filter_sleep.to_string()),
+            Cell::new(&summary.threshold.to_string()),
+            Cell::new(summary.error.as_deref().unwrap_or_default()),
+        ]));
+    }
+
+    table.to_string()
+}
+
+// 所有实例的 processlist 合并为一个表格, 前面添加实例和角色列
+fn get_merged_infos_table(merged_instances: &[MergedInstance]) -> String {
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+    table.set_titles(Row::new(vec![
+        Cell::new("Instance"),
+        Cell::new("Role"),
+        Cell::new("Id"),
+        Cell::new("User"),
+        Cell::new("Host"),
+        Cell::new("db"),
+        Cell::new("Command"),
+        Cell::new("Time"),
+        Cell::new("State"),
+        Cell::new("Info"),
+    ]));
+    for merged in merged_instances.iter() {
+        let poll = match &merged.result {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+        for info in poll.output_infos.iter() {
+            let mut cells = vec![Cell::new(&merged.host_port), Cell::new(&merged.role)];
+            cells.extend(common::get_info_cells(info));
+            table.add_row(Row::new(cells));
+        }
+    }
+
+    table.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::show_processlist::merged_handler::get_cluster_summary;
+    use crate::models::{ClusterSummary, InstanceSummary};
+
+    #[test]
+    fn test_get_cluster_summary() {
+        let summaries = vec![
+            InstanceSummary {
+                role: String::from("master"),
+                total: 100,
+                filter_sleep: 60,
+                threshold: 55,
+                error: None,
+            },
+            InstanceSummary {
+                role: String::from("slave"),
+                total: 20,
+                filter_sleep: 3,
+                threshold: 1,
+                error: None,
+            },
+            InstanceSummary {
+                role: String::from("slave"),
+                error: Some(String::from("链接失败")),
+                ..Default::default()
+            },
+        ];
+        let summary = get_cluster_summary(&summaries, 1);
+        println!("{:?}", summary);
+        assert_eq!(
+            summary,
+            ClusterSummary {
+                instance_cnt: 3,
+                error_cnt: 1,
+                incident_cnt: 1,
+                total: 120,
+                filter_sleep: 63,
+                threshold: 56,
+            }
+        );
+    }
+}
@@ -0,0 +1,397 @@
+use crate::error::CustomError;
+use crate::models::{Instance, ShowProcesslistInfo};
+use crate::utils;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::sync::{OnceLock, RwLock};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+const METRICS_PATH: &str = "/metrics";
+const COMMAND_SLEEP: &str = "Sleep";
+const MAX_REQUEST_HEADER_SIZE: u64 = 8 * 1024;
+
+// 全局指标, 没有开启 --metrics-addr 时为空, 更新指标的方法直接忽略
+static METRICS: OnceLock<RwLock<Metrics>> = OnceLock::new();
+
+type GaugeValue = fn(&InstanceMetrics) -> i64;
+type GroupValue = fn(&InstanceMetrics) -> &HashMap<String, usize>;
+
+#[derive(Default)]
+struct Metrics {
+    monitored_instances: usize,
+    instances: BTreeMap<String, InstanceMetrics>, // host:port -> 实例指标
+}
+
+#[derive(Default)]
+struct InstanceMetrics {
+    cluster_name: String,
+    threads: usize,
+    active_threads: usize, // 非 Sleep 线程数
+    threads_by_command: HashMap<String, usize>,
+    threads_by_state: HashMap<String, usize>,
+    threads_by_user: HashMap<String, usize>,
+    max_time: i64,
+    last_success_timestamp: i64,
+    consecutive_errors: u64,
+}
+
+impl Metrics {
+    fn record_success(
+        &mut self,
+        instance: &Instance,
+        infos: &[ShowProcesslistInfo],
+        now_timestamp: i64,
+    ) {
+        let mut instance_metrics = InstanceMetrics {
+            cluster_name: instance.cluster_name.clone().unwrap_or_default(),
+            threads: infos.len(),
+            last_success_timestamp: now_timestamp,
+            ..Default::default()
+        };
+        for info in infos.iter() {
+            let command = info.command.as_deref().unwrap_or("");
+            *instance_metrics
+                .threads_by_command
+                .entry(command.to_string())
+                .or_default() += 1;
+            if command == COMMAND_SLEEP {
+                continue;
+            }
+
+            instance_metrics.active_threads += 1;
+            *instance_metrics
+                .threads_by_state
+                .entry(info.state.clone().unwrap_or_default())
+                .or_default() += 1;
+            *instance_metrics
+                .threads_by_user
+                .entry(info.user.clone().unwrap_or_default())
+                .or_default() += 1;
+            instance_metrics.max_time =
+                instance_metrics.max_time.max(info.time.unwrap_or(0) as i64);
+        }
+
+        self.instances
+            .insert(instance_key(instance), instance_metrics);
+    }
+
+    fn record_error(&mut self, instance: &Instance) {
+        let instance_metrics = self
+            .instances
+            .entry(instance_key(instance))
+            .or_insert_with(|| InstanceMetrics {
+                cluster_name: instance.cluster_name.clone().unwrap_or_default(),
+                ..Default::default()
+            });
+        instance_metrics.consecutive_errors += 1;
+    }
+
+    fn render(&self) -> String {
+        let mut data = String::new();
+        write_header(
+            &mut data,
+            "mysql_processlist_monitored_instances",
+            "正在采集 processlist 的实例数",
+        );
+        let _ = writeln!(
+            data,
+            "mysql_processlist_monitored_instances {cnt}",
+            cnt = self.monitored_instances
+        );
+
+        let gauges: [(&str, &str, GaugeValue); 5] = [
+            ("mysql_processlist_threads", "线程总数", |m| {
+                m.threads as i64
+            }),
+            (
+                "mysql_processlist_active_threads",
+                "非 Sleep 线程数",
+                |m| m.active_threads as i64,
+            ),
+            (
+                "mysql_processlist_max_time_seconds",
+                "非 Sleep 线程中最长的 TIME",
+                |m| m.max_time,
+            ),
+            (
+                "mysql_processlist_last_success_timestamp_seconds",
+                "最后一次成功执行 processlist 的时间戳",
+                |m| m.last_success_timestamp,
+            ),
+            (
+                "mysql_processlist_consecutive_errors",
+                "连续执行 processlist 失败的次数",
+                |m| m.consecutive_errors as i64,
+            ),
+        ];
+        for (name, help, get_value) in gauges.iter() {
+            write_header(&mut data, name, help);
+            for (instance, m) in self.instances.iter() {
+                let _ = writeln!(
+                    data,
+                    "{name}{{{labels}}} {value}",
+                    name = name,
+                    labels = get_labels(instance, m, None),
+                    value = get_value(m)
+                );
+            }
+        }
+
+        let groups: [(&str, &str, &str, GroupValue); 3] = [
+            (
+                "mysql_processlist_threads_by_command",
+                "按 COMMAND 分组的线程数",
+                "command",
+                |m| &m.threads_by_command,
+            ),
+            (
+                "mysql_processlist_threads_by_state",
+                "按 STATE 分组的非 Sleep 线程数",
+                "state",
+                |m| &m.threads_by_state,
+            ),
+            (
+                "mysql_processlist_threads_by_user",
+                "按 USER 分组的非 Sleep 线程数",
+                "user",
+                |m| &m.threads_by_user,
+            ),
+        ];
+        for (name, help, label, get_group) in groups.iter() {
+            write_header(&mut data, name, help);
+            for (instance, m) in self.instances.iter() {
+                let mut group = get_group(m).iter().collect::<Vec<(&String, &usize)>>();
+                group.sort();
+                for (value, cnt) in group {
+                    let _ = writeln!(
+                        data,
+                        "{name}{{{labels}}} {cnt}",
+                        name = name,
+                        labels = get_labels(instance, m, Some((label, value))),
+                        cnt = cnt
+                    );
+                }
+            }
+        }
+
+        data
+    }
+}
+
+// 启动 http 服务, 通过 /metrics 输出 prometheus 格式的指标
+pub async fn start_server(addr: &str) -> Result<(), CustomError> {
+    let listener = TcpListener::bind(addr).await.map_err(|e| {
+        CustomError::new(format!(
+            "启动 metrics 服务失败. 地址: {addr}. {e}",
+            addr = addr,
+            e = e
+        ))
+    })?;
+    METRICS.get_or_init(|| RwLock::new(Metrics::default()));
+    log::info!(
+        "metrics 服务启动成功: http://{addr}{METRICS_PATH}",
+        addr = addr
+    );
+
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = match listener.accept().await {
+                Ok(v) => v,
+                Err(e) => {
+                    log::error!("metrics 服务接收链接失败. {e}", e = e);
+                    continue;
+                }
+            };
+            tokio::spawn(async move {
+                if let Err(e) = handle_connection(stream).await {
+                    log::warn!("metrics 服务处理请求失败. {e}", e = e);
+                }
+            });
+        }
+    });
+
+    Ok(())
+}
+
+async fn handle_connection(mut stream: TcpStream) -> std::io::Result<()> {
+    let request_line = read_request_line(&mut stream).await?;
+    let path = request_line.split_whitespace().nth(1).unwrap_or("");
+
+    let response = if path == METRICS_PATH {
+        let body = render();
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4; charset=utf-8\r\nContent-Length: {len}\r\nConnection: close\r\n\r\n{body}",
+            len = body.len(),
+            body = body
+        )
+    } else {
+        String::from("HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+    };
+
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+// 只需要请求行, 请求头读取到空行为止但不解析, 最多读取 MAX_REQUEST_HEADER_SIZE 字节
+async fn read_request_line(stream: &mut TcpStream) -> std::io::Result<String> {
+    let mut reader = BufReader::new(stream).take(MAX_REQUEST_HEADER_SIZE);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 || line.trim_end().is_empty() {
+            break;
+        }
+    }
+
+    Ok(request_line)
+}
+
+fn instance_key(instance: &Instance) -> String {
+    format!(
+        "{host}:{port}",
+        host = instance.machine_host.as_ref().unwrap(),
+        port = instance.port.unwrap()
+    )
+}
+
+// 一次 processlist 成功后更新实例指标
+pub fn record_success(instance: &Instance, infos: &[ShowProcesslistInfo]) {
+    if let Some(metrics) = METRICS.get() {
+        metrics
+            .write()
+            .unwrap()
+            .record_success(instance, infos, utils::time::now_timestamp());
+    }
+}
+
+// 一次 processlist 失败后增加连续失败次数, 保留上一次成功的指标
+pub fn record_error(instance: &Instance) {
+    if let Some(metrics) = METRICS.get() {
+        metrics.write().unwrap().record_error(instance);
+    }
+}
+
+// 实例不再采集时移除指标
+pub fn remove_instance(instance: &Instance) {
+    if let Some(metrics) = METRICS.get() {
+        metrics
+            .write()
+            .unwrap()
+            .instances
+            .remove(&instance_key(instance));
+    }
+}
+
+// 使用 --all 参数时, 正在采集的实例数
+pub fn set_monitored_instances(cnt: usize) {
+    if let Some(metrics) = METRICS.get() {
+        metrics.write().unwrap().monitored_instances = cnt;
+    }
+}
+
+fn render() -> String {
+    match METRICS.get() {
+        Some(v) => v.read().unwrap().render(),
+        None => String::new(),
+    }
+}
+
+fn write_header(data: &mut String, name: &str, help: &str) {
+    let _ = writeln!(data, "# HELP {name} {help}");
+    let _ = writeln!(data, "# TYPE {name} gauge");
+}
+
+fn get_labels(instance: &str, m: &InstanceMetrics, extra: Option<(&str, &str)>) -> String {
+    let mut labels = format!(
+        r#"instance="{instance}",cluster="{cluster}""#,
+        instance = escape_label_value(instance),
+        cluster = escape_label_value(&m.cluster_name)
+    );
+    if let Some((name, value)) = extra {
+        let _ = write!(
+            labels,
+            r#",{name}="{value}""#,
+            value = escape_label_value(value)
+        );
+    }
+
+    labels
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', r#"\""#)
+        .replace('\n', r"\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::show_processlist::metrics::Metrics;
+    use crate::models::{Instance, ShowProcesslistInfo};
+
+    #[test]
+    fn test_metrics_render() {
+        let instance = Instance {
+            machine_host: Some(String::from("127.0.0.1")),
+            port: Some(3306),
+            cluster_name: Some(String::from("cluster\"a\\b")),
+            ..Default::default()
+        };
+        let get_info = |user: &str, command: &str, time: i32| ShowProcesslistInfo {
+            user: Some(user.to_string()),
+            command: Some(command.to_string()),
+            state: Some(String::from("Sending data")),
+            time: Some(time),
+            ..Default::default()
+        };
+
+        let mut metrics = Metrics::default();
+        metrics.record_success(
+            &instance,
+            &[
+                get_info("app", "Query", 20),
+                get_info("app\nx", "Query", 5),
+                get_info("app", "Sleep", 100),
+            ],
+            1000,
+        );
+        metrics.record_error(&instance);
+        let data = metrics.render();
+        println!("{}", data);
+
+        let labels = r#"instance="127.0.0.1:3306",cluster="cluster\"a\\b""#;
+        assert!(data.contains("# HELP mysql_processlist_threads 线程总数\n"));
+        assert!(data.contains("# TYPE mysql_processlist_threads gauge\n"));
+        assert!(data.contains(&format!("mysql_processlist_threads{{{labels}}} 3\n")));
+        assert!(data.contains(&format!("mysql_processlist_active_threads{{{labels}}} 2\n")));
+        assert!(data.contains(&format!(
+            "mysql_processlist_max_time_seconds{{{labels}}} 20\n"
+        )));
+        // 失败时保留上一次成功的指标, 只增加连续失败次数
+        assert!(data.contains(&format!(
+            "mysql_processlist_last_success_timestamp_seconds{{{labels}}} 1000\n"
+        )));
+        assert!(data.contains(&format!(
+            "mysql_processlist_consecutive_errors{{{labels}}} 1\n"
+        )));
+        assert!(data.contains(&format!(
+            r#"mysql_processlist_threads_by_user{{{labels},user="app\nx"}} 1"#
+        )));
+        assert!(data.contains(&format!(
+            r#"mysql_processlist_threads_by_command{{{labels},command="Sleep"}} 1"#
+        )));
+
+        // 再次成功后连续失败次数清零
+        metrics.record_success(&instance, &[], 2000);
+        let data = metrics.render();
+        assert!(data.contains(&format!(
+            "mysql_processlist_consecutive_errors{{{labels}}} 0\n"
+        )));
+        assert!(!data.contains("mysql_processlist_threads_by_user{"));
+    }
+}
@@ -1,5 +1,27 @@
+pub mod adaptive_sleep;
+pub mod alert;
 pub mod all_cluster_handler;
+pub mod collector;
+pub mod collector_state;
 pub mod common;
+pub mod connection_budget;
+pub mod diagnostic;
+pub mod explain;
+pub mod filter;
 pub mod handler;
+pub mod health;
+pub mod history;
+pub mod killer;
+pub mod lifecycle;
+pub mod merged_handler;
+pub mod metrics;
+pub mod output_file;
+pub mod pre_trigger;
+pub mod reload;
+pub mod saturation;
+pub mod shutdown;
+pub mod source;
+pub mod summary;
+pub mod threshold_override;
 
 pub use handler::run;
@@ -0,0 +1,221 @@
+use crate::config::show_processlist_conf::ShowProcesslistConf;
+use crate::error::CustomError;
+use crate::models::Instance;
+use crate::utils;
+use chrono::NaiveDate;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+const DATE_FMT: &str = "%Y-%m-%d";
+const DATE_FMT_LEN: usize = 10; // DATE_FMT 格式化后的长度, 例如: 2023-01-31
+const UNKNOWN_CLUSTER_NAME: &str = "unknown_cluster";
+
+// 使用 --all 参数时, 每个实例输出的文件类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputKind {
+    Processlist,
+    QueryEvents,
+    Diagnostic,
+}
+
+impl OutputKind {
+    // 文件名中区分文件类型的部分
+    fn name_suffix(&self) -> &str {
+        match self {
+            OutputKind::Processlist => "",
+            OutputKind::QueryEvents => "_query_events",
+            OutputKind::Diagnostic => "_diagnostic",
+        }
+    }
+
+    fn ext<'a>(&self, cfg: &'a ShowProcesslistConf) -> &'a str {
+        match self {
+            OutputKind::QueryEvents => "jsonl",
+            _ => cfg.output_file_ext(),
+        }
+    }
+}
+
+// 获取实例输出文件路径.
+// flat: {output_dir}/{host}_{port}{suffix}.{ext}
+// cluster: {output_dir}/{集群名}/{host}_{port}/{日期}{suffix}.{ext}
+pub fn get_file_path(cfg: &ShowProcesslistConf, instance: &Instance, kind: OutputKind) -> String {
+    let host = instance.machine_host.as_ref().unwrap();
+    let port = instance.port.unwrap();
+
+    if cfg.is_cluster_layout() {
+        return format!(
+            "{dir}/{cluster_name}/{host}_{port}/{date}{suffix}.{ext}",
+            dir = &cfg.output_dir,
+            cluster_name = instance
+                .cluster_name
+                .as_deref()
+                .unwrap_or(UNKNOWN_CLUSTER_NAME),
+            host = host,
+            port = port,
+            date = utils::time::now_str(DATE_FMT),
+            suffix = kind.name_suffix(),
+            ext = kind.ext(cfg),
+        );
+    }
+
+    format!(
+        "{dir}/{host}_{port}{suffix}.{ext}",
+        dir = &cfg.output_dir,
+        host = host,
+        port = port,
+        suffix = kind.name_suffix(),
+        ext = kind.ext(cfg),
+    )
+}
+
+// 追加内容到实例输出文件, 开启滚动时按滚动策略写入
+pub async fn append(
+    cfg: &ShowProcesslistConf,
+    instance: &Instance,
+    kind: OutputKind,
+    data: &str,
+) -> Result<(), CustomError> {
+    let file_path = get_file_path(cfg, instance, kind);
+
+    let result = if cfg.is_rotate() {
+        // 历史文件在阻塞线程池中处理, 判断历史文件需要的参数复制一份
+        let archive_cfg = cfg.clone();
+        let file_name = file_name_of(&file_path).to_string();
+        utils::rotate_file::append_file_rotate(
+            &file_path,
+            data,
+            &cfg.get_rotate_policy(),
+            move |name| is_archive(&archive_cfg, kind, &file_name, name),
+        )
+        .await
+    } else {
+        utils::file::append_file(&file_path, data)
+    };
+
+    result.map_err(|e| {
+        CustomError::new(format!(
+            "写入文件失败, 文件: {file_path}, {e}",
+            file_path = &file_path,
+            e = e
+        ))
+    })
+}
+
+// 写入实例 processlist 文件, 开启滚动时按滚动策略写入, 否则达到清理时间清空文件
+pub async fn write_processlist(
+    cfg: &ShowProcesslistConf,
+    instance: &Instance,
+    clean_timestamp: &mut i64,
+    data: &str,
+) -> Result<(), CustomError> {
+    if cfg.is_rotate() {
+        return append(cfg, instance, OutputKind::Processlist, data).await;
+    }
+
+    let now_timestamp = utils::time::now_timestamp();
+
+    // 打开文件, 并且追加内容
+    let file_path = get_file_path(cfg, instance, OutputKind::Processlist);
+
+    let mut open_ops = fs::OpenOptions::new();
+    if !Path::new(&file_path).exists() {
+        open_ops.create_new(true).append(true);
+    } else {
+        // 达到需要清理文件的时间
+        if now_timestamp - *clean_timestamp >= cfg.clear_file_duration {
+            open_ops.write(true).truncate(true);
+
+            // 清理后清理时间变成当前时间
+            *clean_timestamp = now_timestamp;
+        } else {
+            // append打开
+            open_ops.append(true);
+        }
+    }
+
+    // 打开文件
+    let mut file = open_ops.open(&file_path).map_err(|e| {
+        CustomError::new(format!(
+            "打开文件出错. 路径: {file_path}. {e}",
+            file_path = &file_path,
+            e = e
+        ))
+    })?;
+    // processlist写入文件
+    file.write_all(data.as_bytes()).map_err(|e| {
+        CustomError::new(format!(
+            "写入 processlist 信息失败, 文件: {file_path}, {e}",
+            file_path = &file_path,
+            e = e
+        ))
+    })?;
+
+    Ok(())
+}
+
+fn file_name_of(file_path: &str) -> &str {
+    file_path.rsplit('/').next().unwrap_or(file_path)
+}
+
+// 判断同目录下的文件是否是当前输出文件的历史文件
+fn is_archive(cfg: &ShowProcesslistConf, kind: OutputKind, file_name: &str, name: &str) -> bool {
+    if name.starts_with(&format!("{file_name}.")) {
+        return true;
+    }
+    if !cfg.is_cluster_layout() {
+        return false;
+    }
+
+    // 按日期命名的文件, 之前日期的同类型文件也是历史文件
+    let (date, tail) = match (name.get(..DATE_FMT_LEN), name.get(DATE_FMT_LEN..)) {
+        (Some(date), Some(tail)) => (date, tail),
+        _ => return false,
+    };
+    if NaiveDate::parse_from_str(date, DATE_FMT).is_err() {
+        return false;
+    }
+
+    let kind_tail = format!(
+        "{suffix}.{ext}",
+        suffix = kind.name_suffix(),
+        ext = kind.ext(cfg)
+    );
+    tail == kind_tail || tail.starts_with(&format!("{kind_tail}."))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::show_processlist_conf::parse_test_args;
+    use crate::core::show_processlist::output_file::{is_archive, OutputKind};
+
+    #[test]
+    fn test_is_archive() {
+        let cfg = parse_test_args(&["--all", "--output-layout=cluster"]);
+
+        let file_name = "2023-01-31.log";
+        let kind = OutputKind::Processlist;
+        assert!(is_archive(
+            &cfg,
+            kind,
+            file_name,
+            "2023-01-31.log.20230131120000.gz"
+        ));
+        assert!(is_archive(&cfg, kind, file_name, "2023-01-30.log"));
+        assert!(is_archive(&cfg, kind, file_name, "2023-01-29.log.gz"));
+        assert!(!is_archive(
+            &cfg,
+            kind,
+            file_name,
+            "2023-01-30_diagnostic.log"
+        ));
+        assert!(!is_archive(
+            &cfg,
+            kind,
+            file_name,
+            "2023-01-30_query_events.jsonl"
+        ));
+        assert!(!is_archive(&cfg, kind, file_name, "other.log"));
+    }
+}
@@ -0,0 +1,116 @@
+use crate::config::show_processlist_conf::ShowProcesslistConf;
+use crate::core::show_processlist::common;
+use crate::models::{Instance, ShowProcesslistInfo};
+use std::collections::VecDeque;
+
+// 没有超过阈值时保存的快照, 超过阈值时补充输出
+pub struct BufferedSnapshot {
+    pub capture_time: String,
+    pub all_infos: Vec<ShowProcesslistInfo>,
+    pub infos: Vec<ShowProcesslistInfo>,
+}
+
+// 保存实例最近 capacity 次没有超过阈值的快照, 超过阈值时输出超过阈值前的情况
+pub struct PreTriggerBuffer {
+    capacity: usize,
+    snapshots: VecDeque<BufferedSnapshot>,
+}
+
+impl PreTriggerBuffer {
+    // capacity 为 0 不开启
+    pub fn new(capacity: usize) -> Option<PreTriggerBuffer> {
+        if capacity == 0 {
+            return None;
+        }
+
+        Some(PreTriggerBuffer {
+            capacity,
+            snapshots: VecDeque::with_capacity(capacity),
+        })
+    }
+
+    // 超过容量时丢弃最早的快照
+    pub fn push(&mut self, snapshot: BufferedSnapshot) {
+        if self.snapshots.len() >= self.capacity {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(snapshot);
+    }
+
+    // 取出所有快照并清空, 按采集时间正序
+    pub fn take(&mut self) -> Vec<BufferedSnapshot> {
+        self.snapshots.drain(..).collect()
+    }
+}
+
+// 生成超过阈值前的快照需要输出的内容, 每个快照标记为 pre-trigger
+pub fn get_pre_trigger_data(
+    cfg: &ShowProcesslistConf,
+    instance: &Instance,
+    snapshots: &[BufferedSnapshot],
+) -> String {
+    if snapshots.is_empty() {
+        return String::new();
+    }
+
+    let mut data = String::new();
+    if !cfg.is_jsonl() {
+        data.push_str(&format!(
+            "\n==== {host}:{port} 超过阈值前的 {cnt} 次快照(pre-trigger) ====\n",
+            host = instance.machine_host.as_ref().unwrap(),
+            port = instance.port.unwrap(),
+            cnt = snapshots.len()
+        ));
+    }
+    for snapshot in snapshots.iter() {
+        data.push_str(&common::get_snapshot_data(
+            cfg,
+            &common::Snapshot {
+                instance,
+                capture_time: snapshot.capture_time.clone(),
+                all_infos: &snapshot.all_infos,
+                infos: &snapshot.infos,
+                lock_waits: Vec::new(),
+                saturation: None,
+                explains: Vec::new(),
+                pre_trigger: true,
+            },
+        ));
+    }
+
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::show_processlist::pre_trigger::{BufferedSnapshot, PreTriggerBuffer};
+
+    #[test]
+    fn test_pre_trigger_buffer() {
+        assert!(PreTriggerBuffer::new(0).is_none());
+
+        let mut buffer = PreTriggerBuffer::new(3).unwrap();
+        for i in 0..5 {
+            buffer.push(BufferedSnapshot {
+                capture_time: format!("2023-01-31 16:00:0{i}"),
+                all_infos: Vec::new(),
+                infos: Vec::new(),
+            });
+        }
+        let times = buffer
+            .take()
+            .into_iter()
+            .map(|v| v.capture_time)
+            .collect::<Vec<String>>();
+        println!("{:?}", times);
+        assert_eq!(
+            times,
+            vec![
+                "2023-01-31 16:00:02",
+                "2023-01-31 16:00:03",
+                "2023-01-31 16:00:04"
+            ]
+        );
+        assert!(buffer.take().is_empty());
+    }
+}
@@ -0,0 +1,396 @@
+use crate::config::show_processlist_conf::ShowProcesslistConf;
+use crate::core::show_processlist::collector_state::CollectorState;
+use crate::core::show_processlist::filter::ProcesslistFilter;
+use crate::core::show_processlist::threshold_override::ThresholdOverrides;
+use crate::error::CustomError;
+use crate::models::Instance;
+use std::fs;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, SystemTime};
+use tokio::sync::watch;
+
+// 检测配置文件是否修改的间隔
+const WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+// 需要重启才能生效的配置项, 重新加载时忽略这些配置项的修改
+const RESTART_REQUIRED_FIELDS: [&str; 25] = [
+    "username",
+    "password",
+    "host",
+    "port",
+    "easydb_username",
+    "easydb_password",
+    "easydb_host",
+    "easydb_port",
+    "easydb_database",
+    "vip_port",
+    "merged_view",
+    "all",
+    "output_dir",
+    "output_layout",
+    "output_format",
+    "processlist_source",
+    "connection_budget",
+    "connect_rate",
+    "metrics_addr",
+    "health_log_interval",
+    "history_store",
+    "history_sqlite_file",
+    "history_database",
+    "history_table",
+    "is_sql_log",
+];
+// 启动时已经使用命令行参数初始化, 不能在配置文件中指定
+const UNSUPPORTED_FIELDS: [&str; 3] = ["log_file", "log_level", "config_file"];
+
+// 当前生效的配置, 包含阈值覆盖配置
+pub struct LoadedConfig {
+    pub cfg: ShowProcesslistConf,
+    pub threshold_overrides: ThresholdOverrides,
+}
+
+impl LoadedConfig {
+    // 实例使用的配置
+    pub fn get_instance_cfg(&self, instance: &Instance) -> ShowProcesslistConf {
+        self.threshold_overrides.apply(&self.cfg, instance)
+    }
+}
+
+// 命令行参数, 重新加载时在命令行参数的基础上覆盖配置文件中的配置
+static CLI_CONFIG: OnceLock<ShowProcesslistConf> = OnceLock::new();
+static CONFIG: OnceLock<watch::Sender<Arc<LoadedConfig>>> = OnceLock::new();
+
+fn get_sender() -> &'static watch::Sender<Arc<LoadedConfig>> {
+    CONFIG
+        .get()
+        .expect("配置没有初始化, 需要先调用 reload::init")
+}
+
+// 启动时加载配置, 返回命令行参数和配置文件合并后的配置
+pub fn init(cli_cfg: &ShowProcesslistConf) -> Result<Arc<LoadedConfig>, CustomError> {
+    let loaded = Arc::new(load(cli_cfg, None)?);
+    CLI_CONFIG.get_or_init(|| cli_cfg.clone());
+    CONFIG.get_or_init(|| watch::channel(loaded.clone()).0);
+
+    Ok(current())
+}
+
+// 当前生效的配置
+pub fn current() -> Arc<LoadedConfig> {
+    get_sender().borrow().clone()
+}
+
+// 订阅配置变化. 订阅前可能已经重新加载过, 标记为已变化, 第一次检查时和实例当前使用的配置比较一次
+pub fn subscribe() -> watch::Receiver<Arc<LoadedConfig>> {
+    let mut rx = get_sender().subscribe();
+    rx.mark_changed();
+    rx
+}
+
+// 等待配置重新加载
+pub async fn changed(rx: &mut watch::Receiver<Arc<LoadedConfig>>) {
+    // 发送端是全局变量, 不会被释放
+    if rx.changed().await.is_err() {
+        std::future::pending::<()>().await
+    }
+}
+
+// 配置重新加载后更新实例的配置和采集状态, 不需要重新链接实例
+pub fn apply_changed(
+    rx: &mut watch::Receiver<Arc<LoadedConfig>>,
+    instance: &Instance,
+    cfg: &mut ShowProcesslistConf,
+    state: &mut CollectorState,
+) {
+    if !rx.has_changed().unwrap_or(false) {
+        return;
+    }
+
+    let new_cfg = rx.borrow_and_update().get_instance_cfg(instance);
+    let changes = get_changes(cfg, &new_cfg);
+    if changes.is_empty() {
+        return;
+    }
+
+    let host_port = format!(
+        "{host}:{port}",
+        host = instance.machine_host.as_ref().unwrap(),
+        port = instance.port.unwrap()
+    );
+    if let Err(e) = state.reload(cfg, &new_cfg) {
+        log::error!(
+            "{host_port}, 使用新的配置失败, 继续使用原来的配置. {e}",
+            host_port = &host_port,
+            e = e
+        );
+        return;
+    }
+    log::info!(
+        "{host_port}, 使用新的配置: {changes}",
+        host_port = &host_port,
+        changes = changes.join(", ")
+    );
+    *cfg = new_cfg;
+}
+
+// 收到 SIGHUP 或者配置文件修改后重新加载配置
+pub fn spawn_reload_handler() {
+    let cli_cfg = match CLI_CONFIG.get() {
+        Some(v) => v.clone(),
+        None => return,
+    };
+
+    tokio::spawn(async move {
+        let mut modified = get_modified(&cli_cfg.config_file);
+        let mut sighup = get_sighup();
+        loop {
+            let reason = tokio::select! {
+                _ = wait_sighup(&mut sighup) => {
+                    // 修改文件后发送 SIGHUP 只需要重新加载一次
+                    modified = get_modified(&cli_cfg.config_file);
+                    "收到 SIGHUP 信号"
+                }
+                _ = tokio::time::sleep(WATCH_INTERVAL), if !cli_cfg.config_file.is_empty() => {
+                    let new_modified = get_modified(&cli_cfg.config_file);
+                    if new_modified == modified {
+                        continue;
+                    }
+                    modified = new_modified;
+                    "配置文件已修改"
+                }
+            };
+
+            log::info!(
+                "{reason}, 重新加载配置. 配置文件: {file}",
+                reason = reason,
+                file = &cli_cfg.config_file
+            );
+            if let Err(e) = reload(&cli_cfg) {
+                log::error!("重新加载配置失败, 继续使用原来的配置. {e}", e = e);
+            }
+        }
+    });
+}
+
+#[cfg(unix)]
+type Sighup = Option<tokio::signal::unix::Signal>;
+#[cfg(not(unix))]
+type Sighup = Option<()>;
+
+#[cfg(unix)]
+fn get_sighup() -> Sighup {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    match signal(SignalKind::hangup()) {
+        Ok(v) => Some(v),
+        Err(e) => {
+            log::error!("监听 SIGHUP 信号失败. {e}", e = e);
+            None
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn get_sighup() -> Sighup {
+    None
+}
+
+async fn wait_sighup(sighup: &mut Sighup) {
+    #[cfg(unix)]
+    if let Some(sighup) = sighup.as_mut() {
+        sighup.recv().await;
+        return;
+    }
+
+    let _ = sighup;
+    std::future::pending::<()>().await
+}
+
+fn get_modified(file: &str) -> Option<SystemTime> {
+    if file.is_empty() {
+        return None;
+    }
+
+    fs::metadata(file).and_then(|meta| meta.modified()).ok()
+}
+
+fn reload(cli_cfg: &ShowProcesslistConf) -> Result<(), CustomError> {
+    let sender = get_sender();
+    let old = current();
+    let loaded = load(cli_cfg, Some(&old.cfg))?;
+
+    let changes = get_changes(&old.cfg, &loaded.cfg);
+    log::info!(
+        "重新加载配置成功, 变更: {changes}",
+        changes = if changes.is_empty() {
+            String::from("无")
+        } else {
+            changes.join(", ")
+        }
+    );
+    sender.send_replace(Arc::new(loaded));
+
+    Ok(())
+}
+
+// 合并命令行参数和配置文件并检测. 重新加载时 current 为当前的配置, 需要重启才能生效的配置项保持不变
+fn load(
+    cli_cfg: &ShowProcesslistConf,
+    current: Option<&ShowProcesslistConf>,
+) -> Result<LoadedConfig, CustomError> {
+    let mut cfg = cli_cfg.clone();
+    if !cli_cfg.config_file.is_empty() {
+        cfg = merge_config_file(cli_cfg, current)?;
+    }
+
+    cfg.check()?;
+    if cfg.all {
+        cfg.check_all()?;
+    }
+    ProcesslistFilter::new(&cfg)?;
+    let threshold_overrides = ThresholdOverrides::load(&cfg)?;
+
+    Ok(LoadedConfig {
+        cfg,
+        threshold_overrides,
+    })
+}
+
+// 配置文件为 json 格式, 配置项和命令行参数相同(使用下划线), 例如:
+// {
+//   "ignore_instances": ["10.0.0.1:3306"],
+//   "print_cnt_threshold": 100,
+//   "threshold_filter": "command != 'Sleep' and time > 1"
+// }
+fn merge_config_file(
+    cli_cfg: &ShowProcesslistConf,
+    current: Option<&ShowProcesslistConf>,
+) -> Result<ShowProcesslistConf, CustomError> {
+    let file = &cli_cfg.config_file;
+    let data = fs::read_to_string(file).map_err(|e| {
+        CustomError::new(format!(
+            "读取配置文件失败. 文件: {file}. {e}",
+            file = file,
+            e = e
+        ))
+    })?;
+    let file_value = serde_json::from_str::<serde_json::Value>(&data).map_err(|e| {
+        CustomError::new(format!(
+            "解析配置文件失败. 文件: {file}. {e}",
+            file = file,
+            e = e
+        ))
+    })?;
+    let file_map = file_value.as_object().ok_or_else(|| {
+        CustomError::new(format!(
+            "配置文件内容需要是 json 对象. 文件: {file}",
+            file = file
+        ))
+    })?;
+
+    let mut value = to_value(cli_cfg)?;
+    let current_value = match current {
+        Some(v) => Some(to_value(v)?),
+        None => None,
+    };
+    let map = value.as_object_mut().unwrap();
+    for (key, item) in file_map.iter() {
+        if !map.contains_key(key) || UNSUPPORTED_FIELDS.contains(&key.as_str()) {
+            return Err(CustomError::new(format!(
+                "配置文件中不支持的配置项: {key}. 文件: {file}",
+                key = key,
+                file = file
+            )));
+        }
+        map.insert(key.clone(), item.clone());
+    }
+
+    // 重新加载时, 需要重启才能生效的配置项保持当前的值
+    if let Some(current_value) = current_value {
+        for key in RESTART_REQUIRED_FIELDS.iter() {
+            if map.get(*key) != current_value.get(*key) {
+                log::warn!("配置项 {key} 需要重启才能生效, 本次忽略", key = key);
+                map.insert(key.to_string(), current_value[*key].clone());
+            }
+        }
+    }
+
+    serde_json::from_value::<ShowProcesslistConf>(value).map_err(|e| {
+        CustomError::new(format!(
+            "配置文件中的配置项类型不正确. 文件: {file}. {e}",
+            file = file,
+            e = e
+        ))
+    })
+}
+
+fn to_value(cfg: &ShowProcesslistConf) -> Result<serde_json::Value, CustomError> {
+    serde_json::to_value(cfg).map_err(|e| CustomError::new(format!("配置序列化失败. {e}", e = e)))
+}
+
+// 比较两个配置, 返回修改的配置项, 例如: print_cnt_threshold: 50 -> 100
+fn get_changes(old: &ShowProcesslistConf, new: &ShowProcesslistConf) -> Vec<String> {
+    let (old_value, new_value) = match (to_value(old), to_value(new)) {
+        (Ok(old), Ok(new)) => (old, new),
+        _ => return Vec::new(),
+    };
+    let (old_map, new_map) = match (old_value.as_object(), new_value.as_object()) {
+        (Some(old), Some(new)) => (old, new),
+        _ => return Vec::new(),
+    };
+
+    new_map
+        .iter()
+        .filter(|(key, value)| old_map.get(*key) != Some(*value))
+        .map(|(key, value)| {
+            format!(
+                "{key}: {old} -> {new}",
+                key = key,
+                old = old_map.get(key).map(|v| v.to_string()).unwrap_or_default(),
+                new = value
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::show_processlist_conf::parse_test_args;
+    use crate::core::show_processlist::reload::{get_changes, merge_config_file};
+    use std::fs;
+
+    #[test]
+    fn test_merge_config_file() {
+        let file = std::env::temp_dir().join("mysql_tool_rs_test_reload.json");
+        let mut cli_cfg = parse_test_args(&["--all"]);
+        cli_cfg.config_file = file.to_string_lossy().to_string();
+
+        fs::write(
+            &file,
+            r#"{"print_cnt_threshold": 100, "ignore_instances": ["10.0.0.1:3306"], "output_dir": "/tmp/a"}"#,
+        )
+        .unwrap();
+        let cfg = merge_config_file(&cli_cfg, None).unwrap();
+        assert_eq!(cfg.print_cnt_threshold, 100);
+        assert_eq!(cfg.ignore_instances, vec![String::from("10.0.0.1:3306")]);
+        assert_eq!(cfg.output_dir, "/tmp/a");
+
+        // 重新加载时 output_dir 需要重启才能生效
+        fs::write(
+            &file,
+            r#"{"print_cnt_threshold": 200, "output_dir": "/tmp/b"}"#,
+        )
+        .unwrap();
+        let new_cfg = merge_config_file(&cli_cfg, Some(&cfg)).unwrap();
+        assert_eq!(new_cfg.print_cnt_threshold, 200);
+        assert_eq!(new_cfg.output_dir, "/tmp/a");
+        assert!(new_cfg.ignore_instances.is_empty());
+        println!("{:?}", get_changes(&cfg, &new_cfg));
+        assert_eq!(get_changes(&cfg, &new_cfg).len(), 2);
+
+        fs::write(&file, r#"{"log_level": "debug"}"#).unwrap();
+        assert!(merge_config_file(&cli_cfg, Some(&cfg)).is_err());
+
+        let _ = fs::remove_file(&file);
+    }
+}
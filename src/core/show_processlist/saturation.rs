@@ -0,0 +1,194 @@
+use crate::config::show_processlist_conf::ShowProcesslistConf;
+use crate::core::show_processlist::summary;
+use crate::dao::NormalDao;
+use crate::models::{ConnectionSaturation, ConnectionStats, Instance, ShowProcesslistInfo};
+use sqlx::{MySql, Pool};
+use std::collections::HashMap;
+
+const GROUP_BY_USER: &str = "user";
+const GROUP_BY_CLIENT_IP: &str = "client_ip";
+
+// 开启 --saturation-connected-pct 或 --saturation-running-pct 时读取链接相关的变量和状态,
+// 达到阈值时返回饱和信息. 获取失败只记录日志, 不影响 processlist
+pub async fn check_saturation(
+    cfg: &ShowProcesslistConf,
+    db: &Pool<MySql>,
+    instance: &Instance,
+    infos: &[ShowProcesslistInfo],
+) -> Option<ConnectionSaturation> {
+    if !cfg.is_saturation() {
+        return None;
+    }
+
+    let stats = match NormalDao::get_connection_stats(db).await {
+        Ok(v) => v,
+        Err(e) => {
+            log::error!(
+                "{host}:{port}, 获取链接数相关变量和状态失败. {e}",
+                host = instance.machine_host.as_ref().unwrap(),
+                port = instance.port.unwrap(),
+                e = e
+            );
+            return None;
+        }
+    };
+
+    let reasons = get_reasons(
+        &stats,
+        infos,
+        cfg.saturation_connected_pct,
+        cfg.saturation_running_pct,
+    );
+    if reasons.is_empty() {
+        return None;
+    }
+
+    // 链接数最多的用户和客户端, 包含 Sleep 线程
+    let (top_users, top_client_ips) = summary::get_group_summaries(infos, cfg.saturation_top)
+        .into_iter()
+        .filter(|v| v.group_by == GROUP_BY_USER || v.group_by == GROUP_BY_CLIENT_IP)
+        .partition(|v| v.group_by == GROUP_BY_USER);
+
+    Some(ConnectionSaturation {
+        connected_pct: get_pct(stats.threads_connected, stats.max_connections),
+        running_pct: get_pct(stats.threads_running, stats.max_connections),
+        max_used_pct: get_pct(stats.max_used_connections, stats.max_connections),
+        stats,
+        reasons,
+        top_users,
+        top_client_ips,
+    })
+}
+
+// 保留两位小数, max 为 0 时返回 0
+fn get_pct(value: u64, max: u64) -> f64 {
+    if max == 0 {
+        return 0.0;
+    }
+
+    (value as f64 * 10000.0 / max as f64).round() / 100.0
+}
+
+// 达到阈值的原因, 阈值为 0 不检测.
+// connected_pct 同时用于检测单个用户的链接数是否接近 max_user_connections
+fn get_reasons(
+    stats: &ConnectionStats,
+    infos: &[ShowProcesslistInfo],
+    connected_pct: u64,
+    running_pct: u64,
+) -> Vec<String> {
+    let mut reasons = Vec::new();
+    let is_reached =
+        |value: u64, max: u64, pct: u64| pct > 0 && max > 0 && value * 100 >= max * pct;
+
+    if is_reached(
+        stats.threads_connected,
+        stats.max_connections,
+        connected_pct,
+    ) {
+        reasons.push(format!(
+            "Threads_connected: {connected} 达到 max_connections: {max} 的 {pct}%",
+            connected = stats.threads_connected,
+            max = stats.max_connections,
+            pct = connected_pct
+        ));
+    }
+    if is_reached(stats.threads_running, stats.max_connections, running_pct) {
+        reasons.push(format!(
+            "Threads_running: {running} 达到 max_connections: {max} 的 {pct}%",
+            running = stats.threads_running,
+            max = stats.max_connections,
+            pct = running_pct
+        ));
+    }
+
+    if stats.max_user_connections > 0 && connected_pct > 0 {
+        let mut user_map = HashMap::<&str, u64>::new();
+        for info in infos.iter() {
+            *user_map
+                .entry(info.user.as_deref().unwrap_or_default())
+                .or_default() += 1;
+        }
+        let mut users = user_map
+            .into_iter()
+            .filter(|(_, cnt)| is_reached(*cnt, stats.max_user_connections, connected_pct))
+            .collect::<Vec<(&str, u64)>>();
+        users.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        for (user, cnt) in users {
+            reasons.push(format!(
+                "用户 {user} 链接数: {cnt} 达到 max_user_connections: {max} 的 {pct}%",
+                user = user,
+                cnt = cnt,
+                max = stats.max_user_connections,
+                pct = connected_pct
+            ));
+        }
+    }
+
+    reasons
+}
+
+// 快照中链接饱和度部分的内容
+pub fn get_saturation_text(saturation: &ConnectionSaturation) -> String {
+    let stats = &saturation.stats;
+    let mut data = format!(
+        "Threads_connected: {connected}({connected_pct}%), Threads_running: {running}({running_pct}%), Max_used_connections: {max_used}({max_used_pct}%), max_connections: {max}, max_user_connections: {max_user}\n",
+        connected = stats.threads_connected,
+        connected_pct = saturation.connected_pct,
+        running = stats.threads_running,
+        running_pct = saturation.running_pct,
+        max_used = stats.max_used_connections,
+        max_used_pct = saturation.max_used_pct,
+        max = stats.max_connections,
+        max_user = stats.max_user_connections,
+    );
+    for reason in saturation.reasons.iter() {
+        data.push_str(&format!("- {reason}\n", reason = reason));
+    }
+    let top = saturation
+        .top_users
+        .iter()
+        .chain(saturation.top_client_ips.iter())
+        .cloned()
+        .collect::<Vec<_>>();
+    if !top.is_empty() {
+        data.push_str(&summary::get_group_summary_table(&top));
+    }
+
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::show_processlist::saturation::{get_pct, get_reasons};
+    use crate::models::{ConnectionStats, ShowProcesslistInfo};
+
+    #[test]
+    fn test_get_reasons() {
+        let stats = ConnectionStats {
+            max_connections: 1000,
+            max_user_connections: 4,
+            threads_connected: 900,
+            threads_running: 50,
+            max_used_connections: 950,
+        };
+        let infos = ["app", "app", "app", "app", "root"]
+            .iter()
+            .map(|user| ShowProcesslistInfo {
+                user: Some(user.to_string()),
+                ..Default::default()
+            })
+            .collect::<Vec<ShowProcesslistInfo>>();
+
+        let reasons = get_reasons(&stats, &infos, 90, 10);
+        println!("{:?}", reasons);
+        assert_eq!(reasons.len(), 2);
+        assert!(reasons[0].starts_with("Threads_connected"));
+        assert!(reasons[1].starts_with("用户 app"));
+
+        assert_eq!(get_reasons(&stats, &infos, 0, 5).len(), 1);
+        assert!(get_reasons(&stats, &infos, 0, 0).is_empty());
+        assert_eq!(get_pct(2, 3), 66.67);
+        assert_eq!(get_pct(1, 0), 0.0);
+    }
+}
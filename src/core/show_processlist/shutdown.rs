@@ -0,0 +1,127 @@
+use crate::core::show_processlist::history;
+use std::future::Future;
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+// 等待后台任务(诊断信息, 告警)和历史快照写入结束的最长时间
+const BACKGROUND_TIMEOUT: Duration = Duration::from_secs(10);
+
+// 退出信号, true 表示收到 SIGINT/SIGTERM
+static SHUTDOWN: OnceLock<watch::Sender<bool>> = OnceLock::new();
+// 正在执行的后台任务数
+static BACKGROUND_TASKS: AtomicUsize = AtomicUsize::new(0);
+// 退出时输出的汇总信息
+static STOPPED_COLLECTORS: AtomicU64 = AtomicU64::new(0);
+static POLL_CNT: AtomicU64 = AtomicU64::new(0);
+static SNAPSHOT_CNT: AtomicU64 = AtomicU64::new(0);
+
+fn get_sender() -> &'static watch::Sender<bool> {
+    SHUTDOWN.get_or_init(|| watch::channel(false).0)
+}
+
+// 监听 SIGINT/SIGTERM, 收到后通知所有采集任务在当前这一次 processlist 结束后退出
+pub fn spawn_signal_handler() {
+    get_sender();
+
+    tokio::spawn(async move {
+        let signal = wait_signal().await;
+        log::info!(
+            "收到 {signal} 信号, 等待所有实例完成当前这一次 processlist 后退出",
+            signal = signal
+        );
+        get_sender().send_replace(true);
+    });
+}
+
+#[cfg(unix)]
+async fn wait_signal() -> &'static str {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut sigterm = match signal(SignalKind::terminate()) {
+        Ok(v) => v,
+        Err(e) => {
+            log::error!("监听 SIGTERM 信号失败. {e}", e = e);
+            let _ = tokio::signal::ctrl_c().await;
+            return "SIGINT";
+        }
+    };
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => "SIGINT",
+        _ = sigterm.recv() => "SIGTERM",
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_signal() -> &'static str {
+    let _ = tokio::signal::ctrl_c().await;
+    "SIGINT"
+}
+
+pub fn is_shutdown() -> bool {
+    *get_sender().borrow()
+}
+
+// 收到退出信号后返回
+pub async fn wait() {
+    let mut rx = get_sender().subscribe();
+    // 发送端是全局变量, 不会被释放
+    let _ = rx.wait_for(|is_shutdown| *is_shutdown).await;
+}
+
+// 休眠, 收到退出信号时提前返回 false
+pub async fn sleep(duration: Duration) -> bool {
+    tokio::select! {
+        _ = tokio::time::sleep(duration) => !is_shutdown(),
+        _ = wait() => false,
+    }
+}
+
+// 启动需要在退出前完成的后台任务, 例如采集诊断信息, 发送告警
+pub fn spawn<F>(future: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    BACKGROUND_TASKS.fetch_add(1, Ordering::SeqCst);
+    tokio::spawn(async move {
+        future.await;
+        BACKGROUND_TASKS.fetch_sub(1, Ordering::SeqCst);
+    });
+}
+
+// 一个实例采集任务退出时记录汇总信息
+pub fn record_collector(poll_cnt: u64, snapshot_cnt: u64) {
+    STOPPED_COLLECTORS.fetch_add(1, Ordering::SeqCst);
+    POLL_CNT.fetch_add(poll_cnt, Ordering::SeqCst);
+    SNAPSHOT_CNT.fetch_add(snapshot_cnt, Ordering::SeqCst);
+}
+
+// 所有采集任务退出后调用, 等待后台任务结束, 关闭历史库, 刷新输出并打印汇总信息
+pub async fn finish(start_time: Instant) {
+    let deadline = Instant::now() + BACKGROUND_TIMEOUT;
+    while BACKGROUND_TASKS.load(Ordering::SeqCst) > 0 && Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let unfinished = BACKGROUND_TASKS.load(Ordering::SeqCst);
+    if unfinished > 0 {
+        log::warn!(
+            "等待后台任务超时, 还有 {unfinished} 个任务没有完成",
+            unfinished = unfinished
+        );
+    }
+    history::close(deadline.saturating_duration_since(Instant::now())).await;
+
+    log::info!(
+        "processlist 采集已停止. 运行时长: {duration}s, 实例数: {collectors}, processlist 次数: {polls}, 输出快照数: {snapshots}",
+        duration = start_time.elapsed().as_secs(),
+        collectors = STOPPED_COLLECTORS.load(Ordering::SeqCst),
+        polls = POLL_CNT.load(Ordering::SeqCst),
+        snapshots = SNAPSHOT_CNT.load(Ordering::SeqCst)
+    );
+
+    let _ = std::io::stdout().flush();
+    log::logger().flush();
+}
@@ -0,0 +1,218 @@
+use crate::config::show_processlist_conf::{
+    ShowProcesslistConf, PROCESSLIST_SOURCE_AUTO, PROCESSLIST_SOURCE_INFORMATION_SCHEMA,
+    PROCESSLIST_SOURCE_PS_PROCESSLIST, PROCESSLIST_SOURCE_PS_THREADS,
+};
+use crate::dao::NormalDao;
+use crate::models::{Instance, ShowProcesslistInfo};
+use sqlx::{Error, MySql, Pool};
+
+// performance_schema.processlist 从 8.0.22 开始提供
+const PS_PROCESSLIST_VERSION: (u32, u32, u32) = (8, 0, 22);
+// EXECUTION_ENGINE 列从 8.0.29 开始提供
+const EXECUTION_ENGINE_VERSION: (u32, u32, u32) = (8, 0, 29);
+// performance_schema.threads 只在 5.7 及以上版本使用
+const PS_THREADS_VERSION: (u32, u32, u32) = (5, 7, 0);
+// EXPLAIN FOR CONNECTION 从 5.7 开始支持, MariaDB 不支持
+const EXPLAIN_FOR_CONNECTION_VERSION: (u32, u32, u32) = (5, 7, 0);
+
+// 获取 processlist 的数据来源, 每个实例连接后根据版本确定
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcesslistSource {
+    InformationSchema,
+    PsProcesslist { has_execution_engine: bool },
+    PsThreads { has_execution_engine: bool },
+}
+
+impl ProcesslistSource {
+    // 根据 --processlist-source 和数据库版本选择数据来源, performance_schema 不可用时使用 information_schema.
+    // 同时返回数据库版本, 获取失败时为 None
+    pub async fn detect(
+        cfg: &ShowProcesslistConf,
+        db: &Pool<MySql>,
+        instance: &Instance,
+    ) -> (ProcesslistSource, Option<String>) {
+        let host_port = format!(
+            "{host}:{port}",
+            host = instance.machine_host.as_ref().unwrap(),
+            port = instance.port.unwrap()
+        );
+
+        let version = match NormalDao::get_version(db).await {
+            Ok(v) => v,
+            Err(e) => {
+                log::warn!(
+                    "{host_port}, 获取数据库版本失败, 使用 information_schema.PROCESSLIST. {e}",
+                    host_port = &host_port,
+                    e = e
+                );
+                return (ProcesslistSource::InformationSchema, None);
+            }
+        };
+        if cfg.processlist_source == PROCESSLIST_SOURCE_INFORMATION_SCHEMA {
+            return (ProcesslistSource::InformationSchema, Some(version));
+        }
+
+        let source = get_source(&cfg.processlist_source, &version);
+        if source == ProcesslistSource::InformationSchema {
+            if cfg.processlist_source != PROCESSLIST_SOURCE_AUTO {
+                log::warn!(
+                    "{host_port}, 数据库版本 {version} 不支持 {source}, 使用 information_schema.PROCESSLIST",
+                    host_port = &host_port,
+                    version = &version,
+                    source = &cfg.processlist_source
+                );
+            }
+            return (source, Some(version));
+        }
+
+        // 没有开启 performance_schema 或者没有权限时, 查询失败或者查不到当前链接
+        let source = match source.show_processlist(db).await {
+            Ok(infos) if !infos.is_empty() => {
+                log::info!(
+                    "{host_port}, 数据库版本: {version}, processlist 数据来源: {source}",
+                    host_port = &host_port,
+                    version = &version,
+                    source = source.name()
+                );
+                source
+            }
+            Ok(_) => {
+                log::warn!(
+                    "{host_port}, {source} 没有数据, 可能没有开启 performance_schema, 使用 information_schema.PROCESSLIST",
+                    host_port = &host_port,
+                    source = source.name()
+                );
+                ProcesslistSource::InformationSchema
+            }
+            Err(e) => {
+                log::warn!(
+                    "{host_port}, 查询 {source} 失败, 使用 information_schema.PROCESSLIST. {e}",
+                    host_port = &host_port,
+                    source = source.name(),
+                    e = e
+                );
+                ProcesslistSource::InformationSchema
+            }
+        };
+
+        (source, Some(version))
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProcesslistSource::InformationSchema => PROCESSLIST_SOURCE_INFORMATION_SCHEMA,
+            ProcesslistSource::PsProcesslist { .. } => PROCESSLIST_SOURCE_PS_PROCESSLIST,
+            ProcesslistSource::PsThreads { .. } => PROCESSLIST_SOURCE_PS_THREADS,
+        }
+    }
+
+    pub async fn show_processlist(
+        &self,
+        db: &Pool<MySql>,
+    ) -> Result<Vec<ShowProcesslistInfo>, Error> {
+        match *self {
+            ProcesslistSource::InformationSchema => NormalDao::show_processlist(db).await,
+            ProcesslistSource::PsProcesslist {
+                has_execution_engine,
+            } => NormalDao::show_processlist_ps(db, has_execution_engine).await,
+            ProcesslistSource::PsThreads {
+                has_execution_engine,
+            } => NormalDao::show_processlist_threads(db, has_execution_engine).await,
+        }
+    }
+}
+
+fn get_source(processlist_source: &str, version: &str) -> ProcesslistSource {
+    // MariaDB 没有 performance_schema.processlist, threads 的列也不一样
+    let version_num = match parse_version(version) {
+        Some(v) if !version.contains("MariaDB") => v,
+        _ => return ProcesslistSource::InformationSchema,
+    };
+    let has_execution_engine = version_num >= EXECUTION_ENGINE_VERSION;
+
+    let is_ps_processlist = match processlist_source {
+        PROCESSLIST_SOURCE_PS_PROCESSLIST => true,
+        PROCESSLIST_SOURCE_PS_THREADS => false,
+        _ => version_num >= PS_PROCESSLIST_VERSION,
+    };
+    if is_ps_processlist && version_num >= PS_PROCESSLIST_VERSION {
+        ProcesslistSource::PsProcesslist {
+            has_execution_engine,
+        }
+    } else if !is_ps_processlist && version_num >= PS_THREADS_VERSION {
+        ProcesslistSource::PsThreads {
+            has_execution_engine,
+        }
+    } else {
+        ProcesslistSource::InformationSchema
+    }
+}
+
+// 是否支持 EXPLAIN FOR CONNECTION
+pub fn is_explain_for_connection_supported(version: &str) -> bool {
+    match parse_version(version) {
+        Some(v) => !version.contains("MariaDB") && v >= EXPLAIN_FOR_CONNECTION_VERSION,
+        None => false,
+    }
+}
+
+// 解析版本号, 例如: 8.0.32-log -> (8, 0, 32)
+pub fn parse_version(version: &str) -> Option<(u32, u32, u32)> {
+    let mut nums = version
+        .split(|c: char| !c.is_ascii_digit())
+        .take(3)
+        .map(|v| v.parse::<u32>().ok());
+
+    Some((nums.next()??, nums.next()??, nums.next()??))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::show_processlist::source::{
+        get_source, is_explain_for_connection_supported, ProcesslistSource,
+    };
+
+    #[test]
+    fn test_get_source() {
+        assert_eq!(
+            get_source("auto", "8.0.32-log"),
+            ProcesslistSource::PsProcesslist {
+                has_execution_engine: true
+            }
+        );
+        assert_eq!(
+            get_source("auto", "8.0.25"),
+            ProcesslistSource::PsProcesslist {
+                has_execution_engine: false
+            }
+        );
+        assert_eq!(
+            get_source("auto", "8.0.18"),
+            ProcesslistSource::PsThreads {
+                has_execution_engine: false
+            }
+        );
+        assert_eq!(
+            get_source("performance_schema.threads", "8.0.32"),
+            ProcesslistSource::PsThreads {
+                has_execution_engine: true
+            }
+        );
+        assert_eq!(
+            get_source("performance_schema.processlist", "5.7.40-log"),
+            ProcesslistSource::InformationSchema
+        );
+        assert_eq!(
+            get_source("auto", "5.6.51"),
+            ProcesslistSource::InformationSchema
+        );
+        assert_eq!(
+            get_source("auto", "10.6.12-MariaDB"),
+            ProcesslistSource::InformationSchema
+        );
+
+        assert!(is_explain_for_connection_supported("5.7.40-log"));
+        assert!(!is_explain_for_connection_supported("5.6.51"));
+        assert!(!is_explain_for_connection_supported("10.6.12-MariaDB"));
+    }
+}
@@ -0,0 +1,202 @@
+use crate::models::{FingerprintSummary, GroupSummary, ShowProcesslistInfo};
+use crate::utils::fingerprint;
+use prettytable::{format, Cell, Row, Table};
+use std::collections::{BTreeSet, HashMap};
+
+// 按 sql 指纹聚合 processlist, 按数量倒序, 数量相同按最大执行时间倒序
+pub fn get_fingerprint_summaries(infos: &[ShowProcesslistInfo]) -> Vec<FingerprintSummary> {
+    struct Agg {
+        count: u64,
+        max_time: i64,
+        total_time: i64,
+        users: BTreeSet<String>,
+        dbs: BTreeSet<String>,
+    }
+
+    let mut agg_map = HashMap::<String, Agg>::new();
+    for info in infos.iter() {
+        let sql = match info.info.as_ref() {
+            Some(v) if !v.trim().is_empty() => v,
+            _ => continue,
+        };
+
+        let time = info.time.unwrap_or(0) as i64;
+        let agg = agg_map
+            .entry(fingerprint::fingerprint(sql))
+            .or_insert_with(|| Agg {
+                count: 0,
+                max_time: 0,
+                total_time: 0,
+                users: BTreeSet::new(),
+                dbs: BTreeSet::new(),
+            });
+        agg.count += 1;
+        agg.max_time = agg.max_time.max(time);
+        agg.total_time += time;
+        if let Some(user) = info.user.as_ref() {
+            agg.users.insert(user.clone());
+        }
+        if let Some(db) = info.db.as_ref() {
+            agg.dbs.insert(db.clone());
+        }
+    }
+
+    let mut summaries = agg_map
+        .into_iter()
+        .map(|(fingerprint, agg)| FingerprintSummary {
+            fingerprint,
+            count: agg.count,
+            max_time: agg.max_time,
+            avg_time: agg.total_time as f64 / agg.count as f64,
+            users: agg.users.into_iter().collect(),
+            dbs: agg.dbs.into_iter().collect(),
+        })
+        .collect::<Vec<FingerprintSummary>>();
+
+    summaries.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then(b.max_time.cmp(&a.max_time))
+            .then(a.fingerprint.cmp(&b.fingerprint))
+    });
+
+    summaries
+}
+
+pub fn get_fingerprint_summary_table(summaries: &[FingerprintSummary]) -> String {
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+    // 设置title
+    table.set_titles(Row::new(vec![
+        Cell::new("Count"),
+        Cell::new("Max Time"),
+        Cell::new("Avg Time"),
+        Cell::new("Users"),
+        Cell::new("Dbs"),
+        Cell::new("Fingerprint"),
+    ]));
+
+    for summary in summaries.iter() {
+        table.add_row(Row::new(vec![
+            Cell::new(&summary.count.to_string()),
+            Cell::new(&summary.max_time.to_string()),
+            Cell::new(&format!("{:.1}", summary.avg_time)),
+            Cell::new(&summary.users.join(",")),
+            Cell::new(&summary.dbs.join(",")),
+            Cell::new(&summary.fingerprint),
+        ]));
+    }
+
+    table.to_string()
+}
+
+type GroupValue = fn(&ShowProcesslistInfo) -> String;
+
+// 分组字段, 以及从 processlist 中获取分组值的方法
+const GROUP_BYS: [(&str, GroupValue); 5] = [
+    ("user", |info| info.user.clone().unwrap_or_default()),
+    ("client_ip", |info| {
+        get_client_ip(info.host.as_deref().unwrap_or(""))
+    }),
+    ("db", |info| info.db.clone().unwrap_or_default()),
+    ("command", |info| info.command.clone().unwrap_or_default()),
+    ("state", |info| info.state.clone().unwrap_or_default()),
+];
+
+// 按 user, client_ip, db, command, state 分组统计线程数, 每个分组按数量倒序最多保留 limit 个
+pub fn get_group_summaries(infos: &[ShowProcesslistInfo], limit: usize) -> Vec<GroupSummary> {
+    let mut summaries = Vec::<GroupSummary>::new();
+    for (group_by, get_value) in GROUP_BYS.iter() {
+        let mut count_map = HashMap::<String, u64>::new();
+        for info in infos.iter() {
+            *count_map.entry(get_value(info)).or_default() += 1;
+        }
+
+        let mut group = count_map
+            .into_iter()
+            .map(|(value, count)| GroupSummary {
+                group_by: group_by.to_string(),
+                value,
+                count,
+            })
+            .collect::<Vec<GroupSummary>>();
+        group.sort_by(|a, b| b.count.cmp(&a.count).then(a.value.cmp(&b.value)));
+        group.truncate(limit);
+
+        summaries.extend(group);
+    }
+
+    summaries
+}
+
+// HOST 去掉端口, 例如: 10.0.0.1:52341 -> 10.0.0.1
+fn get_client_ip(host: &str) -> String {
+    match host.rsplit_once(':') {
+        Some((ip, port)) if !port.is_empty() && port.chars().all(|c| c.is_ascii_digit()) => {
+            ip.to_string()
+        }
+        _ => host.to_string(),
+    }
+}
+
+pub fn get_group_summary_table(summaries: &[GroupSummary]) -> String {
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+    // 设置title
+    table.set_titles(Row::new(vec![
+        Cell::new("Group By"),
+        Cell::new("Value"),
+        Cell::new("Count"),
+    ]));
+
+    for summary in summaries.iter() {
+        table.add_row(Row::new(vec![
+            Cell::new(&summary.group_by),
+            Cell::new(&summary.value),
+            Cell::new(&summary.count.to_string()),
+        ]));
+    }
+
+    table.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::show_processlist::summary::{get_client_ip, get_group_summaries};
+    use crate::models::ShowProcesslistInfo;
+
+    #[test]
+    fn test_get_client_ip() {
+        assert_eq!(get_client_ip("10.0.0.1:52341"), "10.0.0.1");
+        assert_eq!(get_client_ip("localhost"), "localhost");
+        assert_eq!(get_client_ip(""), "");
+    }
+
+    #[test]
+    fn test_get_group_summaries() {
+        let infos = ["10.0.0.1:1000", "10.0.0.1:1001", "10.0.0.2:1000"]
+            .iter()
+            .map(|host| ShowProcesslistInfo {
+                id: Some(1),
+                user: Some(String::from("app")),
+                host: Some(host.to_string()),
+                db: Some(String::from("db1")),
+                command: Some(String::from("Sleep")),
+                time: Some(1),
+                state: Some(String::new()),
+                info: None,
+                ..Default::default()
+            })
+            .collect::<Vec<ShowProcesslistInfo>>();
+
+        let summaries = get_group_summaries(&infos, 10);
+        println!("{:?}", summaries);
+
+        let client_ips = summaries
+            .iter()
+            .filter(|summary| summary.group_by == "client_ip")
+            .map(|summary| (summary.value.as_str(), summary.count))
+            .collect::<Vec<(&str, u64)>>();
+        assert_eq!(client_ips, vec![("10.0.0.1", 2), ("10.0.0.2", 1)]);
+    }
+}
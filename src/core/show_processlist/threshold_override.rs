@@ -0,0 +1,165 @@
+use crate::config::show_processlist_conf::ShowProcesslistConf;
+use crate::error::CustomError;
+use crate::models::Instance;
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+// 阈值覆盖配置文件, json 格式. 例如:
+// {
+//   "overrides": [
+//     {"role": "slave", "print_cnt_threshold": 100},
+//     {"cluster_name": "pay_cluster", "print_cnt_threshold": 300, "sleep": 500},
+//     {"host_port": "10.0.0.1:3306", "print_cnt_threshold": 20}
+//   ]
+// }
+// 规则中指定的匹配条件都满足才生效, 按文件中的顺序依次覆盖, 后面的规则优先级更高
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct ThresholdOverrides {
+    #[serde(default)]
+    pub overrides: Vec<ThresholdOverride>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ThresholdOverride {
+    // 匹配条件
+    pub cluster_name: Option<String>,
+    pub business_line: Option<String>,
+    pub set_name: Option<String>,
+    pub role: Option<String>,
+    pub host_port: Option<String>,
+    // 覆盖的参数
+    pub print_cnt_threshold: Option<u64>,
+    pub sleep: Option<u64>,
+    pub alert_threshold: Option<u64>,
+}
+
+impl ThresholdOverrides {
+    // 没有指定 --threshold-override-file 返回空配置
+    pub fn load(cfg: &ShowProcesslistConf) -> Result<ThresholdOverrides, CustomError> {
+        if cfg.threshold_override_file.is_empty() {
+            return Ok(ThresholdOverrides::default());
+        }
+
+        let data = fs::read_to_string(&cfg.threshold_override_file).map_err(|e| {
+            CustomError::new(format!(
+                "读取阈值覆盖配置文件失败. 文件: {file}. {e}",
+                file = &cfg.threshold_override_file,
+                e = e
+            ))
+        })?;
+        let overrides = serde_json::from_str::<ThresholdOverrides>(&data).map_err(|e| {
+            CustomError::new(format!(
+                "解析阈值覆盖配置文件失败. 文件: {file}. {e}",
+                file = &cfg.threshold_override_file,
+                e = e
+            ))
+        })?;
+
+        for (i, item) in overrides.overrides.iter().enumerate() {
+            if !item.has_condition() {
+                return Err(CustomError::new(format!(
+                    "阈值覆盖配置文件第 {no} 条规则没有指定任何匹配条件. 文件: {file}",
+                    no = i + 1,
+                    file = &cfg.threshold_override_file
+                )));
+            }
+        }
+
+        Ok(overrides)
+    }
+
+    // 生成实例使用的配置
+    pub fn apply(&self, cfg: &ShowProcesslistConf, instance: &Instance) -> ShowProcesslistConf {
+        let mut instance_cfg = cfg.clone();
+        for item in self.overrides.iter().filter(|item| item.is_match(instance)) {
+            if let Some(v) = item.print_cnt_threshold {
+                instance_cfg.print_cnt_threshold = v;
+            }
+            if let Some(v) = item.sleep {
+                instance_cfg.sleep = v;
+            }
+            if let Some(v) = item.alert_threshold {
+                instance_cfg.alert_threshold = v;
+            }
+        }
+
+        instance_cfg
+    }
+}
+
+impl ThresholdOverride {
+    fn has_condition(&self) -> bool {
+        self.cluster_name.is_some()
+            || self.business_line.is_some()
+            || self.set_name.is_some()
+            || self.role.is_some()
+            || self.host_port.is_some()
+    }
+
+    fn is_match(&self, instance: &Instance) -> bool {
+        let is_match_field = |condition: &Option<String>, value: &Option<String>| match condition {
+            Some(condition) => value.as_ref() == Some(condition),
+            None => true,
+        };
+
+        let host_port = Some(format!(
+            "{host}:{port}",
+            host = instance.machine_host.as_deref().unwrap_or(""),
+            port = instance.port.unwrap_or(0)
+        ));
+
+        is_match_field(&self.cluster_name, &instance.cluster_name)
+            && is_match_field(&self.business_line, &instance.business_line)
+            && is_match_field(&self.set_name, &instance.set_name)
+            && is_match_field(&self.role, &instance.role)
+            && is_match_field(&self.host_port, &host_port)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::show_processlist_conf::parse_test_args;
+    use crate::core::show_processlist::threshold_override::ThresholdOverrides;
+    use crate::models::Instance;
+
+    #[test]
+    fn test_threshold_overrides_apply() {
+        let cfg = parse_test_args(&["--all"]);
+        let overrides = serde_json::from_str::<ThresholdOverrides>(
+            r#"
+{
+  "overrides": [
+    {"role": "slave", "print_cnt_threshold": 100},
+    {"cluster_name": "pay_cluster", "print_cnt_threshold": 300, "sleep": 500},
+    {"host_port": "10.0.0.1:3306", "print_cnt_threshold": 20}
+  ]
+}
+            "#,
+        )
+        .unwrap();
+
+        let get_instance = |host: &str, cluster_name: &str, role: &str| Instance {
+            machine_host: Some(host.to_string()),
+            port: Some(3306),
+            cluster_name: Some(cluster_name.to_string()),
+            role: Some(role.to_string()),
+            ..Default::default()
+        };
+
+        let instance_cfg = overrides.apply(&cfg, &get_instance("10.0.0.2", "test", "master"));
+        assert_eq!(instance_cfg.print_cnt_threshold, cfg.print_cnt_threshold);
+
+        let instance_cfg = overrides.apply(&cfg, &get_instance("10.0.0.2", "test", "slave"));
+        assert_eq!(instance_cfg.print_cnt_threshold, 100);
+
+        let instance_cfg = overrides.apply(&cfg, &get_instance("10.0.0.2", "pay_cluster", "slave"));
+        assert_eq!(instance_cfg.print_cnt_threshold, 300);
+        assert_eq!(instance_cfg.sleep, 500);
+
+        let instance_cfg =
+            overrides.apply(&cfg, &get_instance("10.0.0.1", "pay_cluster", "master"));
+        assert_eq!(instance_cfg.print_cnt_threshold, 20);
+        assert_eq!(instance_cfg.sleep, 500);
+    }
+}
@@ -0,0 +1,435 @@
+use crate::config::top_conf::TopConf;
+use crate::core::top::ui;
+use crate::core::top::view::{Mode, SortField, TopRow, TopView};
+use crate::dao::{InstanceDao, MetaClusterDao, NormalDao};
+use crate::error::CustomError;
+use crate::models::{Instance, ShowProcesslistInfo};
+use crate::rdbc;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::DefaultTerminal;
+use sqlx::{MySql, Pool};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+
+// 翻页时移动的行数
+const PAGE_SIZE: i64 = 10;
+// 读取终端事件的超时时间, 超时后检查界面是否已经退出
+const EVENT_POLL_TIMEOUT: Duration = Duration::from_millis(100);
+// 获取一个实例 processlist 的超时时间, 避免无法链接的实例导致界面卡住
+const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+
+// 一次刷新中一个实例的结果: 实例下标, 链接和 processlist
+type FetchResult = (
+    usize,
+    Result<(Pool<MySql>, Vec<ShowProcesslistInfo>), CustomError>,
+);
+
+// 需要查看的实例, 链接在第一次刷新时创建, 出错后关闭, 下一次刷新重新链接
+struct TopInstance {
+    host_port: String,
+    instance: Instance,
+    db: Option<Pool<MySql>>,
+    error: Option<String>,
+}
+
+pub async fn run(cfg: &TopConf) -> Result<(), CustomError> {
+    // 检测配置文件相关参数
+    cfg.check()?;
+    let mut view = TopView::new(&cfg.sort, &cfg.filter)?;
+
+    // 进入全屏前获取实例, 出错时直接输出到终端
+    let mut instances = find_instances(cfg)
+        .await?
+        .into_iter()
+        .map(|instance| TopInstance {
+            host_port: format!(
+                "{host}:{port}",
+                host = instance.machine_host.as_ref().unwrap(),
+                port = instance.port.unwrap()
+            ),
+            instance,
+            db: None,
+            error: None,
+        })
+        .collect::<Vec<TopInstance>>();
+
+    let mut terminal =
+        ratatui::try_init().map_err(|e| CustomError::new(format!("初始化终端失败. {e}", e = e)))?;
+    let result = run_loop(cfg, &mut terminal, &mut view, &mut instances).await;
+    ratatui::restore();
+
+    // 关闭数据库链接
+    for instance in instances.iter() {
+        if let Some(db) = &instance.db {
+            db.close().await;
+        }
+    }
+
+    result
+}
+
+async fn run_loop(
+    cfg: &TopConf,
+    terminal: &mut DefaultTerminal,
+    view: &mut TopView,
+    instances: &mut [TopInstance],
+) -> Result<(), CustomError> {
+    let mut events = spawn_event_reader();
+    let mut interval = tokio::time::interval(Duration::from_millis(cfg.refresh));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    // 获取 processlist 在单独的任务中执行, 等待结果期间界面可以继续响应按键
+    let (fetch_tx, mut fetch_rx) = mpsc::channel::<Vec<FetchResult>>(1);
+    let mut is_fetching = false;
+
+    loop {
+        let errors = instances
+            .iter()
+            .filter_map(|instance| {
+                instance.error.as_ref().map(|e| {
+                    format!(
+                        "{host_port}, 获取 processlist 失败. {e}",
+                        host_port = &instance.host_port,
+                        e = e
+                    )
+                })
+            })
+            .collect::<Vec<String>>();
+        terminal
+            .draw(|frame| ui::draw(frame, view, instances.len(), &errors))
+            .map_err(|e| CustomError::new(format!("绘制界面失败. {e}", e = e)))?;
+
+        tokio::select! {
+            _ = interval.tick() => {
+                // 上一次刷新还没有返回时跳过
+                if !view.paused && !is_fetching {
+                    is_fetching = true;
+                    spawn_refresh(cfg, instances, fetch_tx.clone());
+                }
+            }
+            Some(results) = fetch_rx.recv() => {
+                is_fetching = false;
+                apply_refresh(instances, view, results);
+            }
+            event = events.recv() => match event {
+                Some(Event::Key(key)) if key.kind == KeyEventKind::Press => {
+                    if !handle_key(key, view, instances).await {
+                        return Ok(());
+                    }
+                }
+                // 其他事件(例如调整窗口大小)只需要重新绘制
+                Some(_) => {}
+                None => return Ok(()),
+            },
+        }
+    }
+}
+
+// 终端事件是阻塞读取, 在单独的线程中读取后发送给界面
+fn spawn_event_reader() -> mpsc::Receiver<Event> {
+    let (tx, rx) = mpsc::channel::<Event>(100);
+    std::thread::spawn(move || loop {
+        match event::poll(EVENT_POLL_TIMEOUT) {
+            Ok(true) => match event::read() {
+                Ok(event) => {
+                    if tx.blocking_send(event).is_err() {
+                        break;
+                    }
+                }
+                Err(_) => break,
+            },
+            // 界面已经退出
+            Ok(false) if tx.is_closed() => break,
+            Ok(false) => {}
+            Err(_) => break,
+        }
+    });
+
+    rx
+}
+
+// 在单独的任务中并发获取所有实例的 processlist, 完成后通过 tx 发送结果.
+// 链接池可以共享, 任务中使用链接的副本, 刷新期间仍然可以使用原来的链接执行 KILL QUERY
+fn spawn_refresh(cfg: &TopConf, instances: &[TopInstance], tx: mpsc::Sender<Vec<FetchResult>>) {
+    let fetches = instances
+        .iter()
+        .enumerate()
+        .map(|(idx, instance)| (idx, instance.instance.clone(), instance.db.clone()))
+        .collect::<Vec<(usize, Instance, Option<Pool<MySql>>)>>();
+    let cfg = cfg.clone();
+
+    tokio::spawn(async move {
+        let mut tasks = JoinSet::new();
+        for (idx, instance, db) in fetches.into_iter() {
+            let cfg = cfg.clone();
+            tasks.spawn(async move {
+                let result =
+                    tokio::time::timeout(FETCH_TIMEOUT, get_processlist(&cfg, &instance, db))
+                        .await
+                        .unwrap_or_else(|_| {
+                            Err(CustomError::new(format!(
+                                "超过 {timeout}s 没有返回",
+                                timeout = FETCH_TIMEOUT.as_secs()
+                            )))
+                        });
+                (idx, result)
+            });
+        }
+
+        let mut results = Vec::<FetchResult>::new();
+        while let Some(result) = tasks.join_next().await {
+            if let Ok(v) = result {
+                results.push(v);
+            }
+        }
+        // 界面已经退出时不需要结果
+        let _ = tx.send(results).await;
+    });
+}
+
+// 使用刷新结果更新实例链接, 错误信息和界面中的行
+fn apply_refresh(instances: &mut [TopInstance], view: &mut TopView, results: Vec<FetchResult>) {
+    let mut rows = Vec::<TopRow>::new();
+    for (idx, result) in results.into_iter() {
+        let instance = &mut instances[idx];
+        match result {
+            Ok((db, infos)) => {
+                instance.db = Some(db);
+                instance.error = None;
+                rows.extend(infos.into_iter().map(|info| TopRow {
+                    host_port: instance.host_port.clone(),
+                    info,
+                }));
+            }
+            Err(e) => {
+                instance.db = None;
+                instance.error = Some(e.to_string());
+            }
+        }
+    }
+
+    view.set_rows(rows);
+}
+
+// 没有链接时先创建链接, 执行失败关闭链接
+async fn get_processlist(
+    cfg: &TopConf,
+    instance: &Instance,
+    db: Option<Pool<MySql>>,
+) -> Result<(Pool<MySql>, Vec<ShowProcesslistInfo>), CustomError> {
+    let db = match db {
+        Some(v) => v,
+        None => {
+            let password = cfg.get_password();
+            rdbc::get_db_by_default(
+                instance.machine_host.as_ref().unwrap(),
+                instance.port.unwrap() as i16,
+                &cfg.username,
+                &password,
+                "",
+                false,
+            )
+            .await
+            .map_err(|e| CustomError::new(format!("创建实例链接出错. {e}", e = e)))?
+        }
+    };
+
+    match NormalDao::show_processlist(&db).await {
+        Ok(infos) => Ok((db, infos)),
+        Err(e) => {
+            db.close().await;
+            Err(CustomError::new(e.to_string()))
+        }
+    }
+}
+
+// 处理按键, 返回 false 退出界面
+async fn handle_key(key: KeyEvent, view: &mut TopView, instances: &[TopInstance]) -> bool {
+    // 全屏模式下 Ctrl-C 不会产生 SIGINT
+    if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+        return false;
+    }
+
+    match view.mode.clone() {
+        Mode::Filter(mut input) => match key.code {
+            KeyCode::Enter => {
+                view.mode = Mode::Normal;
+                if let Err(e) = view.set_filter(&input) {
+                    view.message =
+                        format!("过滤表达式不合法, 继续使用原来的过滤表达式. {e}", e = e);
+                }
+            }
+            KeyCode::Esc => view.mode = Mode::Normal,
+            KeyCode::Backspace => {
+                input.pop();
+                view.mode = Mode::Filter(input);
+            }
+            KeyCode::Char(c) => {
+                input.push(c);
+                view.mode = Mode::Filter(input);
+            }
+            _ => {}
+        },
+        Mode::Detail(_) => {
+            if matches!(key.code, KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q')) {
+                view.mode = Mode::Normal;
+            }
+        }
+        Mode::ConfirmKill(row) => {
+            view.mode = Mode::Normal;
+            view.message = if key.code == KeyCode::Char('y') {
+                kill_query(instances, &row).await
+            } else {
+                String::from("已取消 KILL QUERY")
+            };
+        }
+        Mode::Normal => {
+            view.message.clear();
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return false,
+                KeyCode::Up => view.move_selection(-1),
+                KeyCode::Down => view.move_selection(1),
+                KeyCode::PageUp => view.move_selection(-PAGE_SIZE),
+                KeyCode::PageDown => view.move_selection(PAGE_SIZE),
+                KeyCode::Home => view.move_selection(i64::MIN / 2),
+                KeyCode::End => view.move_selection(i64::MAX / 2),
+                KeyCode::Char('t') => view.set_sort(SortField::Time),
+                KeyCode::Char('u') => view.set_sort(SortField::User),
+                KeyCode::Char('d') => view.set_sort(SortField::Db),
+                KeyCode::Char('/') => view.mode = Mode::Filter(view.filter_text.clone()),
+                KeyCode::Char('p') | KeyCode::Char(' ') => view.paused = !view.paused,
+                KeyCode::Enter => {
+                    if let Some(row) = view.selected_row() {
+                        view.mode = Mode::Detail(row.clone());
+                    }
+                }
+                KeyCode::Char('k') => {
+                    if let Some(row) = view.selected_row() {
+                        view.mode = Mode::ConfirmKill(row.clone());
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    true
+}
+
+// 执行 KILL QUERY, 返回需要显示在状态栏的结果
+async fn kill_query(instances: &[TopInstance], row: &TopRow) -> String {
+    let id = match row.info.id {
+        Some(v) => v,
+        None => return String::from("线程 id 为空, 不能执行 KILL QUERY"),
+    };
+    let db = match instances
+        .iter()
+        .find(|instance| instance.host_port == row.host_port)
+        .and_then(|instance| instance.db.as_ref())
+    {
+        Some(v) => v,
+        None => {
+            return format!(
+                "{host_port} 没有可用的链接, 不能执行 KILL QUERY",
+                host_port = &row.host_port
+            )
+        }
+    };
+
+    // 界面上的行可能已经过期, 线程还在执行同一个用户的同一个 sql 时才 kill
+    let current = match NormalDao::get_processlist_by_id(db, id).await {
+        Ok(v) => v,
+        Err(e) => {
+            return format!(
+                "{host_port}, 获取线程 {id} 信息失败, 不执行 KILL QUERY. {e}",
+                host_port = &row.host_port,
+                id = id,
+                e = e
+            )
+        }
+    };
+    match current {
+        Some(info) if info.user == row.info.user && info.info == row.info.info => {}
+        Some(_) => {
+            return format!(
+                "{host_port}, 线程 {id} 已经在执行其他 sql, 不执行 KILL QUERY",
+                host_port = &row.host_port,
+                id = id
+            )
+        }
+        None => {
+            return format!(
+                "{host_port}, 线程 {id} 已经结束, 不执行 KILL QUERY",
+                host_port = &row.host_port,
+                id = id
+            )
+        }
+    }
+
+    match NormalDao::kill(db, id, true).await {
+        Ok(_) => format!(
+            "{host_port}, KILL QUERY {id} 成功",
+            host_port = &row.host_port,
+            id = id
+        ),
+        Err(e) => format!(
+            "{host_port}, KILL QUERY {id} 失败. {e}",
+            host_port = &row.host_port,
+            id = id,
+            e = e
+        ),
+    }
+}
+
+// 获取需要查看的实例, 指定 --host --port 时只有一个实例, 否则为 vip_port 对应集群的所有实例
+async fn find_instances(cfg: &TopConf) -> Result<Vec<Instance>, CustomError> {
+    if cfg.have_host_port() {
+        return Ok(vec![Instance {
+            machine_host: Some(cfg.host.clone()),
+            port: Some(cfg.port as i32),
+            ..Default::default()
+        }]);
+    }
+
+    let easydb = rdbc::get_db(&cfg.get_easydb_dsn(), false)
+        .await
+        .map_err(|e| CustomError::new(format!("获取easydb数据库链接出错. {e}", e = e)))?;
+    let instances = find_instances_op(cfg, &easydb).await;
+    let _ = easydb.close().await;
+
+    instances
+}
+
+async fn find_instances_op(
+    cfg: &TopConf,
+    easydb: &Pool<MySql>,
+) -> Result<Vec<Instance>, CustomError> {
+    let cluster = MetaClusterDao::get_by_vip_port(easydb, &cfg.vip_port)
+        .await
+        .map_err(|e| {
+            CustomError::new(format!(
+                "通过vip_port 获取集群信息失败. vip_port: {vip_port}, {e}",
+                vip_port = &cfg.vip_port,
+                e = e
+            ))
+        })?;
+
+    let instances = InstanceDao::find_by_meta_cluster_id(easydb, cluster.id.unwrap())
+        .await
+        .map_err(|e| {
+            CustomError::new(format!(
+                "通过集群id获取实例信息失败. meta_cluster_id: {meta_cluster_id}, vip_port: {vip_port}. {e}",
+                meta_cluster_id = cluster.id.unwrap(),
+                vip_port = &cfg.vip_port,
+                e = e
+            ))
+        })?;
+    if instances.is_empty() {
+        return Err(CustomError::new(format!(
+            "vip_port: {vip_port} 对应的集群没有实例",
+            vip_port = &cfg.vip_port
+        )));
+    }
+
+    Ok(instances)
+}
@@ -0,0 +1,5 @@
+pub mod handler;
+pub mod ui;
+pub mod view;
+
+pub use handler::run;
@@ -0,0 +1,186 @@
+use crate::core::top::view::{Mode, TopRow, TopView};
+use crate::utils;
+use ratatui::layout::{Constraint, Flex, Layout, Rect};
+use ratatui::style::{Modifier, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Cell, Clear, Paragraph, Row, Table, TableState, Wrap};
+use ratatui::Frame;
+
+const HELP: &str =
+    "q:退出 ↑↓:选择 t/u/d:按 time/user/db 排序 /:过滤 p:暂停 Enter:完整sql k:KILL QUERY";
+
+// 绘制整个界面: 标题栏, 线程列表, 状态栏, 以及弹出框
+// errors 为获取 processlist 失败的实例信息, 没有其他提示信息时显示在状态栏
+pub fn draw(frame: &mut Frame, view: &TopView, instance_cnt: usize, errors: &[String]) {
+    let [header_area, table_area, status_area, help_area] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Min(0),
+        Constraint::Length(1),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    frame.render_widget(
+        Paragraph::new(get_header(view, instance_cnt, errors.len())).reversed(),
+        header_area,
+    );
+    draw_table(frame, view, instance_cnt > 1, table_area);
+
+    let status = match &view.mode {
+        Mode::Filter(input) => format!("过滤表达式(Enter 确认, Esc 取消): {input}_"),
+        _ if !view.message.is_empty() => view.message.clone(),
+        _ => errors.first().cloned().unwrap_or_default(),
+    };
+    frame.render_widget(Paragraph::new(status), status_area);
+    frame.render_widget(Paragraph::new(HELP).dim(), help_area);
+
+    match &view.mode {
+        Mode::Detail(row) => draw_detail(frame, row),
+        Mode::ConfirmKill(row) => draw_confirm_kill(frame, row),
+        _ => {}
+    }
+}
+
+fn get_header(view: &TopView, instance_cnt: usize, error_cnt: usize) -> String {
+    format!(
+        "mysql-tool-rs top - {time} - 实例: {instance_cnt}(失败: {error_cnt}) - 线程: {visible}/{total} - 排序: {sort} {order} - 过滤: {filter}{paused}",
+        time = utils::time::now_str(utils::time::NORMAL_FMT),
+        instance_cnt = instance_cnt,
+        error_cnt = error_cnt,
+        visible = view.visible_rows().len(),
+        total = view.total(),
+        sort = view.sort.name(),
+        order = if view.desc { "desc" } else { "asc" },
+        filter = if view.filter_text.is_empty() {
+            "无"
+        } else {
+            &view.filter_text
+        },
+        paused = if view.paused { " - [已暂停]" } else { "" },
+    )
+}
+
+fn draw_table(frame: &mut Frame, view: &TopView, show_instance: bool, area: Rect) {
+    let mut titles = vec![
+        "Id", "User", "Host", "db", "Command", "Time", "State", "Info",
+    ];
+    let mut widths = vec![
+        Constraint::Length(10),
+        Constraint::Length(16),
+        Constraint::Length(22),
+        Constraint::Length(16),
+        Constraint::Length(12),
+        Constraint::Length(7),
+        Constraint::Length(24),
+        Constraint::Min(20),
+    ];
+    if show_instance {
+        titles.insert(0, "Instance");
+        widths.insert(0, Constraint::Length(21));
+    }
+
+    let rows = view.visible_rows().iter().map(|row| {
+        let info = &row.info;
+        let mut cells = vec![
+            info.id.map(|v| v.to_string()).unwrap_or_default(),
+            info.user.clone().unwrap_or_default(),
+            info.host.clone().unwrap_or_default(),
+            info.db.clone().unwrap_or_default(),
+            info.command.clone().unwrap_or_default(),
+            info.time.map(|v| v.to_string()).unwrap_or_default(),
+            info.state.clone().unwrap_or_default(),
+            // 列表中只显示一行
+            info.info
+                .as_deref()
+                .unwrap_or_default()
+                .split_whitespace()
+                .collect::<Vec<&str>>()
+                .join(" "),
+        ];
+        if show_instance {
+            cells.insert(0, row.host_port.clone());
+        }
+
+        Row::new(cells.into_iter().map(Cell::from))
+    });
+
+    let table = Table::new(rows, widths)
+        .header(Row::new(titles).bold().underlined())
+        .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+    let mut state = TableState::default();
+    if !view.visible_rows().is_empty() {
+        state.select(Some(view.selected));
+    }
+    frame.render_stateful_widget(table, area, &mut state);
+}
+
+fn draw_detail(frame: &mut Frame, row: &TopRow) {
+    let info = &row.info;
+    let lines = vec![
+        Line::from(format!(
+            "Instance: {host_port}  Id: {id}  User: {user}  Host: {host}  db: {db}",
+            host_port = &row.host_port,
+            id = info.id.unwrap_or_default(),
+            user = info.user.as_deref().unwrap_or_default(),
+            host = info.host.as_deref().unwrap_or_default(),
+            db = info.db.as_deref().unwrap_or_default(),
+        )),
+        Line::from(format!(
+            "Command: {command}  Time: {time}  State: {state}",
+            command = info.command.as_deref().unwrap_or_default(),
+            time = info.time.unwrap_or_default(),
+            state = info.state.as_deref().unwrap_or_default(),
+        )),
+        Line::from(""),
+    ]
+    .into_iter()
+    .chain(
+        info.info
+            .as_deref()
+            .unwrap_or_default()
+            .lines()
+            .map(|line| Line::from(line.to_string())),
+    )
+    .collect::<Vec<Line>>();
+
+    let area = get_popup_area(frame.area(), 80, 60);
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Paragraph::new(lines)
+            .wrap(Wrap { trim: false })
+            .block(Block::bordered().title(" 完整 sql (Esc/Enter 关闭) ")),
+        area,
+    );
+}
+
+fn draw_confirm_kill(frame: &mut Frame, row: &TopRow) {
+    let text = format!(
+        "确认在 {host_port} 执行 KILL QUERY {id} ?\nUser: {user}  Time: {time}\n{info}\n\n(y 确认, 其他键取消)",
+        host_port = &row.host_port,
+        id = row.info.id.unwrap_or_default(),
+        user = row.info.user.as_deref().unwrap_or_default(),
+        time = row.info.time.unwrap_or_default(),
+        info = row.info.info.as_deref().unwrap_or_default(),
+    );
+
+    let area = get_popup_area(frame.area(), 60, 30);
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Paragraph::new(text)
+            .wrap(Wrap { trim: false })
+            .block(Block::bordered().title(" KILL QUERY ").red()),
+        area,
+    );
+}
+
+// 居中的弹出框, 宽高为百分比
+fn get_popup_area(area: Rect, percent_x: u16, percent_y: u16) -> Rect {
+    let [area] = Layout::vertical([Constraint::Percentage(percent_y)])
+        .flex(Flex::Center)
+        .areas(area);
+    let [area] = Layout::horizontal([Constraint::Percentage(percent_x)])
+        .flex(Flex::Center)
+        .areas(area);
+
+    area
+}
@@ -0,0 +1,231 @@
+use crate::config::top_conf::{SORT_DB, SORT_USER};
+use crate::core::show_processlist::filter::FilterExpr;
+use crate::error::CustomError;
+use crate::models::ShowProcesslistInfo;
+use std::cmp::Ordering;
+
+// 一个线程, 多个实例时通过 host_port 区分
+#[derive(Debug, Clone)]
+pub struct TopRow {
+    pub host_port: String,
+    pub info: ShowProcesslistInfo,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortField {
+    Time,
+    User,
+    Db,
+}
+
+impl SortField {
+    pub fn from_name(name: &str) -> SortField {
+        match name {
+            SORT_USER => SortField::User,
+            SORT_DB => SortField::Db,
+            _ => SortField::Time,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SortField::Time => "time",
+            SortField::User => "user",
+            SortField::Db => "db",
+        }
+    }
+}
+
+// 界面当前的交互模式
+#[derive(Debug, Clone)]
+pub enum Mode {
+    Normal,
+    Filter(String), // 正在输入过滤表达式
+    Detail(TopRow), // 查看完整 sql
+    ConfirmKill(TopRow),
+}
+
+// top 界面的状态: 所有线程, 排序, 过滤, 选中的线程. 不包含任何终端相关的逻辑
+pub struct TopView {
+    rows: Vec<TopRow>,
+    visible: Vec<TopRow>, // 过滤并排序后需要显示的线程
+    pub sort: SortField,
+    pub desc: bool,
+    pub filter_text: String,
+    filter: Option<FilterExpr>,
+    pub selected: usize,
+    pub paused: bool,
+    pub mode: Mode,
+    pub message: String, // 状态栏信息, 例如 kill 结果, 链接失败
+}
+
+impl TopView {
+    pub fn new(sort: &str, filter_text: &str) -> Result<TopView, CustomError> {
+        let sort = SortField::from_name(sort);
+        let mut view = TopView {
+            rows: Vec::new(),
+            visible: Vec::new(),
+            sort,
+            desc: sort == SortField::Time,
+            filter_text: String::new(),
+            filter: None,
+            selected: 0,
+            paused: false,
+            mode: Mode::Normal,
+            message: String::new(),
+        };
+        view.set_filter(filter_text)?;
+
+        Ok(view)
+    }
+
+    pub fn total(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn visible_rows(&self) -> &[TopRow] {
+        &self.visible
+    }
+
+    pub fn selected_row(&self) -> Option<&TopRow> {
+        self.visible.get(self.selected)
+    }
+
+    // 刷新后尽量保持选中同一个线程
+    pub fn set_rows(&mut self, rows: Vec<TopRow>) {
+        self.rows = rows;
+        self.refresh_visible();
+    }
+
+    // 空字符串表示不过滤, 表达式不合法时返回错误, 保留原来的过滤表达式
+    pub fn set_filter(&mut self, filter_text: &str) -> Result<(), CustomError> {
+        let filter_text = filter_text.trim();
+        self.filter = if filter_text.is_empty() {
+            None
+        } else {
+            Some(FilterExpr::parse(filter_text)?)
+        };
+        self.filter_text = filter_text.to_string();
+        self.refresh_visible();
+
+        Ok(())
+    }
+
+    // 选择同一个排序字段时反向排序, time 默认倒序, user db 默认正序
+    pub fn set_sort(&mut self, sort: SortField) {
+        if self.sort == sort {
+            self.desc = !self.desc;
+        } else {
+            self.sort = sort;
+            self.desc = sort == SortField::Time;
+        }
+        self.refresh_visible();
+    }
+
+    pub fn move_selection(&mut self, offset: i64) {
+        if self.visible.is_empty() {
+            self.selected = 0;
+            return;
+        }
+
+        let max = self.visible.len() as i64 - 1;
+        self.selected = (self.selected as i64 + offset).clamp(0, max) as usize;
+    }
+
+    fn refresh_visible(&mut self) {
+        let selected_key = self.selected_row().map(get_row_key);
+
+        let mut visible = self
+            .rows
+            .iter()
+            .filter(|row| match &self.filter {
+                Some(filter) => filter.is_match(&row.info),
+                None => true,
+            })
+            .cloned()
+            .collect::<Vec<TopRow>>();
+        visible.sort_by(|a, b| {
+            let ordering = compare_rows(self.sort, a, b);
+            if self.desc {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+        self.visible = visible;
+
+        self.selected = selected_key
+            .and_then(|key| self.visible.iter().position(|row| get_row_key(row) == key))
+            .unwrap_or(self.selected);
+        self.move_selection(0);
+    }
+}
+
+fn get_row_key(row: &TopRow) -> (String, Option<u64>) {
+    (row.host_port.clone(), row.info.id)
+}
+
+// 排序字段相同时按 time 倒序, 再按 id 排序, 保证刷新后顺序稳定
+fn compare_rows(sort: SortField, a: &TopRow, b: &TopRow) -> Ordering {
+    let ordering = match sort {
+        SortField::Time => a.info.time.cmp(&b.info.time),
+        SortField::User => a.info.user.cmp(&b.info.user),
+        SortField::Db => a.info.db.cmp(&b.info.db),
+    };
+
+    ordering
+        .then_with(|| b.info.time.cmp(&a.info.time))
+        .then_with(|| a.host_port.cmp(&b.host_port))
+        .then_with(|| a.info.id.cmp(&b.info.id))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::top::view::{SortField, TopRow, TopView};
+    use crate::models::ShowProcesslistInfo;
+
+    fn get_row(id: u64, user: &str, command: &str, time: i32) -> TopRow {
+        TopRow {
+            host_port: String::from("127.0.0.1:3306"),
+            info: ShowProcesslistInfo {
+                id: Some(id),
+                user: Some(user.to_string()),
+                command: Some(command.to_string()),
+                time: Some(time),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_top_view() {
+        let mut view = TopView::new("time", "command != 'Sleep'").unwrap();
+        view.set_rows(vec![
+            get_row(1, "b", "Query", 5),
+            get_row(2, "a", "Query", 10),
+            get_row(3, "c", "Sleep", 100),
+        ]);
+        let ids = |view: &TopView| {
+            view.visible_rows()
+                .iter()
+                .map(|row| row.info.id.unwrap())
+                .collect::<Vec<u64>>()
+        };
+        assert_eq!(ids(&view), vec![2, 1]);
+
+        // 刷新后保持选中同一个线程
+        view.move_selection(1);
+        view.set_sort(SortField::User);
+        assert_eq!(ids(&view), vec![2, 1]);
+        view.set_sort(SortField::User);
+        assert_eq!(ids(&view), vec![1, 2]);
+        assert_eq!(view.selected_row().unwrap().info.id, Some(1));
+
+        assert!(view.set_filter("command = ").is_err());
+        assert_eq!(view.filter_text, "command != 'Sleep'");
+        view.set_filter("").unwrap();
+        assert_eq!(view.total(), 3);
+        assert_eq!(ids(&view).len(), 3);
+        println!("{:?}", ids(&view));
+    }
+}
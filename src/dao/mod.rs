@@ -1,7 +1,9 @@
 pub mod instance_dao;
 pub mod meta_cluster_dao;
 pub mod normal_dao;
+pub mod processlist_history_dao;
 
 pub use instance_dao::InstanceDao;
 pub use meta_cluster_dao::MetaClusterDao;
 pub use normal_dao::NormalDao;
+pub use processlist_history_dao::{HistoryDb, ProcesslistHistoryDao, ProcesslistHistoryFilter};
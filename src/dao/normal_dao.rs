@@ -1,6 +1,11 @@
 use crate::models::show_index_info::ShowIndexInfo8;
-use crate::models::{ShowIndexInfo, ShowProcesslistInfo};
-use sqlx::{Error, MySql, Pool};
+use crate::models::{
+    ConnectionStats, DynamicRows, InnodbTrxInfo, LockWaitEdge, ShowIndexInfo, ShowProcesslistInfo,
+};
+use chrono::NaiveDateTime;
+use rust_decimal::Decimal;
+use sqlx::mysql::MySqlRow;
+use sqlx::{Column, Error, Executor, MySql, Pool, Row};
 
 pub struct NormalDao;
 
@@ -25,6 +30,239 @@ FROM information_schema.PROCESSLIST;
             .await
     }
 
+    // 获取 processlist 信息, MySQL 8.0.22 开始使用 performance_schema.processlist, 不需要获取全局锁.
+    // 关联 threads 和 events_statements_current 获取 THREAD_ID 和正在执行的 sql 的 DIGEST
+    pub async fn show_processlist_ps(
+        pool: &Pool<MySql>,
+        has_execution_engine: bool,
+    ) -> Result<Vec<ShowProcesslistInfo>, Error> {
+        let query = format!(
+            r#"
+SELECT
+ p.ID
+ , p.USER
+ , p.HOST
+ , p.DB
+ , p.COMMAND
+ , p.TIME
+ , p.STATE
+ , p.INFO
+ , t.THREAD_ID
+ , s.DIGEST{execution_engine}
+FROM performance_schema.processlist AS p
+LEFT JOIN performance_schema.threads AS t ON t.PROCESSLIST_ID = p.ID
+LEFT JOIN performance_schema.events_statements_current AS s
+ ON s.THREAD_ID = t.THREAD_ID AND s.NESTING_EVENT_LEVEL = 0 AND s.END_EVENT_ID IS NULL;
+    "#,
+            execution_engine = if has_execution_engine {
+                "\n , CAST(p.EXECUTION_ENGINE AS CHAR) AS EXECUTION_ENGINE"
+            } else {
+                ""
+            }
+        );
+
+        sqlx::query_as::<_, ShowProcesslistInfo>(&query)
+            .fetch_all(pool)
+            .await
+    }
+
+    // 获取 processlist 信息, 使用 performance_schema.threads, MySQL 5.7 及 8.0.22 之前的版本使用.
+    // threads 中的 PROCESSLIST_HOST 不包含客户端端口
+    pub async fn show_processlist_threads(
+        pool: &Pool<MySql>,
+        has_execution_engine: bool,
+    ) -> Result<Vec<ShowProcesslistInfo>, Error> {
+        let query = format!(
+            r#"
+SELECT
+ t.PROCESSLIST_ID AS ID
+ , t.PROCESSLIST_USER AS USER
+ , t.PROCESSLIST_HOST AS HOST
+ , t.PROCESSLIST_DB AS DB
+ , t.PROCESSLIST_COMMAND AS COMMAND
+ , t.PROCESSLIST_TIME AS TIME
+ , t.PROCESSLIST_STATE AS STATE
+ , t.PROCESSLIST_INFO AS INFO
+ , t.THREAD_ID
+ , s.DIGEST{execution_engine}
+FROM performance_schema.threads AS t
+LEFT JOIN performance_schema.events_statements_current AS s
+ ON s.THREAD_ID = t.THREAD_ID AND s.NESTING_EVENT_LEVEL = 0 AND s.END_EVENT_ID IS NULL
+WHERE t.PROCESSLIST_ID IS NOT NULL;
+    "#,
+            execution_engine = if has_execution_engine {
+                "\n , CAST(t.EXECUTION_ENGINE AS CHAR) AS EXECUTION_ENGINE"
+            } else {
+                ""
+            }
+        );
+
+        sqlx::query_as::<_, ShowProcesslistInfo>(&query)
+            .fetch_all(pool)
+            .await
+    }
+
+    // 获取指定线程 id 的 processlist 信息, 线程已经结束返回 None
+    pub async fn get_processlist_by_id(
+        pool: &Pool<MySql>,
+        id: u64,
+    ) -> Result<Option<ShowProcesslistInfo>, Error> {
+        let query = r#"
+SELECT
+ ID
+ , USER
+ , HOST
+ , DB
+ , COMMAND
+ , TIME
+ , STATE
+ , INFO
+FROM information_schema.PROCESSLIST
+WHERE ID = ?;
+    "#;
+
+        sqlx::query_as::<_, ShowProcesslistInfo>(query)
+            .bind(id)
+            .fetch_optional(pool)
+            .await
+    }
+
+    // kill 线程, is_query: true 执行 KILL QUERY, false 执行 KILL
+    pub async fn kill(pool: &Pool<MySql>, id: u64, is_query: bool) -> Result<(), Error> {
+        let query = if is_query {
+            format!("KILL QUERY {id}", id = id)
+        } else {
+            format!("KILL {id}", id = id)
+        };
+
+        sqlx::query(&query).execute(pool).await.map(|_| ())
+    }
+
+    // 获取当前链接的线程 id
+    pub async fn get_connection_id(pool: &Pool<MySql>) -> Result<u64, Error> {
+        sqlx::query_scalar::<_, u64>("SELECT CONNECTION_ID()")
+            .fetch_one(pool)
+            .await
+    }
+
+    // 切换默认数据库, USE 不支持 prepared statement, 使用文本协议执行
+    pub async fn use_database(pool: &Pool<MySql>, database: &str) -> Result<(), Error> {
+        let query = format!("USE `{database}`", database = database.replace('`', "``"));
+        pool.execute(query.as_str()).await.map(|_| ())
+    }
+
+    // 获取数据库版本
+    pub async fn get_version(pool: &Pool<MySql>) -> Result<String, Error> {
+        sqlx::query_scalar::<_, String>("SELECT VERSION()")
+            .fetch_one(pool)
+            .await
+    }
+
+    // 获取锁等待关系, MySQL 5.7 使用 information_schema.INNODB_LOCK_WAITS
+    pub async fn lock_wait_edges_57(pool: &Pool<MySql>) -> Result<Vec<LockWaitEdge>, Error> {
+        let query = r#"
+SELECT DISTINCT
+ r.trx_mysql_thread_id AS waiting_thread_id
+ , b.trx_mysql_thread_id AS blocking_thread_id
+FROM information_schema.INNODB_LOCK_WAITS AS w
+INNER JOIN information_schema.INNODB_TRX AS b ON b.trx_id = w.blocking_trx_id
+INNER JOIN information_schema.INNODB_TRX AS r ON r.trx_id = w.requesting_trx_id;
+    "#;
+
+        sqlx::query_as::<_, LockWaitEdge>(query)
+            .fetch_all(pool)
+            .await
+    }
+
+    // 获取锁等待关系, MySQL 8.0 使用 performance_schema.data_lock_waits
+    pub async fn lock_wait_edges_80(pool: &Pool<MySql>) -> Result<Vec<LockWaitEdge>, Error> {
+        let query = r#"
+SELECT DISTINCT
+ r.trx_mysql_thread_id AS waiting_thread_id
+ , b.trx_mysql_thread_id AS blocking_thread_id
+FROM performance_schema.data_lock_waits AS w
+INNER JOIN information_schema.INNODB_TRX AS b ON b.trx_id = w.BLOCKING_ENGINE_TRANSACTION_ID
+INNER JOIN information_schema.INNODB_TRX AS r ON r.trx_id = w.REQUESTING_ENGINE_TRANSACTION_ID;
+    "#;
+
+        sqlx::query_as::<_, LockWaitEdge>(query)
+            .fetch_all(pool)
+            .await
+    }
+
+    // 获取所有正在执行的事务
+    pub async fn innodb_trxs(pool: &Pool<MySql>) -> Result<Vec<InnodbTrxInfo>, Error> {
+        let query = r#"
+SELECT
+ trx_mysql_thread_id AS thread_id
+ , trx_state
+ , TIMESTAMPDIFF(SECOND, trx_started, NOW()) AS trx_age
+ , trx_query
+FROM information_schema.INNODB_TRX;
+    "#;
+
+        sqlx::query_as::<_, InnodbTrxInfo>(query)
+            .fetch_all(pool)
+            .await
+    }
+
+    // 获取 max_connections, max_user_connections, Threads_connected, Threads_running, Max_used_connections
+    pub async fn get_connection_stats(pool: &Pool<MySql>) -> Result<ConnectionStats, Error> {
+        let variables = Self::query_dynamic(
+            pool,
+            "SHOW GLOBAL VARIABLES WHERE Variable_name IN ('max_connections', 'max_user_connections')",
+        )
+        .await?;
+        let status = Self::query_dynamic(
+            pool,
+            "SHOW GLOBAL STATUS WHERE Variable_name IN ('Threads_connected', 'Threads_running', 'Max_used_connections')",
+        )
+        .await?;
+
+        let mut stats = ConnectionStats::default();
+        for row in variables.rows.iter().chain(status.rows.iter()) {
+            let (name, value) = match (row.first(), row.get(1)) {
+                (Some(Some(name)), Some(Some(value))) => (name.to_lowercase(), value),
+                _ => continue,
+            };
+            let value = value.parse::<u64>().unwrap_or_default();
+            match name.as_str() {
+                "max_connections" => stats.max_connections = value,
+                "max_user_connections" => stats.max_user_connections = value,
+                "threads_connected" => stats.threads_connected = value,
+                "threads_running" => stats.threads_running = value,
+                "max_used_connections" => stats.max_used_connections = value,
+                _ => {}
+            }
+        }
+
+        Ok(stats)
+    }
+
+    // 执行列不固定的 sql, 使用文本协议执行, 可以执行 SHOW ENGINE INNODB STATUS 等语句
+    pub async fn query_dynamic(pool: &Pool<MySql>, query: &str) -> Result<DynamicRows, Error> {
+        let rows = pool.fetch_all(query).await?;
+
+        let mut dynamic_rows = DynamicRows::default();
+        if let Some(row) = rows.first() {
+            dynamic_rows.columns = row
+                .columns()
+                .iter()
+                .map(|column| column.name().to_string())
+                .collect();
+        }
+        dynamic_rows.rows = rows
+            .iter()
+            .map(|row| {
+                (0..row.columns().len())
+                    .map(|i| get_value_string(row, i))
+                    .collect()
+            })
+            .collect();
+
+        Ok(dynamic_rows)
+    }
+
     // 执行 show index 语句
     pub async fn show_index(
         pool: &Pool<MySql>,
@@ -59,3 +297,30 @@ FROM information_schema.PROCESSLIST;
             .await
     }
 }
+
+// 获取某一列的值并转化为字符串
+fn get_value_string(row: &MySqlRow, index: usize) -> Option<String> {
+    if let Ok(v) = row.try_get::<Option<String>, _>(index) {
+        return v;
+    }
+    if let Ok(v) = row.try_get::<Option<i64>, _>(index) {
+        return v.map(|v| v.to_string());
+    }
+    if let Ok(v) = row.try_get::<Option<u64>, _>(index) {
+        return v.map(|v| v.to_string());
+    }
+    if let Ok(v) = row.try_get::<Option<Decimal>, _>(index) {
+        return v.map(|v| v.to_string());
+    }
+    if let Ok(v) = row.try_get::<Option<f64>, _>(index) {
+        return v.map(|v| v.to_string());
+    }
+    if let Ok(v) = row.try_get::<Option<NaiveDateTime>, _>(index) {
+        return v.map(|v| v.to_string());
+    }
+    if let Ok(v) = row.try_get::<Option<Vec<u8>>, _>(index) {
+        return v.map(|v| String::from_utf8_lossy(&v).to_string());
+    }
+
+    None
+}
@@ -0,0 +1,252 @@
+use crate::models::ProcesslistHistory;
+use chrono::NaiveDateTime;
+use sqlx::{Error, MySql, Pool, QueryBuilder, Sqlite};
+
+// 每条 insert 语句最多写入多少行, 避免超过占位符数量限制
+const INSERT_BATCH_SIZE: usize = 1000;
+
+// 历史快照保存的位置: 本地 sqlite 文件或者 mysql 数据库
+pub enum HistoryDb {
+    Sqlite(Pool<Sqlite>),
+    MySql(Pool<MySql>),
+}
+
+impl HistoryDb {
+    pub async fn close(&self) {
+        match self {
+            HistoryDb::Sqlite(pool) => pool.close().await,
+            HistoryDb::MySql(pool) => pool.close().await,
+        }
+    }
+}
+
+// 两种数据库执行相同的语句, $pool 分别为 Pool<Sqlite> 和 Pool<MySql>
+macro_rules! with_pool {
+    ($db:expr, $pool:ident => $body:expr) => {
+        match $db {
+            HistoryDb::Sqlite($pool) => $body,
+            HistoryDb::MySql($pool) => $body,
+        }
+    };
+}
+
+// 查询历史快照的条件, 为空的条件不过滤
+#[derive(Debug, Clone, Default)]
+pub struct ProcesslistHistoryFilter {
+    pub host_port: String,
+    pub start_time: Option<NaiveDateTime>,
+    pub end_time: Option<NaiveDateTime>,
+    pub user: String,
+    pub sql: String, // info 包含的字符串
+    pub limit: u64,
+}
+
+pub struct ProcesslistHistoryDao;
+
+impl ProcesslistHistoryDao {
+    // 表不存在时创建
+    pub async fn create_table(db: &HistoryDb, table: &str) -> Result<(), Error> {
+        match db {
+            HistoryDb::Sqlite(pool) => {
+                let query = format!(
+                    r#"
+CREATE TABLE IF NOT EXISTS {table} (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    snapshot_time DATETIME NOT NULL,
+    host_port TEXT NOT NULL,
+    cluster_name TEXT,
+    processlist_id INTEGER,
+    user TEXT,
+    host TEXT,
+    db TEXT,
+    command TEXT,
+    time INTEGER,
+    state TEXT,
+    info TEXT,
+    digest TEXT
+)"#,
+                    table = table
+                );
+                sqlx::query(&query).execute(pool).await?;
+
+                let query = format!(
+                    "CREATE INDEX IF NOT EXISTS idx_{table}_time_host_port ON {table} (snapshot_time, host_port)",
+                    table = table
+                );
+                sqlx::query(&query).execute(pool).await?;
+            }
+            HistoryDb::MySql(pool) => {
+                let query = format!(
+                    r#"
+CREATE TABLE IF NOT EXISTS {table} (
+    id BIGINT NOT NULL AUTO_INCREMENT,
+    snapshot_time DATETIME NOT NULL,
+    host_port VARCHAR(64) NOT NULL,
+    cluster_name VARCHAR(128),
+    processlist_id BIGINT,
+    user VARCHAR(128),
+    host VARCHAR(255),
+    db VARCHAR(128),
+    command VARCHAR(32),
+    time BIGINT,
+    state VARCHAR(255),
+    info LONGTEXT,
+    digest VARCHAR(64),
+    PRIMARY KEY (id),
+    KEY idx_time_host_port (snapshot_time, host_port)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4"#,
+                    table = table
+                );
+                sqlx::query(&query).execute(pool).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn insert(
+        db: &HistoryDb,
+        table: &str,
+        records: &[ProcesslistHistory],
+    ) -> Result<(), Error> {
+        for chunk in records.chunks(INSERT_BATCH_SIZE) {
+            with_pool!(db, pool => {
+                let mut builder = QueryBuilder::new(format!(
+                    "INSERT INTO {table} (snapshot_time, host_port, cluster_name, processlist_id, user, host, db, command, time, state, info, digest) ",
+                    table = table
+                ));
+                builder.push_values(chunk, |mut b, record| {
+                    b.push_bind(record.snapshot_time)
+                        .push_bind(record.host_port.clone())
+                        .push_bind(record.cluster_name.clone())
+                        .push_bind(record.processlist_id)
+                        .push_bind(record.user.clone())
+                        .push_bind(record.host.clone())
+                        .push_bind(record.db.clone())
+                        .push_bind(record.command.clone())
+                        .push_bind(record.time)
+                        .push_bind(record.state.clone())
+                        .push_bind(record.info.clone())
+                        .push_bind(record.digest.clone());
+                });
+
+                builder.build().execute(pool).await.map(|_| ())
+            })?;
+        }
+
+        Ok(())
+    }
+
+    // 返回最新的 limit 条记录, 按快照时间正序
+    pub async fn find(
+        db: &HistoryDb,
+        table: &str,
+        filter: &ProcesslistHistoryFilter,
+    ) -> Result<Vec<ProcesslistHistory>, Error> {
+        let mut records = with_pool!(db, pool => {
+            let mut builder = QueryBuilder::new(format!(
+                "SELECT * FROM {table} WHERE 1 = 1",
+                table = table
+            ));
+            if !filter.host_port.is_empty() {
+                builder.push(" AND host_port = ").push_bind(filter.host_port.clone());
+            }
+            if let Some(start_time) = filter.start_time {
+                builder.push(" AND snapshot_time >= ").push_bind(start_time);
+            }
+            if let Some(end_time) = filter.end_time {
+                builder.push(" AND snapshot_time <= ").push_bind(end_time);
+            }
+            if !filter.user.is_empty() {
+                builder.push(" AND user = ").push_bind(filter.user.clone());
+            }
+            if !filter.sql.is_empty() {
+                builder
+                    .push(" AND info LIKE ")
+                    .push_bind(format!("%{sql}%", sql = &filter.sql));
+            }
+            builder
+                .push(" ORDER BY snapshot_time DESC, host_port DESC, processlist_id DESC LIMIT ")
+                .push_bind(filter.limit as i64);
+
+            builder
+                .build_query_as::<ProcesslistHistory>()
+                .fetch_all(pool)
+                .await
+        })?;
+        records.reverse();
+
+        Ok(records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dao::processlist_history_dao::{
+        HistoryDb, ProcesslistHistoryDao, ProcesslistHistoryFilter,
+    };
+    use crate::models::ProcesslistHistory;
+    use crate::rdbc;
+    use chrono::NaiveDateTime;
+
+    #[tokio::test]
+    async fn test_sqlite_history() {
+        let file = std::env::temp_dir().join("mysql_tool_rs_test_history.db");
+        let _ = std::fs::remove_file(&file);
+        let db = HistoryDb::Sqlite(
+            rdbc::get_sqlite_db(&file.to_string_lossy(), false)
+                .await
+                .unwrap(),
+        );
+        ProcesslistHistoryDao::create_table(&db, "processlist_history")
+            .await
+            .unwrap();
+
+        let records = (0..3)
+            .map(|i| ProcesslistHistory {
+                snapshot_time: Some(
+                    NaiveDateTime::parse_from_str(
+                        &format!("2023-01-31 16:00:0{i}"),
+                        "%Y-%m-%d %H:%M:%S",
+                    )
+                    .unwrap(),
+                ),
+                host_port: Some(String::from("127.0.0.1:3306")),
+                processlist_id: Some(i),
+                user: Some(if i == 1 { "app" } else { "root" }.to_string()),
+                info: Some(format!("select * from t{i}")),
+                time: Some(i),
+                ..Default::default()
+            })
+            .collect::<Vec<ProcesslistHistory>>();
+        ProcesslistHistoryDao::insert(&db, "processlist_history", &records)
+            .await
+            .unwrap();
+
+        let mut filter = ProcesslistHistoryFilter {
+            host_port: String::from("127.0.0.1:3306"),
+            start_time: records[1].snapshot_time,
+            limit: 10,
+            ..Default::default()
+        };
+        let found = ProcesslistHistoryDao::find(&db, "processlist_history", &filter)
+            .await
+            .unwrap();
+        println!("{:?}", found);
+        assert_eq!(
+            found.iter().map(|v| v.processlist_id).collect::<Vec<_>>(),
+            vec![Some(1), Some(2)]
+        );
+
+        filter.user = String::from("app");
+        filter.sql = String::from("t1");
+        let found = ProcesslistHistoryDao::find(&db, "processlist_history", &filter)
+            .await
+            .unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].snapshot_time, records[1].snapshot_time);
+
+        db.close().await;
+        let _ = std::fs::remove_file(&file);
+    }
+}
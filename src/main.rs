@@ -29,6 +29,23 @@ async fn main() -> Result<(), CustomError> {
             init_log("", &cfg.log_level)?;
             core::show_index::run(cfg).await
         }
+        Commands::LockWaits(cfg) => {
+            // 只打印到控制台
+            init_log("", &cfg.log_level)?;
+            core::lock_waits::run(cfg).await
+        }
+        Commands::AnalyzeProcesslist(cfg) => {
+            // 只打印到控制台
+            init_log("", &cfg.log_level)?;
+            core::analyze_processlist::run(cfg).await
+        }
+        Commands::ProcesslistHistory(cfg) => {
+            // 只打印到控制台
+            init_log("", &cfg.log_level)?;
+            core::processlist_history::run(cfg).await
+        }
+        // 全屏界面, 不初始化日志, 错误信息显示在界面的状态栏
+        Commands::Top(cfg) => core::top::run(cfg).await,
     }
 }
 
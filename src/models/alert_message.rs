@@ -0,0 +1,17 @@
+use crate::models::FingerprintSummary;
+use serde::{Deserialize, Serialize};
+
+pub const ALERT_STATUS_FIRING: &str = "firing";
+pub const ALERT_STATUS_RESOLVED: &str = "resolved";
+
+// 告警消息, generic webhook 直接发送该结构的 json
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct AlertMessage {
+    pub status: String, // firing: 超过阈值, resolved: 恢复
+    pub time: String,
+    pub instance: String, // host:port
+    pub cluster_name: Option<String>,
+    pub active_threads: usize, // 满足 --threshold-filter 的线程数
+    pub threshold: u64,
+    pub top_fingerprints: Vec<FingerprintSummary>,
+}
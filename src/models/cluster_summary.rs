@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+
+// 合并输出时一个集群的汇总
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClusterSummary {
+    pub instance_cnt: usize,
+    pub error_cnt: usize,    // 本次获取 processlist 失败的实例数
+    pub incident_cnt: usize, // 超过 --print-cnt-threshold 的实例数
+    pub total: usize,
+    pub filter_sleep: usize,
+    pub threshold: usize, // 满足 --threshold-filter 的线程数
+}
+
+// 合并输出时一个实例的小计
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct InstanceSummary {
+    pub role: String,
+    pub total: usize,
+    pub filter_sleep: usize,
+    pub threshold: usize,
+    pub error: Option<String>,
+}
@@ -0,0 +1,25 @@
+use crate::models::GroupSummary;
+use serde::{Deserialize, Serialize};
+
+// 链接相关的全局变量和状态
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct ConnectionStats {
+    pub max_connections: u64,
+    pub max_user_connections: u64, // 0 不限制
+    pub threads_connected: u64,
+    pub threads_running: u64,
+    pub max_used_connections: u64,
+}
+
+// 链接接近饱和时记录的信息
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct ConnectionSaturation {
+    #[serde(flatten)]
+    pub stats: ConnectionStats,
+    pub connected_pct: f64, // Threads_connected 占 max_connections 的百分比
+    pub running_pct: f64,   // Threads_running 占 max_connections 的百分比
+    pub max_used_pct: f64,  // Max_used_connections 占 max_connections 的百分比
+    pub reasons: Vec<String>,
+    pub top_users: Vec<GroupSummary>,      // 链接数最多的用户
+    pub top_client_ips: Vec<GroupSummary>, // 链接数最多的客户端
+}
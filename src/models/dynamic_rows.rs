@@ -0,0 +1,8 @@
+use serde::{Deserialize, Serialize};
+
+// 列不固定的查询结果, 所有值都转化为字符串
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct DynamicRows {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Option<String>>>,
+}
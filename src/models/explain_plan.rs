@@ -0,0 +1,20 @@
+use crate::models::DynamicRows;
+use serde::{Deserialize, Serialize};
+
+pub const EXPLAIN_METHOD_FOR_CONNECTION: &str = "for_connection";
+pub const EXPLAIN_METHOD_STATEMENT: &str = "statement";
+
+// 输出快照时获取的执行计划
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct ExplainPlan {
+    pub processlist_id: Option<u64>,
+    pub user: Option<String>,
+    pub db: Option<String>,
+    pub time: Option<i32>,
+    pub fingerprint: String,
+    pub sql: String,
+    pub method: String, // for_connection: EXPLAIN FOR CONNECTION, statement: 在 DB 上 EXPLAIN sql
+    pub error: Option<String>,
+    #[serde(flatten)]
+    pub plan: DynamicRows,
+}
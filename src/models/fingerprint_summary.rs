@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+// 同一个 sql 指纹的汇总信息
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct FingerprintSummary {
+    pub fingerprint: String,
+    pub count: u64,
+    pub max_time: i64, // 最大执行时间(单位:s)
+    pub avg_time: f64, // 平均执行时间(单位:s)
+    pub users: Vec<String>,
+    pub dbs: Vec<String>,
+}
@@ -0,0 +1,9 @@
+use serde::{Deserialize, Serialize};
+
+// 按某个字段分组的线程数
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GroupSummary {
+    pub group_by: String, // 分组字段: user, client_ip, db, command, state
+    pub value: String,
+    pub count: u64,
+}
@@ -2,7 +2,7 @@ use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 use sqlx;
 
-#[derive(Deserialize, Serialize, Debug, sqlx::FromRow, Clone)]
+#[derive(Deserialize, Serialize, Debug, sqlx::FromRow, Clone, Default)]
 pub struct Instance {
     #[sqlx(default)]
     pub id: Option<i64>,
@@ -48,4 +48,8 @@ pub struct Instance {
     pub vpcgw_vip_port: Option<String>, // VPC GateWay VIP和端口
     #[sqlx(default)]
     pub set_name: Option<String>, // set 名称
+    #[sqlx(default)]
+    pub cluster_name: Option<String>, // 集群名称, 非表字段, 获取实例后通过集群信息填充
+    #[sqlx(default)]
+    pub business_line: Option<String>, // 业务线, 非表字段, 获取实例后通过集群信息填充
 }
@@ -0,0 +1,13 @@
+use crate::models::ShowProcesslistInfo;
+use serde::{Deserialize, Serialize};
+
+// kill 审计记录, 每 kill 一个线程记录一条
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct KillAuditRecord {
+    pub time: String,
+    pub instance: String, // host:port
+    pub dry_run: bool,
+    pub statement: String, // 执行的 kill 语句
+    pub result: String,    // 执行结果: dry-run, success, 失败原因
+    pub processlist: ShowProcesslistInfo,
+}
@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+use sqlx;
+
+// 锁等待关系, waiting 线程在等待 blocking 线程持有的锁
+#[derive(Deserialize, Serialize, Debug, sqlx::FromRow, Clone)]
+pub struct LockWaitEdge {
+    #[sqlx(default)]
+    pub waiting_thread_id: Option<u64>,
+    #[sqlx(default)]
+    pub blocking_thread_id: Option<u64>,
+}
+
+// information_schema.INNODB_TRX 中的事务信息
+#[derive(Deserialize, Serialize, Debug, sqlx::FromRow, Clone)]
+pub struct InnodbTrxInfo {
+    #[sqlx(default)]
+    pub thread_id: Option<u64>,
+    #[sqlx(default)]
+    pub trx_state: Option<String>,
+    #[sqlx(default)]
+    pub trx_age: Option<i64>, // 事务已经执行多久(单位:s)
+    #[sqlx(default)]
+    pub trx_query: Option<String>,
+}
+
+// 锁等待中一个线程的信息, 由 processlist 和 INNODB_TRX 合并而来
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct LockWaitThread {
+    pub thread_id: u64,
+    pub user: Option<String>,
+    pub host: Option<String>,
+    pub db: Option<String>,
+    pub trx_state: Option<String>,
+    pub trx_age: Option<i64>,
+    pub sql: Option<String>,
+}
+
+// 一条锁等待
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct LockWait {
+    pub blocking: LockWaitThread,
+    pub waiting: LockWaitThread,
+}
@@ -1,9 +1,35 @@
+pub mod alert_message;
+pub mod cluster_summary;
+pub mod connection_saturation;
+pub mod dynamic_rows;
+pub mod explain_plan;
+pub mod fingerprint_summary;
+pub mod group_summary;
 pub mod instance;
+pub mod kill_audit_record;
+pub mod lock_wait;
 pub mod meta_cluster;
+pub mod processlist_history;
+pub mod processlist_incident;
+pub mod query_event;
 pub mod show_index_info;
 pub mod show_processlist_info;
+pub mod snapshot_record;
 
+pub use alert_message::AlertMessage;
+pub use cluster_summary::{ClusterSummary, InstanceSummary};
+pub use connection_saturation::{ConnectionSaturation, ConnectionStats};
+pub use dynamic_rows::DynamicRows;
+pub use explain_plan::ExplainPlan;
+pub use fingerprint_summary::FingerprintSummary;
+pub use group_summary::GroupSummary;
 pub use instance::Instance;
+pub use kill_audit_record::KillAuditRecord;
+pub use lock_wait::{InnodbTrxInfo, LockWait, LockWaitEdge, LockWaitThread};
 pub use meta_cluster::MetaCluster;
+pub use processlist_history::ProcesslistHistory;
+pub use processlist_incident::ProcesslistIncident;
+pub use query_event::QueryEvent;
 pub use show_index_info::ShowIndexInfo;
 pub use show_processlist_info::ShowProcesslistInfo;
+pub use snapshot_record::SnapshotRecord;
@@ -0,0 +1,33 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx;
+
+// 历史快照中的一个线程, 每次输出快照时每个线程保存一行
+#[derive(Deserialize, Serialize, Debug, sqlx::FromRow, Clone, Default)]
+pub struct ProcesslistHistory {
+    #[sqlx(default)]
+    #[serde(with = "crate::utils::time::opt_datetime_format_normal")]
+    pub snapshot_time: Option<NaiveDateTime>, // 快照时间
+    #[sqlx(default)]
+    pub host_port: Option<String>, // 实例 host:port
+    #[sqlx(default)]
+    pub cluster_name: Option<String>,
+    #[sqlx(default)]
+    pub processlist_id: Option<i64>, // processlist 中的 ID
+    #[sqlx(default)]
+    pub user: Option<String>,
+    #[sqlx(default)]
+    pub host: Option<String>, // 客户端 host:port
+    #[sqlx(default)]
+    pub db: Option<String>,
+    #[sqlx(default)]
+    pub command: Option<String>,
+    #[sqlx(default)]
+    pub time: Option<i64>,
+    #[sqlx(default)]
+    pub state: Option<String>,
+    #[sqlx(default)]
+    pub info: Option<String>,
+    #[sqlx(default)]
+    pub digest: Option<String>,
+}
@@ -0,0 +1,19 @@
+use crate::models::{FingerprintSummary, GroupSummary};
+use serde::{Deserialize, Serialize};
+
+// 离线分析 processlist 输出文件得到的一次事件, 即一段连续超过阈值的时间
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ProcesslistIncident {
+    pub instance: String, // host:port
+    pub cluster_name: Option<String>,
+    pub start_time: String,
+    pub end_time: String,
+    pub duration: i64,    // 持续时间(单位:s)
+    pub snapshots: usize, // 超过阈值的快照数
+    pub peak_threads: usize,
+    pub peak_total: usize,
+    pub peak_time: String,
+    pub top_fingerprints: Vec<FingerprintSummary>,
+    pub top_users: Vec<GroupSummary>,
+    pub top_client_ips: Vec<GroupSummary>,
+}
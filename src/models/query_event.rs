@@ -0,0 +1,20 @@
+use serde::{Deserialize, Serialize};
+
+pub const QUERY_EVENT_STARTED: &str = "query_started";
+pub const QUERY_EVENT_FINISHED: &str = "query_finished";
+
+// 通过多次 processlist 观察到的 sql 开始/结束事件
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct QueryEvent {
+    pub event: String, // query_started, query_finished
+    pub id: u64,
+    pub user: Option<String>,
+    pub host: Option<String>,
+    pub db: Option<String>,
+    pub command: Option<String>,
+    pub info: String,
+    pub start_time: String,     // 通过 TIME 推算出来的开始时间
+    pub first_seen: String,     // 第一次观察到的时间
+    pub last_seen: String,      // 最后一次观察到的时间
+    pub observed_duration: i64, // 观察到的执行时长(单位:s), 即最后一次观察到的 TIME
+}
@@ -1,7 +1,7 @@
 use serde::{Deserialize, Serialize};
 use sqlx;
 
-#[derive(Deserialize, Serialize, Debug, sqlx::FromRow, Clone)]
+#[derive(Deserialize, Serialize, Debug, sqlx::FromRow, Clone, Default)]
 pub struct ShowProcesslistInfo {
     #[sqlx(default, rename = "ID")]
     pub id: Option<u64>,
@@ -19,4 +19,14 @@ pub struct ShowProcesslistInfo {
     pub state: Option<String>,
     #[sqlx(default, rename = "INFO")]
     pub info: Option<String>,
+    // 以下字段只有从 performance_schema 获取时才有值
+    #[sqlx(default, rename = "THREAD_ID")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thread_id: Option<u64>,
+    #[sqlx(default, rename = "DIGEST")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digest: Option<String>,
+    #[sqlx(default, rename = "EXECUTION_ENGINE")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub execution_engine: Option<String>, // 8.0.29 开始才有
 }
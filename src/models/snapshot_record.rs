@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+
+pub const RECORD_TYPE_PROCESSLIST: &str = "processlist";
+pub const RECORD_TYPE_FINGERPRINT_SUMMARY: &str = "fingerprint_summary";
+pub const RECORD_TYPE_QUERY_EVENT: &str = "query_event";
+pub const RECORD_TYPE_DIAGNOSTIC: &str = "diagnostic";
+pub const RECORD_TYPE_LOCK_WAIT: &str = "lock_wait";
+pub const RECORD_TYPE_GROUP_SUMMARY: &str = "group_summary";
+pub const RECORD_TYPE_CLUSTER_SUMMARY: &str = "cluster_summary";
+pub const RECORD_TYPE_INSTANCE_SUMMARY: &str = "instance_summary";
+pub const RECORD_TYPE_CONNECTION_SATURATION: &str = "connection_saturation";
+pub const RECORD_TYPE_EXPLAIN: &str = "explain";
+
+// 快照结构化输出(jsonl)的一行记录, 通过 record_type 区分 data 的类型
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SnapshotRecord<T> {
+    pub record_type: String,
+    pub instance: String, // host:port
+    pub cluster_name: Option<String>,
+    pub capture_time: String, // 采集时间
+    // 超过阈值前保存的快照
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pre_trigger: bool,
+    #[serde(flatten)]
+    pub data: T,
+}
@@ -1,4 +1,7 @@
 pub mod mysql;
+pub mod sqlite;
 
 pub use mysql::get_db;
 pub use mysql::get_db_by_default;
+pub use mysql::get_db_by_default_with_max_connections;
+pub use sqlite::get_sqlite_db;
@@ -4,6 +4,14 @@ use sqlx::{ConnectOptions, Executor, MySql, Pool};
 use std::str::FromStr;
 
 pub async fn get_db(dsn: &str, is_log: bool) -> Result<Pool<MySql>, sqlx::Error> {
+    get_db_with_max_connections(dsn, is_log, 1).await
+}
+
+pub async fn get_db_with_max_connections(
+    dsn: &str,
+    is_log: bool,
+    max_connections: u32,
+) -> Result<Pool<MySql>, sqlx::Error> {
     //  for MySQL, use MySqlPoolOptions::new()
     let mut connection_options = MySqlConnectOptions::from_str(dsn)?;
     if is_log {
@@ -12,7 +20,7 @@ pub async fn get_db(dsn: &str, is_log: bool) -> Result<Pool<MySql>, sqlx::Error>
         connection_options.disable_statement_logging();
     }
     MySqlPoolOptions::new()
-        .max_connections(1)
+        .max_connections(max_connections)
         .after_connect(|conn, _meta| {
             Box::pin(async move {
                 let _ = conn.execute("SET time_zone='SYSTEM'").await;
@@ -30,6 +38,19 @@ pub async fn get_db_by_default(
     password: &str,
     database: &str,
     is_log: bool,
+) -> Result<Pool<MySql>, sqlx::Error> {
+    get_db_by_default_with_max_connections(host, port, username, password, database, is_log, 1)
+        .await
+}
+
+pub async fn get_db_by_default_with_max_connections(
+    host: &str,
+    port: i16,
+    username: &str,
+    password: &str,
+    database: &str,
+    is_log: bool,
+    max_connections: u32,
 ) -> Result<Pool<MySql>, sqlx::Error> {
     let dsn = format!(
         "mysql://{username}:{password}@{host}:{port}/{database}",
@@ -40,5 +61,5 @@ pub async fn get_db_by_default(
         database = database,
     );
 
-    get_db(&dsn, is_log).await
+    get_db_with_max_connections(&dsn, is_log, max_connections).await
 }
@@ -0,0 +1,21 @@
+use log::LevelFilter;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions};
+use sqlx::{ConnectOptions, Pool, Sqlite};
+use std::str::FromStr;
+
+// 打开本地 sqlite 文件, 不存在时自动创建. sqlite 同时只能有一个写入, 只使用一个链接
+pub async fn get_sqlite_db(file: &str, is_log: bool) -> Result<Pool<Sqlite>, sqlx::Error> {
+    let mut connection_options = SqliteConnectOptions::from_str(file)?
+        .create_if_missing(true)
+        .journal_mode(SqliteJournalMode::Wal);
+    if is_log {
+        connection_options.log_statements(LevelFilter::Info);
+    } else {
+        connection_options.disable_statement_logging();
+    }
+
+    SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect_with(connection_options)
+        .await
+}
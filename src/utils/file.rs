@@ -1,4 +1,6 @@
 use std::fs;
+use std::io::Write;
+use std::path::Path;
 
 #[allow(dead_code)]
 pub fn create_dir(dir: &str) -> std::io::Result<()> {
@@ -8,3 +10,18 @@ pub fn create_dir(dir: &str) -> std::io::Result<()> {
 
     fs::create_dir_all(dir)
 }
+
+// 追加内容到文件, 文件或目录不存在则创建
+pub fn append_file(file_path: &str, data: &str) -> std::io::Result<()> {
+    if let Some(parent) = Path::new(file_path).parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent)?;
+        }
+    }
+
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(file_path)?;
+    file.write_all(data.as_bytes())
+}
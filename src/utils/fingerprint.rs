@@ -0,0 +1,180 @@
+use regex::Regex;
+use std::sync::OnceLock;
+
+// 生成 sql 指纹: 去掉注释, 字符串/数字替换成 ?, IN 列表和多行 VALUES 折叠, 合并空白并转小写
+pub fn fingerprint(sql: &str) -> String {
+    let chars = sql.chars().collect::<Vec<char>>();
+    let mut result = String::with_capacity(sql.len());
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+
+        // 块注释 /* */
+        if c == '/' && next == Some('*') {
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                i += 1;
+            }
+            i += 2;
+            push_space(&mut result);
+            continue;
+        }
+
+        // 行注释 -- 和 #
+        if (c == '-' && next == Some('-')) || c == '#' {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            push_space(&mut result);
+            continue;
+        }
+
+        // 字符串
+        if c == '\'' || c == '"' {
+            i = skip_quoted(&chars, i, c);
+            result.push('?');
+            continue;
+        }
+
+        // 反引号标识符原样保留
+        if c == '`' {
+            let end = skip_quoted(&chars, i, c);
+            chars[i..end.min(chars.len())]
+                .iter()
+                .for_each(|ch| result.extend(ch.to_lowercase()));
+            i = end;
+            continue;
+        }
+
+        // 标识符, 可能包含数字, 例如: t1, col_2
+        if c.is_alphabetic() || c == '_' || c == '$' || c == '@' {
+            while i < chars.len()
+                && (chars[i].is_alphanumeric()
+                    || chars[i] == '_'
+                    || chars[i] == '$'
+                    || chars[i] == '@')
+            {
+                result.extend(chars[i].to_lowercase());
+                i += 1;
+            }
+            continue;
+        }
+
+        // 数字: 整数, 小数, 科学计数, 十六进制. 负数的负号保留由后面的替换处理
+        if c.is_ascii_digit() || (c == '.' && next.is_some_and(|n| n.is_ascii_digit())) {
+            while i < chars.len()
+                && (chars[i].is_ascii_alphanumeric()
+                    || chars[i] == '.'
+                    || ((chars[i] == '+' || chars[i] == '-')
+                        && matches!(chars[i - 1], 'e' | 'E')))
+            {
+                i += 1;
+            }
+            result.push('?');
+            continue;
+        }
+
+        if c.is_whitespace() {
+            push_space(&mut result);
+            i += 1;
+            continue;
+        }
+
+        result.push(c);
+        i += 1;
+    }
+
+    collapse(result.trim().trim_end_matches(';').trim())
+}
+
+// 跳过引号包起来的内容, 返回结束引号的下一个位置
+fn skip_quoted(chars: &[char], start: usize, quote: char) -> usize {
+    let mut i = start + 1;
+    while i < chars.len() {
+        if chars[i] == '\\' {
+            i += 2;
+            continue;
+        }
+        if chars[i] == quote {
+            // 两个连续引号是转义
+            if chars.get(i + 1) == Some(&quote) {
+                i += 2;
+                continue;
+            }
+            return i + 1;
+        }
+        i += 1;
+    }
+
+    i
+}
+
+fn push_space(result: &mut String) {
+    if !result.is_empty() && !result.ends_with(' ') {
+        result.push(' ');
+    }
+}
+
+struct CollapseRegexes {
+    negative: Regex,
+    punctuation: Regex,
+    operator: Regex,
+    in_list: Regex,
+    values_list: Regex,
+}
+
+fn collapse_regexes() -> &'static CollapseRegexes {
+    static REGEXES: OnceLock<CollapseRegexes> = OnceLock::new();
+    REGEXES.get_or_init(|| CollapseRegexes {
+        negative: Regex::new(r"([=(,<>]|\b(?:in|values|and|or|between|then|else)) ?- ?\?").unwrap(),
+        punctuation: Regex::new(r" ?([(,]) ?| (\))").unwrap(),
+        operator: Regex::new(r" ?(<=>|[<>!]=|=) ?").unwrap(),
+        in_list: Regex::new(r"\bin\(\?(?:, \?)*\)").unwrap(),
+        values_list: Regex::new(r"\b(values?)\([^()]*\)(?:, \([^()]*\))*").unwrap(),
+    })
+}
+
+// 折叠负数, 统一标点两边的空白, 折叠 IN 列表和多行 VALUES
+fn collapse(sql: &str) -> String {
+    let regexes = collapse_regexes();
+    let sql = regexes.negative.replace_all(sql, "$1 ?");
+    let sql = regexes.punctuation.replace_all(&sql, |caps: &regex::Captures| {
+        match caps.get(1).map(|m| m.as_str()) {
+            Some(",") => String::from(", "),
+            Some(v) => v.to_string(),
+            None => String::from(")"),
+        }
+    });
+    let sql = regexes.operator.replace_all(&sql, " $1 ");
+    let sql = regexes.in_list.replace_all(&sql, "in (?+)");
+    let sql = regexes.values_list.replace_all(&sql, "$1 (?+)");
+
+    sql.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::fingerprint::fingerprint;
+
+    #[test]
+    fn test_fingerprint() {
+        assert_eq!(
+            fingerprint("SELECT  *\n FROM t1 WHERE id = 10 AND name='abc' -- comment"),
+            "select * from t1 where id = ? and name = ?"
+        );
+        assert_eq!(
+            fingerprint("select * from `T1` where id in (1, 2,3) and b = -1.5e3;"),
+            "select * from `t1` where id in (?+) and b = ?"
+        );
+        assert_eq!(
+            fingerprint("INSERT INTO t1(a, b) VALUES (1, 'a'), (2, 'b\\'c')"),
+            "insert into t1(a, b) values (?+)"
+        );
+        assert_eq!(
+            fingerprint("/* app */ UPDATE t2 SET c = \"x\" WHERE id IN(5)"),
+            "update t2 set c = ? where id in (?+)"
+        );
+    }
+}
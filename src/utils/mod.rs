@@ -1,4 +1,6 @@
 pub mod file;
+pub mod fingerprint;
 pub mod peep;
+pub mod rotate_file;
 pub mod string;
 pub mod time;
@@ -0,0 +1,205 @@
+use crate::utils;
+use chrono::{DateTime, Local};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+const ROTATE_SUFFIX_FMT: &str = "%Y%m%d%H%M%S";
+const GZIP_EXT: &str = "gz";
+
+// 文件滚动策略
+#[derive(Debug, Clone, Default)]
+pub struct RotatePolicy {
+    pub max_size: u64,     // 文件超过多少字节滚动, 0 不按大小滚动
+    pub daily: bool,       // 跨天滚动
+    pub compress: bool,    // 滚动后的文件是否使用 gzip 压缩
+    pub keep_files: usize, // 最多保留多少个历史文件, 0 不限制
+    pub keep_days: u64,    // 历史文件最多保留多少天, 0 不限制
+}
+
+// 追加内容到文件, 需要时先滚动文件.
+// is_archive 用来判断同目录下的文件是否是该文件的历史文件, 用于压缩和清理
+pub async fn append_file_rotate<F>(
+    file_path: &str,
+    data: &str,
+    policy: &RotatePolicy,
+    is_archive: F,
+) -> io::Result<()>
+where
+    F: Fn(&str) -> bool + Send + 'static,
+{
+    let path = Path::new(file_path);
+    if need_rotate(path, data.len() as u64, policy)? {
+        fs::rename(path, get_rotate_path(file_path))?;
+    }
+
+    let is_new_file = !path.exists();
+    utils::file::append_file(file_path, data)?;
+
+    // 新文件说明刚滚动过(或者按日期命名的文件跨天了), 处理历史文件.
+    // 遍历目录和 gzip 压缩比较耗时, 放到阻塞线程池中执行
+    if is_new_file {
+        let archive_path = path.to_path_buf();
+        let policy = policy.clone();
+        let result =
+            tokio::task::spawn_blocking(move || archive(&archive_path, &policy, is_archive))
+                .await
+                .unwrap_or_else(|e| Err(io::Error::other(e)));
+        if let Err(e) = result {
+            log::error!(
+                "处理历史文件失败. 文件: {file_path}. {e}",
+                file_path = file_path,
+                e = e
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn need_rotate(path: &Path, data_len: u64, policy: &RotatePolicy) -> io::Result<bool> {
+    let metadata = match fs::metadata(path) {
+        Ok(v) => v,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e),
+    };
+
+    if policy.daily {
+        let modified = DateTime::<Local>::from(metadata.modified()?).date_naive();
+        if modified != Local::now().date_naive() {
+            return Ok(true);
+        }
+    }
+
+    if policy.max_size > 0 && metadata.len() > 0 && metadata.len() + data_len > policy.max_size {
+        return Ok(true);
+    }
+
+    Ok(false)
+}
+
+// 滚动后的文件名: {文件名}.{时间}, 同一秒多次滚动再加上序号
+fn get_rotate_path(file_path: &str) -> String {
+    let rotate_path = format!(
+        "{file_path}.{time}",
+        file_path = file_path,
+        time = utils::time::now_str(ROTATE_SUFFIX_FMT)
+    );
+
+    let mut index = 0;
+    let mut result = rotate_path.clone();
+    while Path::new(&result).exists() || Path::new(&format!("{result}.{GZIP_EXT}")).exists() {
+        index += 1;
+        result = format!("{rotate_path}-{index}");
+    }
+
+    result
+}
+
+// 压缩并清理历史文件
+fn archive<F>(path: &Path, policy: &RotatePolicy, is_archive: F) -> io::Result<()>
+where
+    F: Fn(&str) -> bool,
+{
+    let dir = match path.parent() {
+        Some(v) if !v.as_os_str().is_empty() => v,
+        _ => Path::new("."),
+    };
+    let file_name = path.file_name().unwrap_or_default();
+
+    let mut archives = Vec::<(std::path::PathBuf, SystemTime)>::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if name == file_name || !entry.file_type()?.is_file() {
+            continue;
+        }
+        if !is_archive(&name.to_string_lossy()) {
+            continue;
+        }
+
+        let mut archive_path = entry.path();
+        if policy.compress && archive_path.extension().unwrap_or_default() != GZIP_EXT {
+            archive_path = compress_file(&archive_path)?;
+        }
+        let modified = fs::metadata(&archive_path)?.modified()?;
+        archives.push((archive_path, modified));
+    }
+
+    // 按修改时间从新到旧, 超过保留天数或者保留个数的删除
+    archives.sort_by_key(|a| std::cmp::Reverse(a.1));
+    let now = SystemTime::now();
+    let keep_duration = Duration::from_secs(policy.keep_days * 24 * 60 * 60);
+    for (i, (archive_path, modified)) in archives.iter().enumerate() {
+        let is_expired = policy.keep_days > 0
+            && now.duration_since(*modified).unwrap_or_default() > keep_duration;
+        let is_overflow = policy.keep_files > 0 && i >= policy.keep_files;
+        if is_expired || is_overflow {
+            fs::remove_file(archive_path)?;
+        }
+    }
+
+    Ok(())
+}
+
+// gzip 压缩文件, 压缩后删除原文件, 保留原文件的修改时间
+fn compress_file(path: &Path) -> io::Result<std::path::PathBuf> {
+    let gz_path =
+        std::path::PathBuf::from(format!("{path}.{GZIP_EXT}", path = path.to_string_lossy()));
+    let modified = fs::metadata(path)?.modified()?;
+
+    let mut src = fs::File::open(path)?;
+    let gz_file = fs::File::create(&gz_path)?;
+    let mut encoder = GzEncoder::new(gz_file, Compression::default());
+    io::copy(&mut src, &mut encoder)?;
+    let gz_file = encoder.finish()?;
+    gz_file.set_modified(modified)?;
+
+    fs::remove_file(path)?;
+
+    Ok(gz_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::rotate_file::{append_file_rotate, RotatePolicy};
+    use std::fs;
+
+    #[tokio::test]
+    async fn test_append_file_rotate() {
+        let dir = std::env::temp_dir().join(format!("rotate_file_test_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let file_path = dir.join("127.0.0.1_3306.txt");
+        let file_path = file_path.to_str().unwrap();
+
+        let policy = RotatePolicy {
+            max_size: 10,
+            compress: true,
+            keep_files: 2,
+            ..Default::default()
+        };
+        for _ in 0..5 {
+            append_file_rotate(file_path, "0123456789", &policy, |name| {
+                name.starts_with("127.0.0.1_3306.txt.")
+            })
+            .await
+            .unwrap();
+        }
+
+        let mut names = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect::<Vec<String>>();
+        names.sort();
+        println!("{:?}", names);
+
+        assert_eq!(names.len(), 3);
+        assert_eq!(names[0], "127.0.0.1_3306.txt");
+        assert!(names[1..].iter().all(|name| name.ends_with(".gz")));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
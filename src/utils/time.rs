@@ -2,6 +2,10 @@ use chrono::{Duration, Local, NaiveDateTime, TimeZone};
 use std::ops::Add;
 
 pub const NORMAL_FMT: &str = "%Y-%m-%d %H:%M:%S";
+// 快照时间精确到毫秒, 采集间隔小于 1 秒时区分同一秒内的快照
+pub const MILLIS_FMT: &str = "%Y-%m-%d %H:%M:%S%.3f";
+// 解析时小数部分可选, 兼容 NORMAL_FMT 和 MILLIS_FMT
+pub const OPTIONAL_FRACTION_FMT: &str = "%Y-%m-%d %H:%M:%S%.f";
 #[allow(dead_code)]
 pub const NORMAL_ZERO_TIME_FMT: &str = "%Y-%m-%d 00:00:00";
 #[allow(dead_code)]
@@ -26,6 +30,10 @@ pub fn now_datetime() -> NaiveDateTime {
     return Local::now().naive_local();
 }
 
+pub fn now_timestamp() -> i64 {
+    Local::now().timestamp()
+}
+
 #[allow(dead_code)]
 pub fn now_str(format: &str) -> String {
     return Local::now().format(format).to_string();